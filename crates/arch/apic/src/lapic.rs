@@ -1,9 +1,23 @@
 //! Provides ways to interact with the Local APIC of the current CPU.
 
-use nd_x86_64::{PhysAddr, VirtAddr};
+use bitflags::bitflags;
+use nd_x86_64::{CpuidFeature, Msr, PhysAddr, VirtAddr};
 
-/// The address of the `IA32_APIC_BASE` MSR.
-pub const IA32_APIC_BASE: u32 = 0x1B;
+/// Returns whether the local APIC timer supports the [`TimerMode::TscDeadline`] mode.
+///
+/// This is reported by bit 24 of ECX for the CPUID leaf `0x1`.
+#[inline]
+pub fn supports_tsc_deadline() -> bool {
+    nd_x86_64::has_feature(CpuidFeature::TscDeadline)
+}
+
+/// Returns whether the local APIC supports the x2APIC mode.
+///
+/// This is reported by bit 21 of ECX for the CPUID leaf `0x1`.
+#[inline]
+pub fn supports_x2apic() -> bool {
+    nd_x86_64::has_feature(CpuidFeature::X2Apic)
+}
 
 /// Returns the base address of the local XAPIC.
 ///
@@ -11,7 +25,7 @@ pub const IA32_APIC_BASE: u32 = 0x1B;
 #[inline(always)]
 #[allow(clippy::missing_safety_doc)]
 pub unsafe fn get_xapic_base() -> PhysAddr {
-    unsafe { nd_x86_64::rdmsr(IA32_APIC_BASE) & 0xFFFFF000 }
+    unsafe { Msr::IA32_APIC_BASE.read() & 0xFFFFF000 }
 }
 
 /// Sets the base address of the local XAPIC.
@@ -20,7 +34,7 @@ pub unsafe fn get_xapic_base() -> PhysAddr {
 #[inline(always)]
 #[allow(clippy::missing_safety_doc)]
 pub unsafe fn set_xapic_base(base: PhysAddr) {
-    unsafe { nd_x86_64::wrmsr(IA32_APIC_BASE, base) };
+    unsafe { Msr::IA32_APIC_BASE.write(base) };
 }
 
 /// Hardware-enables the local APIC by reloading the `IA32_APIC_BASE` MSR.
@@ -94,7 +108,78 @@ pub enum TimerMode {
     /// Periodic mode.
     Periodic = 1,
     /// TSC-deadline mode.
-    Deadline = 2,
+    ///
+    /// In this mode, the timer fires once the *Time-Stamp Counter* reaches the value written to
+    /// the `IA32_TSC_DEADLINE` MSR (see [`XApic::arm_tsc_deadline`]). The initial count register
+    /// is ignored.
+    ///
+    /// This mode is only available when [`supports_tsc_deadline`] returns `true`.
+    TscDeadline = 2,
+}
+
+/// The delivery mode of an *Inter-Processor Interrupt* (IPI).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DeliveryMode {
+    /// Delivers the interrupt specified in the vector field to the target processor(s).
+    Fixed = 0b000,
+    /// Same as [`DeliveryMode::Fixed`], but the interrupt is delivered to the processor with the
+    /// lowest priority among the target processors.
+    LowestPriority = 0b001,
+    /// Delivers a *System Management Interrupt* (SMI) to the target processor(s). The vector must
+    /// be zero.
+    Smi = 0b010,
+    /// Delivers a *Non-Maskable Interrupt* (NMI) to the target processor(s). The vector is
+    /// ignored.
+    Nmi = 0b100,
+    /// Delivers an INIT request to the target processor(s), causing them to enter the
+    /// *wait-for-SIPI* state. The vector must be zero.
+    Init = 0b101,
+    /// Delivers a *Start-Up IPI* (SIPI) to the target processor(s). The vector specifies the
+    /// physical page at which the target processor(s) start executing in real mode.
+    StartUp = 0b110,
+}
+
+/// The destination shorthand of an *Inter-Processor Interrupt* (IPI).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum DestinationShorthand {
+    /// The destination is specified in the destination field of the command register.
+    None = 0b00,
+    /// The interrupt is sent to every processor, excluding the issuing one.
+    AllExcludingSelf = 0b11,
+}
+
+bitflags! {
+    /// The errors that the local APIC might report through its *Error Status Register*.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct ApicError: u32 {
+        /// A checksum error was detected on a message sent on the APIC bus.
+        ///
+        /// This is only used on P6 and Pentium processors.
+        const SEND_CHECKSUM = 1 << 0;
+        /// A checksum error was detected on a message received on the APIC bus.
+        ///
+        /// This is only used on P6 and Pentium processors.
+        const RECEIVE_CHECKSUM = 1 << 1;
+        /// A message sent on the APIC bus was not accepted by any APIC.
+        ///
+        /// This is only used on P6 and Pentium processors.
+        const SEND_ACCEPT = 1 << 2;
+        /// A message received on the APIC bus was not accepted by any APIC, including this one.
+        ///
+        /// This is only used on P6 and Pentium processors.
+        const RECEIVE_ACCEPT = 1 << 3;
+        /// The local APIC detected an attempt to send a lowest-priority IPI while that mode is
+        /// not supported.
+        const REDIRECTABLE_IPI = 1 << 4;
+        /// The local APIC detected an illegal vector in a message it sent.
+        const SEND_ILLEGAL_VECTOR = 1 << 5;
+        /// The local APIC detected an illegal vector in a message it received, or in one of its
+        /// local vector table entries.
+        const RECEIVE_ILLEGAL_VECTOR = 1 << 6;
+        /// Software attempted to access a register that is reserved in the APIC's register
+        /// address space.
+        const ILLEGAL_REGISTER_ADDRESS = 1 << 7;
+    }
 }
 
 /// Represents the registers of a Local APIC.
@@ -219,6 +304,146 @@ impl<'a> XApic<'a> {
             .write(index as u32 | (mode as u32) << 17);
     }
 
+    /// Arms the timer of the local APIC to fire once the *Time-Stamp Counter* reaches
+    /// `deadline_tsc`.
+    ///
+    /// The timer must have been configured in [`TimerMode::TscDeadline`] mode beforehand.
+    ///
+    /// # Notes
+    ///
+    /// - Writing a new deadline re-arms the timer, replacing any previous deadline.
+    /// - A deadline that is already in the past fires immediately.
+    /// - A deadline of `0` disarms the timer (see [`XApic::disarm_tsc_deadline`]).
+    #[inline(always)]
+    pub fn arm_tsc_deadline(&mut self, deadline_tsc: u64) {
+        debug_assert!(supports_tsc_deadline());
+
+        // SAFETY:
+        //  The MSR is available (checked above in debug builds), and the `XApic` is logically
+        //  borrowed, which means that nobody else is configuring the timer.
+        unsafe { Msr::TSC_DEADLINE.write(deadline_tsc) };
+    }
+
+    /// Disarms the timer of the local APIC when it is in [`TimerMode::TscDeadline`] mode.
+    #[inline(always)]
+    pub fn disarm_tsc_deadline(&mut self) {
+        self.arm_tsc_deadline(0);
+    }
+
+    /// Software-enables the local APIC, using the provided spurious interrupt vector.
+    ///
+    /// This sets bit 8 of the *Spurious Interrupt Vector Register*, along with the chosen vector.
+    ///
+    /// # End Of Interrupt
+    ///
+    /// Spurious interrupts are *not* in-service when their handler is called. Unlike any other
+    /// interrupt delivered by the local APIC, the handler of the spurious interrupt vector must
+    /// **not** signal the end of the interrupt with [`XApic::end_of_interrupt`]. Doing so would
+    /// acknowledge an unrelated in-service interrupt, which is a common cause of interrupts being
+    /// lost or stuck.
+    #[inline(always)]
+    pub fn enable(&mut self, spurious_vector: u8) {
+        self.configure_spurious(spurious_vector, true);
+    }
+
+    /// Configures the vector fired by the local APIC when it detects an internal error.
+    ///
+    /// Unlike spurious interrupts, the handler of this vector must signal the end of the
+    /// interrupt.
+    #[inline(always)]
+    pub fn configure_error(&mut self, index: u8) {
+        self.base.lvt_error.write(index as u32);
+    }
+
+    /// Returns the errors detected by the local APIC since the last time this function was
+    /// called.
+    ///
+    /// The *Error Status Register* is only updated when it is written to, so this function writes
+    /// to it before reading the errors it latched.
+    #[inline(always)]
+    pub fn error_status(&mut self) -> ApicError {
+        self.base.error_status.write(0);
+        ApicError::from_bits_retain(self.base.error_status.read())
+    }
+
+    /// Writes the *Interrupt Command Register*, sending an *Inter-Processor Interrupt* (IPI).
+    ///
+    /// The high half of the register must be written first, because writing the low half is what
+    /// triggers the sending of the interrupt.
+    #[inline(always)]
+    fn write_interrupt_command(
+        &mut self,
+        dest: u8,
+        vector: u8,
+        mode: DeliveryMode,
+        shorthand: DestinationShorthand,
+    ) {
+        // Bit 14 (level) must be set for every delivery mode except the INIT level de-assert,
+        // which is not supported on modern processors anyway.
+        const LEVEL_ASSERT: u32 = 1 << 14;
+
+        self.base.interrupt_command[1].write((dest as u32) << 24);
+        self.base.interrupt_command[0]
+            .write(vector as u32 | (mode as u32) << 8 | LEVEL_ASSERT | (shorthand as u32) << 18);
+    }
+
+    /// Sends an *Inter-Processor Interrupt* (IPI) to the local APIC with the provided ID.
+    #[inline(always)]
+    pub fn send_ipi(&mut self, dest: u8, vector: u8, mode: DeliveryMode) {
+        self.write_interrupt_command(dest, vector, mode, DestinationShorthand::None);
+    }
+
+    /// Sends an *Inter-Processor Interrupt* (IPI) to every processor, except the current one.
+    #[inline(always)]
+    pub fn send_ipi_all_excluding_self(&mut self, vector: u8, mode: DeliveryMode) {
+        self.write_interrupt_command(0, vector, mode, DestinationShorthand::AllExcludingSelf);
+    }
+
+    /// Sends an INIT IPI to the local APIC with the provided ID.
+    ///
+    /// This is the first step of the INIT-SIPI-SIPI sequence used to start application
+    /// processors.
+    #[inline(always)]
+    pub fn send_init(&mut self, dest: u8) {
+        self.send_ipi(dest, 0, DeliveryMode::Init);
+    }
+
+    /// Sends a Start-Up IPI to the local APIC with the provided ID.
+    ///
+    /// The target processor starts executing in real mode at the physical address
+    /// `entry_page * 4096`.
+    #[inline(always)]
+    pub fn send_startup(&mut self, dest: u8, entry_page: u8) {
+        self.send_ipi(dest, entry_page, DeliveryMode::StartUp);
+    }
+
+    /// Returns whether the last IPI sent by this local APIC has not been accepted by its target
+    /// yet.
+    #[inline(always)]
+    pub fn is_ipi_pending(&self) -> bool {
+        self.base.interrupt_command[0].read() & (1 << 12) != 0
+    }
+
+    /// Spins until the last IPI sent by this local APIC has been accepted by its target.
+    #[inline(always)]
+    pub fn wait_for_ipi_delivery(&self) {
+        while self.is_ipi_pending() {
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Sends a fixed interrupt to every processor except the current one, and waits until it has
+    /// been accepted.
+    ///
+    /// Note that the interrupt being *accepted* does not mean that it has been *handled*. Callers
+    /// that need to know when the other processors are done (e.g. for TLB shootdowns) must
+    /// synchronize with them separately.
+    #[inline(always)]
+    pub fn broadcast_and_wait(&mut self, vector: u8) {
+        self.send_ipi_all_excluding_self(vector, DeliveryMode::Fixed);
+        self.wait_for_ipi_delivery();
+    }
+
     /// Configures the spurious interrupt vector. This is also used to enable the local APIC.
     #[inline(always)]
     pub fn configure_spurious(&mut self, index: u8, apic_enable: bool) {
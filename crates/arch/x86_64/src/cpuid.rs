@@ -0,0 +1,140 @@
+//! Access to the `CPUID` instruction.
+
+use core::arch::asm;
+
+/// The values returned by the `CPUID` instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CpuidResult {
+    /// The value of the **EAX** register.
+    pub eax: u32,
+    /// The value of the **EBX** register.
+    pub ebx: u32,
+    /// The value of the **ECX** register.
+    pub ecx: u32,
+    /// The value of the **EDX** register.
+    pub edx: u32,
+}
+
+/// Executes the `CPUID` instruction for the provided leaf.
+///
+/// The maximum supported leaf is available in `cpuid(0).eax` (and `cpuid(0x8000_0000).eax` for
+/// extended leaves). Querying a leaf which is not supported does not fail, but returns
+/// meaningless values.
+#[inline(always)]
+pub fn cpuid(leaf: u32) -> CpuidResult {
+    cpuid_count(leaf, 0)
+}
+
+/// Executes the `CPUID` instruction for the provided leaf and sub-leaf.
+///
+/// See [`cpuid`] for more information.
+#[inline(always)]
+pub fn cpuid_count(leaf: u32, subleaf: u32) -> CpuidResult {
+    let eax: u32;
+    let ebx: u32;
+    let ecx: u32;
+    let edx: u32;
+
+    // SAFETY:
+    //  The CPUID instruction is always available in long mode and has no side effects.
+    //
+    //  LLVM reserves RBX, meaning that it cannot be used as an operand. Its value is saved in
+    //  another register while the instruction executes.
+    unsafe {
+        asm!(
+            "mov {0:r}, rbx",
+            "cpuid",
+            "xchg {0:r}, rbx",
+            out(reg) ebx,
+            inout("eax") leaf => eax,
+            inout("ecx") subleaf => ecx,
+            out("edx") edx,
+            options(nomem, nostack, preserves_flags),
+        );
+    }
+
+    CpuidResult { eax, ebx, ecx, edx }
+}
+
+/// A CPU feature reported by the leaf `1` of the `CPUID` instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CpuidFeature {
+    /// The CPU has a local APIC (bit 9 of EDX).
+    Apic,
+    /// The CPU supports SSE instructions (bit 25 of EDX).
+    Sse,
+    /// The CPU supports SSE2 instructions (bit 26 of EDX).
+    Sse2,
+    /// The CPU supports *Process-Context Identifiers* (bit 17 of ECX).
+    Pcid,
+    /// The local APIC supports the x2APIC mode (bit 21 of ECX).
+    X2Apic,
+    /// The local APIC timer supports the TSC-deadline mode (bit 24 of ECX).
+    TscDeadline,
+    /// The CPU supports the `XSAVE` family of instructions (bit 26 of ECX).
+    Xsave,
+}
+
+impl CpuidFeature {
+    /// Returns whether this feature is reported in `leaf_1`, the result of `cpuid(1)`.
+    #[inline]
+    pub const fn is_set_in(self, leaf_1: &CpuidResult) -> bool {
+        match self {
+            Self::Apic => leaf_1.edx & (1 << 9) != 0,
+            Self::Sse => leaf_1.edx & (1 << 25) != 0,
+            Self::Sse2 => leaf_1.edx & (1 << 26) != 0,
+            Self::Pcid => leaf_1.ecx & (1 << 17) != 0,
+            Self::X2Apic => leaf_1.ecx & (1 << 21) != 0,
+            Self::TscDeadline => leaf_1.ecx & (1 << 24) != 0,
+            Self::Xsave => leaf_1.ecx & (1 << 26) != 0,
+        }
+    }
+}
+
+/// Returns whether the CPU supports the provided feature.
+///
+/// This executes the `CPUID` instruction every time. When checking several features, prefer
+/// calling [`cpuid`] once and using [`CpuidFeature::is_set_in`].
+#[inline]
+pub fn has_feature(feature: CpuidFeature) -> bool {
+    feature.is_set_in(&cpuid(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vendor_string() {
+        let leaf_0 = cpuid(0);
+        assert!(leaf_0.eax >= 1);
+
+        let mut vendor = [0u8; 12];
+        vendor[0..4].copy_from_slice(&leaf_0.ebx.to_le_bytes());
+        vendor[4..8].copy_from_slice(&leaf_0.edx.to_le_bytes());
+        vendor[8..12].copy_from_slice(&leaf_0.ecx.to_le_bytes());
+        assert!(vendor.iter().all(|b| b.is_ascii_graphic() || *b == b' '));
+    }
+
+    #[test]
+    fn baseline_features() {
+        // Every x86_64 CPU supports SSE2.
+        assert!(has_feature(CpuidFeature::Sse));
+        assert!(has_feature(CpuidFeature::Sse2));
+    }
+
+    #[test]
+    fn feature_bits() {
+        let leaf_1 = CpuidResult {
+            eax: 0,
+            ebx: 0,
+            ecx: 1 << 21,
+            edx: 1 << 9,
+        };
+
+        assert!(CpuidFeature::X2Apic.is_set_in(&leaf_1));
+        assert!(CpuidFeature::Apic.is_set_in(&leaf_1));
+        assert!(!CpuidFeature::Xsave.is_set_in(&leaf_1));
+        assert!(!CpuidFeature::Sse.is_set_in(&leaf_1));
+    }
+}
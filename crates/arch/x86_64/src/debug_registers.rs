@@ -0,0 +1,325 @@
+//! The debug registers, used to set hardware breakpoints.
+
+#![allow(clippy::missing_safety_doc)]
+
+use core::arch::asm;
+
+use bitflags::bitflags;
+
+use crate::VirtAddr;
+
+macro_rules! address_register {
+    ($($get:ident, $set:ident => $reg:literal;)*) => {
+        $(
+            #[doc = concat!("Returns the value of the **", $reg, "** register.")]
+            ///
+            /// This is the address watched by the hardware breakpoint with the same index.
+            #[inline(always)]
+            pub fn $get() -> VirtAddr {
+                let ret: u64;
+                unsafe {
+                    asm!(concat!("mov {}, ", $reg), out(reg) ret, options(nomem, nostack, preserves_flags));
+                }
+                ret
+            }
+
+            #[doc = concat!("Sets the value of the **", $reg, "** register.")]
+            ///
+            /// This is the address watched by the hardware breakpoint with the same index.
+            #[inline(always)]
+            pub unsafe fn $set(addr: VirtAddr) {
+                unsafe {
+                    asm!(concat!("mov ", $reg, ", {}"), in(reg) addr, options(nomem, nostack, preserves_flags));
+                }
+            }
+        )*
+    };
+}
+
+address_register! {
+    dr0, set_dr0 => "dr0";
+    dr1, set_dr1 => "dr1";
+    dr2, set_dr2 => "dr2";
+    dr3, set_dr3 => "dr3";
+}
+
+bitflags! {
+    /// The flags of the **DR6** register, describing which debug condition triggered a
+    /// [debug exception](crate::CpuException::Debug).
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Dr6: u64 {
+        /// The condition of the breakpoint 0 was met.
+        const BREAKPOINT_0 = 1 << 0;
+        /// The condition of the breakpoint 1 was met.
+        const BREAKPOINT_1 = 1 << 1;
+        /// The condition of the breakpoint 2 was met.
+        const BREAKPOINT_2 = 1 << 2;
+        /// The condition of the breakpoint 3 was met.
+        const BREAKPOINT_3 = 1 << 3;
+        /// The next instruction accesses a debug register while
+        /// [`GENERAL_DETECT`](Dr7::GENERAL_DETECT) is set.
+        const DEBUG_REGISTER_ACCESS = 1 << 13;
+        /// The exception was triggered by single-stepping ([`RFlags::TRAP`](crate::RFlags::TRAP)).
+        const SINGLE_STEP = 1 << 14;
+        /// The exception was triggered by a hardware task switch.
+        const TASK_SWITCH = 1 << 15;
+    }
+}
+
+/// Returns the value of the **DR6** register.
+#[inline(always)]
+pub fn dr6() -> Dr6 {
+    let ret: u64;
+    unsafe {
+        asm!("mov {}, dr6", out(reg) ret, options(nomem, nostack, preserves_flags));
+    }
+    Dr6::from_bits_retain(ret)
+}
+
+/// Sets the value of the **DR6** register.
+///
+/// The processor never clears this register, which must be done by the debug exception handler.
+#[inline(always)]
+pub unsafe fn set_dr6(dr6: Dr6) {
+    unsafe {
+        asm!("mov dr6, {}", in(reg) dr6.bits(), options(nomem, nostack, preserves_flags));
+    }
+}
+
+/// The kind of access which triggers a hardware breakpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum BreakpointKind {
+    /// The breakpoint is triggered when an instruction is executed at the address.
+    ///
+    /// The length of such breakpoints must be [`BreakpointLen::One`].
+    Execute = 0b00,
+    /// The breakpoint is triggered when data is written at the address.
+    Write = 0b01,
+    /// The breakpoint is triggered by I/O reads and writes at the port.
+    ///
+    /// This requires the [`DEBUGGING_EXTENSIONS`](crate::Cr4::DEBUGGING_EXTENSIONS) flag to be
+    /// set.
+    Io = 0b10,
+    /// The breakpoint is triggered when data is read or written at the address.
+    ReadWrite = 0b11,
+}
+
+/// The size of the memory region watched by a hardware breakpoint.
+///
+/// The watched address must be aligned to that size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum BreakpointLen {
+    /// A single byte.
+    One = 0b00,
+    /// Two bytes.
+    Two = 0b01,
+    /// Eight bytes.
+    Eight = 0b10,
+    /// Four bytes.
+    Four = 0b11,
+}
+
+bitflags! {
+    /// The flags of the **DR7** register, controlling the hardware breakpoints.
+    ///
+    /// Besides the flags, this register also stores the condition and the length of each
+    /// breakpoint. They can be accessed with [`Dr7::condition`] and [`Dr7::set_condition`].
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Dr7: u64 {
+        /// Enables the breakpoint 0 for the current task.
+        const LOCAL_ENABLE_0 = 1 << 0;
+        /// Enables the breakpoint 0 for all tasks.
+        const GLOBAL_ENABLE_0 = 1 << 1;
+        /// Enables the breakpoint 1 for the current task.
+        const LOCAL_ENABLE_1 = 1 << 2;
+        /// Enables the breakpoint 1 for all tasks.
+        const GLOBAL_ENABLE_1 = 1 << 3;
+        /// Enables the breakpoint 2 for the current task.
+        const LOCAL_ENABLE_2 = 1 << 4;
+        /// Enables the breakpoint 2 for all tasks.
+        const GLOBAL_ENABLE_2 = 1 << 5;
+        /// Enables the breakpoint 3 for the current task.
+        const LOCAL_ENABLE_3 = 1 << 6;
+        /// Enables the breakpoint 3 for all tasks.
+        const GLOBAL_ENABLE_3 = 1 << 7;
+        /// Makes data breakpoints report the exact instruction which triggered them (for the
+        /// current task). Ignored by most modern CPUs.
+        const LOCAL_EXACT = 1 << 8;
+        /// Makes data breakpoints report the exact instruction which triggered them (for all
+        /// tasks). Ignored by most modern CPUs.
+        const GLOBAL_EXACT = 1 << 9;
+        /// Triggers a debug exception before any access to a debug register.
+        const GENERAL_DETECT = 1 << 13;
+    }
+}
+
+impl Dr7 {
+    /// Returns the flag enabling the breakpoint `index` for all tasks.
+    #[inline]
+    pub const fn global_enable(index: u8) -> Self {
+        debug_assert!(index < 4, "there are only 4 hardware breakpoints");
+        Self::from_bits_retain(1 << (index * 2 + 1))
+    }
+
+    /// Returns the flag enabling the breakpoint `index` for the current task.
+    #[inline]
+    pub const fn local_enable(index: u8) -> Self {
+        debug_assert!(index < 4, "there are only 4 hardware breakpoints");
+        Self::from_bits_retain(1 << (index * 2))
+    }
+
+    /// Returns the condition and the length of the breakpoint `index`.
+    #[inline]
+    pub const fn condition(self, index: u8) -> (BreakpointKind, BreakpointLen) {
+        debug_assert!(index < 4, "there are only 4 hardware breakpoints");
+
+        let field = (self.bits() >> (16 + index * 4)) & 0b1111;
+
+        let kind = match field & 0b11 {
+            0b00 => BreakpointKind::Execute,
+            0b01 => BreakpointKind::Write,
+            0b10 => BreakpointKind::Io,
+            _ => BreakpointKind::ReadWrite,
+        };
+
+        let len = match field >> 2 {
+            0b00 => BreakpointLen::One,
+            0b01 => BreakpointLen::Two,
+            0b10 => BreakpointLen::Eight,
+            _ => BreakpointLen::Four,
+        };
+
+        (kind, len)
+    }
+
+    /// Sets the condition and the length of the breakpoint `index`.
+    #[inline]
+    pub fn set_condition(&mut self, index: u8, kind: BreakpointKind, len: BreakpointLen) {
+        debug_assert!(index < 4, "there are only 4 hardware breakpoints");
+
+        let shift = 16 + index * 4;
+        let field = (kind as u64) | (len as u64) << 2;
+
+        *self = Self::from_bits_retain(self.bits() & !(0b1111 << shift) | field << shift);
+    }
+}
+
+/// Returns the value of the **DR7** register.
+#[inline(always)]
+pub fn dr7() -> Dr7 {
+    let ret: u64;
+    unsafe {
+        asm!("mov {}, dr7", out(reg) ret, options(nomem, nostack, preserves_flags));
+    }
+    Dr7::from_bits_retain(ret)
+}
+
+/// Sets the value of the **DR7** register.
+#[inline(always)]
+pub unsafe fn set_dr7(dr7: Dr7) {
+    unsafe {
+        asm!("mov dr7, {}", in(reg) dr7.bits(), options(nomem, nostack, preserves_flags));
+    }
+}
+
+/// Sets the address register of the breakpoint `index`.
+///
+/// # Safety
+///
+/// See [`set_hardware_breakpoint`].
+unsafe fn set_breakpoint_addr(index: u8, addr: VirtAddr) {
+    unsafe {
+        match index {
+            0 => set_dr0(addr),
+            1 => set_dr1(addr),
+            2 => set_dr2(addr),
+            _ => set_dr3(addr),
+        }
+    }
+}
+
+/// Enables the hardware breakpoint `index`, triggering a
+/// [debug exception](crate::CpuException::Debug) when `addr` is accessed as described by `kind`
+/// and `len`.
+///
+/// The breakpoint is enabled globally, meaning that it is not affected by hardware task switches.
+///
+/// # Safety
+///
+/// A debug exception handler must be installed.
+///
+/// `index` must be less than 4. `addr` must be aligned to `len`.
+pub unsafe fn set_hardware_breakpoint(
+    index: u8,
+    addr: VirtAddr,
+    kind: BreakpointKind,
+    len: BreakpointLen,
+) {
+    debug_assert!(index < 4, "there are only 4 hardware breakpoints");
+    debug_assert!(
+        kind != BreakpointKind::Execute || len == BreakpointLen::One,
+        "execution breakpoints must have a length of 1",
+    );
+
+    let mut flags = dr7();
+    flags.set_condition(index, kind, len);
+    flags.insert(Dr7::global_enable(index));
+
+    unsafe {
+        set_breakpoint_addr(index, addr);
+        set_dr7(flags);
+    }
+}
+
+/// Disables the hardware breakpoint `index`.
+///
+/// # Safety
+///
+/// `index` must be less than 4.
+pub unsafe fn clear_hardware_breakpoint(index: u8) {
+    debug_assert!(index < 4, "there are only 4 hardware breakpoints");
+
+    let mut flags = dr7();
+    flags.remove(Dr7::global_enable(index) | Dr7::local_enable(index));
+
+    unsafe { set_dr7(flags) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dr7_conditions() {
+        let mut dr7 = Dr7::empty();
+
+        dr7.set_condition(0, BreakpointKind::Write, BreakpointLen::Eight);
+        dr7.set_condition(3, BreakpointKind::ReadWrite, BreakpointLen::Four);
+        assert_eq!(dr7.bits(), 0b1001 << 16 | 0b1111 << 28);
+
+        assert_eq!(
+            dr7.condition(0),
+            (BreakpointKind::Write, BreakpointLen::Eight)
+        );
+        assert_eq!(
+            dr7.condition(1),
+            (BreakpointKind::Execute, BreakpointLen::One)
+        );
+        assert_eq!(
+            dr7.condition(3),
+            (BreakpointKind::ReadWrite, BreakpointLen::Four)
+        );
+
+        dr7.set_condition(0, BreakpointKind::Execute, BreakpointLen::One);
+        assert_eq!(dr7.bits(), 0b1111 << 28);
+    }
+
+    #[test]
+    fn dr7_enable_bits() {
+        assert_eq!(Dr7::global_enable(0), Dr7::GLOBAL_ENABLE_0);
+        assert_eq!(Dr7::global_enable(3), Dr7::GLOBAL_ENABLE_3);
+        assert_eq!(Dr7::local_enable(2), Dr7::LOCAL_ENABLE_2);
+    }
+}
@@ -153,6 +153,44 @@ impl<const SIZE: usize> SegmentDescriptor<SIZE> {
     pub const fn to_raw(self) -> [u64; SIZE] {
         self.0
     }
+
+    /// Returns the first 64-bit word of the descriptor, which stores the access byte and flags.
+    #[inline(always)]
+    const fn low(&self) -> u64 {
+        // SIZE is always at least 1 for valid descriptors.
+        self.0[0]
+    }
+
+    /// Returns whether the segment is present.
+    #[inline(always)]
+    pub const fn present(&self) -> bool {
+        self.low() & (1 << 47) != 0
+    }
+
+    /// Returns the *descriptor privilege level* of the segment.
+    #[inline(always)]
+    pub const fn dpl(&self) -> PrivilegeLevel {
+        unsafe { PrivilegeLevel::from_raw_unchecked((self.low() >> 45) as u8 & 0b11) }
+    }
+
+    /// Returns whether this descriptor describes a system segment (e.g. a **TSS** or an **LDT**)
+    /// rather than a code or data segment.
+    #[inline(always)]
+    pub const fn is_system(&self) -> bool {
+        self.low() & (1 << 44) == 0
+    }
+
+    /// Returns whether this descriptor describes a code segment.
+    #[inline(always)]
+    pub const fn is_code(&self) -> bool {
+        !self.is_system() && self.low() & (1 << 43) != 0
+    }
+
+    /// Returns whether this descriptor describes a data segment.
+    #[inline(always)]
+    pub const fn is_data(&self) -> bool {
+        !self.is_system() && self.low() & (1 << 43) == 0
+    }
 }
 
 impl SegmentDescriptor<1> {
@@ -206,11 +244,56 @@ impl SegmentDescriptor<1> {
 
         Self::from_raw([value])
     }
+
+    /// Returns whether this code segment executes in 64-bit mode.
+    ///
+    /// This is always `false` for data segments.
+    #[inline(always)]
+    pub const fn is_long_mode(&self) -> bool {
+        self.is_code() && self.0[0] & (1 << 53) != 0
+    }
+
+    /// Returns whether privilege levels bellow the [`dpl`](SegmentDescriptor::dpl) of this code
+    /// segment are also allowed to execute code within it.
+    ///
+    /// This is always `false` for data segments.
+    #[inline(always)]
+    pub const fn conforming(&self) -> bool {
+        self.is_code() && self.0[0] & (1 << 42) != 0
+    }
+
+    /// Returns whether this data segment
+    /// [grows downwards](https://wiki.osdev.org/Expand_Down) rather than upwards.
+    ///
+    /// This is always `false` for code segments.
+    #[inline(always)]
+    pub const fn direction(&self) -> bool {
+        self.is_data() && self.0[0] & (1 << 42) != 0
+    }
+
+    /// Returns whether this code segment is readable.
+    ///
+    /// This is always `false` for data segments, which are always readable.
+    #[inline(always)]
+    pub const fn readable(&self) -> bool {
+        self.is_code() && self.0[0] & (1 << 41) != 0
+    }
+
+    /// Returns whether this data segment is writable.
+    ///
+    /// This is always `false` for code segments, which are never writable.
+    #[inline(always)]
+    pub const fn writable(&self) -> bool {
+        self.is_data() && self.0[0] & (1 << 41) != 0
+    }
 }
 
 impl SegmentDescriptor<2> {
     /// Creates a new 64-bit [**TSS**](https://wiki.osdev.org/Task_State_Segment) descriptor.
     ///
+    /// The limit of the segment covers the [`Tss`] structure only. When an I/O permission bitmap
+    /// is placed after the structure, [`SegmentDescriptor::tss_with_limit`] must be used instead.
+    ///
     /// # Arguments
     ///
     /// * `present`: Whether the descriptor is present. Must be set for any valid descriptor.
@@ -218,11 +301,46 @@ impl SegmentDescriptor<2> {
     /// * `dpl`: The privilege level of the segment.
     ///
     /// * `tss`: The virtual address of the *Task State Segment* structure.
+    #[inline]
     pub const fn tss(present: bool, dpl: PrivilegeLevel, tss: VirtAddr) -> Self {
+        Self::tss_with_limit(present, dpl, tss, size_of::<Tss>() as u64 - 1)
+    }
+
+    /// Creates a new 64-bit [**TSS**](https://wiki.osdev.org/Task_State_Segment) descriptor with
+    /// an explicit limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `present`: Whether the descriptor is present. Must be set for any valid descriptor.
+    ///
+    /// * `dpl`: The privilege level of the segment.
+    ///
+    /// * `tss`: The virtual address of the *Task State Segment* structure.
+    ///
+    /// * `limit`: The size in bytes of the segment, minus one. When the **TSS** is followed by an
+    /// I/O permission bitmap, the limit must cover the whole bitmap (plus its trailing `0xFF`
+    /// byte). Ports whose bit lies beyond the limit are not accessible from user mode.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `limit` is larger than `0xFFFFF`. The granularity flag of the
+    /// descriptor is never set, meaning that the limit is always expressed in bytes, and this is
+    /// the largest limit that can be encoded. This is more than enough for a full I/O permission
+    /// bitmap.
+    pub const fn tss_with_limit(
+        present: bool,
+        dpl: PrivilegeLevel,
+        tss: VirtAddr,
+        limit: u64,
+    ) -> Self {
+        assert!(
+            limit <= 0xFFFFF,
+            "`SegmentDescriptor::tss_with_limit`: limit too large"
+        );
+
         let mut high = 0;
         let mut low = 0;
 
-        let limit = size_of::<Tss>() as u64 - 1;
         low |= limit & 0xFFFF;
         low |= (limit & 0xF0000) << 32;
 
@@ -273,6 +391,41 @@ impl SegmentDescriptor<2> {
     }
 }
 
+impl SegmentDescriptor<2> {
+    /// Returns the type of this system segment.
+    ///
+    /// For example, `0x9` is an available 64-bit **TSS** and `0x2` is an **LDT**.
+    #[inline(always)]
+    pub const fn system_type(&self) -> u8 {
+        (self.0[0] >> 40) as u8 & 0xF
+    }
+
+    /// Returns the base address of the segment.
+    #[inline(always)]
+    pub const fn base(&self) -> VirtAddr {
+        let [low, high] = self.0;
+
+        ((low >> 16) & 0x00FFFFFF) | ((low >> 32) & 0xFF000000) | (high << 32)
+    }
+
+    /// Returns the limit of the segment, as stored in the descriptor.
+    ///
+    /// If the granularity flag of the descriptor is set, the limit is expressed in 4 KiB units
+    /// rather than bytes.
+    #[inline(always)]
+    pub const fn limit(&self) -> u64 {
+        let low = self.0[0];
+
+        (low & 0xFFFF) | ((low >> 32) & 0xF0000)
+    }
+
+    /// Returns whether the granularity flag of the descriptor is set.
+    #[inline(always)]
+    pub const fn granularity(&self) -> bool {
+        self.0[0] & (1 << 55) != 0
+    }
+}
+
 impl<const N: usize> fmt::Debug for SegmentDescriptor<N> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut l = f.debug_list();
@@ -370,3 +523,122 @@ impl fmt::Debug for Tss {
         s.finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segment_selector_round_trip() {
+        let sel = SegmentSelector::new(5, DescriptorTable::Ldt, PrivilegeLevel::Ring3);
+        assert_eq!(sel.to_raw(), 5 << 3 | 1 << 2 | 3);
+        assert_eq!(sel.index(), 5);
+        assert_eq!(sel.table(), DescriptorTable::Ldt);
+        assert_eq!(sel.requested_privilege_level(), PrivilegeLevel::Ring3);
+
+        let sel = SegmentSelector::new(0x1FFF, DescriptorTable::Gdt, PrivilegeLevel::Ring0);
+        assert_eq!(sel.index(), 0x1FFF);
+        assert_eq!(sel.table(), DescriptorTable::Gdt);
+        assert_eq!(sel.requested_privilege_level(), PrivilegeLevel::Ring0);
+    }
+
+    #[test]
+    fn code_and_data_descriptors() {
+        let code = SegmentDescriptor::code(true, PrivilegeLevel::Ring3, false, true);
+        assert!(code.present());
+        assert!(code.is_code());
+        assert!(!code.is_data());
+        assert!(!code.is_system());
+        assert_eq!(code.dpl(), PrivilegeLevel::Ring3);
+
+        let data = SegmentDescriptor::data(false, PrivilegeLevel::Ring0, false, true);
+        assert!(!data.present());
+        assert!(data.is_data());
+        assert!(!data.is_code());
+        assert_eq!(data.dpl(), PrivilegeLevel::Ring0);
+    }
+
+    #[test]
+    fn code_and_data_flags_round_trip() {
+        let levels = [
+            PrivilegeLevel::Ring0,
+            PrivilegeLevel::Ring1,
+            PrivilegeLevel::Ring2,
+            PrivilegeLevel::Ring3,
+        ];
+
+        for dpl in levels {
+            for (a, b) in [(false, false), (false, true), (true, false), (true, true)] {
+                let code = SegmentDescriptor::code(true, dpl, a, b);
+                assert_eq!(code.dpl(), dpl);
+                assert_eq!(code.conforming(), a);
+                assert_eq!(code.readable(), b);
+                assert!(code.is_long_mode());
+                assert!(!code.writable());
+                assert!(!code.direction());
+
+                let data = SegmentDescriptor::data(true, dpl, a, b);
+                assert_eq!(data.dpl(), dpl);
+                assert_eq!(data.direction(), a);
+                assert_eq!(data.writable(), b);
+                assert!(!data.is_long_mode());
+                assert!(!data.readable());
+                assert!(!data.conforming());
+            }
+        }
+    }
+
+    #[test]
+    fn tss_descriptor_round_trip() {
+        let base = 0xFFFF_8000_1234_5678;
+        let tss = SegmentDescriptor::tss(true, PrivilegeLevel::Ring0, base);
+        assert!(tss.present());
+        assert!(tss.is_system());
+        assert_eq!(tss.system_type(), 0x9);
+        assert_eq!(tss.dpl(), PrivilegeLevel::Ring0);
+        assert_eq!(tss.base(), base);
+        assert_eq!(tss.limit(), core::mem::size_of::<Tss>() as u64 - 1);
+    }
+
+    /// Addresses with a different pattern in every byte, so that a misplaced mask shows up.
+    const BASES: [VirtAddr; 5] = [
+        0,
+        0x0123_4567_89AB_CDEF,
+        0xFEDC_BA98_7654_3210,
+        0xFFFF_FFFF_FFFF_FFFF,
+        0xFFFF_8000_00FF_0000,
+    ];
+
+    #[test]
+    fn tss_base_round_trip() {
+        for base in BASES {
+            let tss = SegmentDescriptor::tss(true, PrivilegeLevel::Ring0, base);
+            assert_eq!(tss.base(), base, "base {base:#x}");
+        }
+    }
+
+    #[test]
+    fn ldt_base_and_limit_round_trip() {
+        for (base, limit) in BASES
+            .into_iter()
+            .zip([0, 0xFFFFF, 0x12345, 0xF0000, 0xFFFF])
+        {
+            let ldt = SegmentDescriptor::ldt(true, PrivilegeLevel::Ring0, base, limit);
+            assert_eq!(ldt.base(), base, "base {base:#x}");
+            assert_eq!(ldt.limit(), limit, "limit {limit:#x}");
+            assert_eq!(ldt.system_type(), 0x2);
+            assert!(!ldt.granularity());
+        }
+    }
+
+    #[test]
+    fn tss_limit_round_trip() {
+        for limit in [0, 0x67, 0x2068, 0xABCDE, 0xFFFFF] {
+            let tss = SegmentDescriptor::tss_with_limit(true, PrivilegeLevel::Ring3, !0, limit);
+            assert_eq!(tss.limit(), limit, "limit {limit:#x}");
+            assert_eq!(tss.base(), !0);
+            assert_eq!(tss.dpl(), PrivilegeLevel::Ring3);
+            assert!(!tss.granularity());
+        }
+    }
+}
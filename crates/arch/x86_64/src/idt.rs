@@ -2,12 +2,26 @@ use bitflags::bitflags;
 use core::fmt;
 use core::ops::{Index, IndexMut};
 
-use crate::{IstIndex, PrivilegeLevel, SegmentSelector, TablePtr, VirtAddr};
+use crate::{IstIndex, PrivilegeLevel, RFlags, SegmentSelector, TablePtr, VirtAddr};
 
 /// The address of an
 /// [Interrupt Service Routine](https://wiki.osdev.org/Interrupt_Service_Routines).
 pub type HandlerAddr = u64;
 
+/// An *Interrupt Service Routine* for an interrupt that does not push an error code.
+pub type HandlerFn = extern "x86-interrupt" fn(InterruptStackFrame);
+
+/// An *Interrupt Service Routine* for an exception that pushes an error code of type `E`.
+pub type HandlerFnWithErrCode<E = u64> = extern "x86-interrupt" fn(InterruptStackFrame, E);
+
+/// An *Interrupt Service Routine* for an exception that cannot be recovered from.
+pub type DivergingHandlerFn = extern "x86-interrupt" fn(InterruptStackFrame) -> !;
+
+/// An *Interrupt Service Routine* for an exception that pushes an error code of type `E` and
+/// cannot be recovered from.
+pub type DivergingHandlerFnWithErrCode<E = u64> =
+    extern "x86-interrupt" fn(InterruptStackFrame, E) -> !;
+
 /// The kind of a gate descriptor.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum GateType {
@@ -179,13 +193,13 @@ macro_rules! exception_setters {
     (
         $(
             [$index:expr]
-            fn $fn_name:ident ( $( $args:ty ),* ) $( -> $ret:ty)?;
+            fn $fn_name:ident ( $handler:ty );
         )*
     ) => {
         $(
             #[doc = concat!("Sets the *Interrupt Service Routine* to be called when the [`", stringify!($index), "`] exception occurs.")]
             #[inline(always)]
-            pub fn $fn_name(&mut self, handler: extern "x86-interrupt" fn($( $args ),* ) $( -> $ret )?, cs: SegmentSelector, ist: Option<IstIndex>, ty: GateType, dpl: PrivilegeLevel) {
+            pub fn $fn_name(&mut self, handler: $handler, cs: SegmentSelector, ist: Option<IstIndex>, ty: GateType, dpl: PrivilegeLevel) {
                 self[$index] = GateDescriptor::new(
                     handler as usize as u64,
                     cs,
@@ -228,53 +242,71 @@ impl Idt {
         }
     }
 
+    /// Sets the *Interrupt Service Routine* to be called when the interrupt with the provided
+    /// vector occurs.
+    ///
+    /// This is meant for interrupts that do not push an error code (e.g. hardware interrupts).
+    /// CPU exceptions should be configured through their dedicated setters.
+    #[inline(always)]
+    pub fn set_interrupt_handler(
+        &mut self,
+        vector: u8,
+        handler: HandlerFn,
+        cs: SegmentSelector,
+        ist: Option<IstIndex>,
+        ty: GateType,
+        dpl: PrivilegeLevel,
+    ) {
+        self[vector] = GateDescriptor::new(handler as usize as u64, cs, ist, ty, dpl, true);
+    }
+
     exception_setters!(
         [CpuException::DivisionError]
-        fn set_division_error(InterruptStackFrame);
+        fn set_division_error(HandlerFn);
         [CpuException::Debug]
-        fn set_debug(InterruptStackFrame);
+        fn set_debug(HandlerFn);
         [CpuException::NonMaskableInterrupt]
-        fn set_non_maskable_interrupt(InterruptStackFrame);
+        fn set_non_maskable_interrupt(HandlerFn);
         [CpuException::Breakpoint]
-        fn set_breakpoint(InterruptStackFrame);
+        fn set_breakpoint(HandlerFn);
         [CpuException::Overflow]
-        fn set_overflow(InterruptStackFrame);
+        fn set_overflow(HandlerFn);
         [CpuException::BoundRangeExceeded]
-        fn set_bound_range_exceeded(InterruptStackFrame);
+        fn set_bound_range_exceeded(HandlerFn);
         [CpuException::InvalidOpCode]
-        fn set_invalid_op_code(InterruptStackFrame);
+        fn set_invalid_op_code(HandlerFn);
         [CpuException::DeviceNotAvailable]
-        fn set_device_not_available(InterruptStackFrame);
+        fn set_device_not_available(HandlerFn);
         [CpuException::DoubleFault]
-        fn set_double_fault(InterruptStackFrame, u64) -> !;
+        fn set_double_fault(DivergingHandlerFnWithErrCode);
         [CpuException::InvalidTSS]
-        fn set_invalid_tss(InterruptStackFrame, TableEntryError);
+        fn set_invalid_tss(HandlerFnWithErrCode<TableEntryError>);
         [CpuException::SegmentNotPresent]
-        fn set_segment_not_present(InterruptStackFrame, TableEntryError);
+        fn set_segment_not_present(HandlerFnWithErrCode<TableEntryError>);
         [CpuException::StackSegmentFault]
-        fn set_stack_segment_fault(InterruptStackFrame, TableEntryError);
+        fn set_stack_segment_fault(HandlerFnWithErrCode<TableEntryError>);
         [CpuException::GeneralProtectionFault]
-        fn set_general_protection_fault(InterruptStackFrame, TableEntryError);
+        fn set_general_protection_fault(HandlerFnWithErrCode<TableEntryError>);
         [CpuException::PageFault]
-        fn set_page_fault(InterruptStackFrame, PageFaultError);
+        fn set_page_fault(HandlerFnWithErrCode<PageFaultError>);
         [CpuException::X87FloatingPointException]
-        fn set_x87_floating_point_exception(InterruptStackFrame);
+        fn set_x87_floating_point_exception(HandlerFn);
         [CpuException::AlignmentCheck]
-        fn set_alignment_check(InterruptStackFrame, u64);
+        fn set_alignment_check(HandlerFnWithErrCode);
         [CpuException::MachineCheck]
-        fn set_machine_check(InterruptStackFrame) -> !;
+        fn set_machine_check(DivergingHandlerFn);
         [CpuException::SimdFloatingPointException]
-        fn set_simd_floating_point_exception(InterruptStackFrame);
+        fn set_simd_floating_point_exception(HandlerFn);
         [CpuException::VirtualizationException]
-        fn set_virtualization_exception(InterruptStackFrame);
+        fn set_virtualization_exception(HandlerFn);
         [CpuException::ControlProtectionException]
-        fn set_control_protection_exception(InterruptStackFrame, u64);
+        fn set_control_protection_exception(HandlerFnWithErrCode);
         [CpuException::HypervisorInjectionException]
-        fn set_hypervisor_injection_exception(InterruptStackFrame);
+        fn set_hypervisor_injection_exception(HandlerFn);
         [CpuException::VmmCommunicationException]
-        fn set_vmm_communication_exception(InterruptStackFrame, u64);
+        fn set_vmm_communication_exception(HandlerFnWithErrCode);
         [CpuException::SecurityException]
-        fn set_security_exception(InterruptStackFrame, u64);
+        fn set_security_exception(HandlerFnWithErrCode);
     );
 }
 
@@ -421,6 +453,89 @@ pub enum CpuException {
     SecurityException = 0x1E,
 }
 
+impl CpuException {
+    /// Returns the [`CpuException`] associated with the provided interrupt vector, if any.
+    pub const fn from_u8(vector: u8) -> Option<Self> {
+        Some(match vector {
+            0x00 => Self::DivisionError,
+            0x01 => Self::Debug,
+            0x02 => Self::NonMaskableInterrupt,
+            0x03 => Self::Breakpoint,
+            0x04 => Self::Overflow,
+            0x05 => Self::BoundRangeExceeded,
+            0x06 => Self::InvalidOpCode,
+            0x07 => Self::DeviceNotAvailable,
+            0x08 => Self::DoubleFault,
+            0x0A => Self::InvalidTSS,
+            0x0B => Self::SegmentNotPresent,
+            0x0C => Self::StackSegmentFault,
+            0x0D => Self::GeneralProtectionFault,
+            0x0E => Self::PageFault,
+            0x10 => Self::X87FloatingPointException,
+            0x11 => Self::AlignmentCheck,
+            0x12 => Self::MachineCheck,
+            0x13 => Self::SimdFloatingPointException,
+            0x14 => Self::VirtualizationException,
+            0x15 => Self::ControlProtectionException,
+            0x1C => Self::HypervisorInjectionException,
+            0x1D => Self::VmmCommunicationException,
+            0x1E => Self::SecurityException,
+            _ => return None,
+        })
+    }
+
+    /// Returns whether the CPU pushes an error code on the stack when this exception occurs.
+    ///
+    /// When this is `true`, the handler of the exception receives an additional argument after
+    /// the [`InterruptStackFrame`].
+    pub const fn pushes_error_code(self) -> bool {
+        matches!(
+            self,
+            Self::DoubleFault
+                | Self::InvalidTSS
+                | Self::SegmentNotPresent
+                | Self::StackSegmentFault
+                | Self::GeneralProtectionFault
+                | Self::PageFault
+                | Self::AlignmentCheck
+                | Self::ControlProtectionException
+                | Self::VmmCommunicationException
+                | Self::SecurityException
+        )
+    }
+
+    /// Returns whether this exception is a **fault**.
+    ///
+    /// The saved instruction pointer of a fault points to the faulting instruction, which is
+    /// executed again when the handler returns.
+    pub const fn is_fault(self) -> bool {
+        !matches!(
+            self,
+            Self::Debug
+                | Self::NonMaskableInterrupt
+                | Self::Breakpoint
+                | Self::Overflow
+                | Self::DoubleFault
+                | Self::MachineCheck
+        )
+    }
+
+    /// Returns whether this exception is a **trap**.
+    ///
+    /// The saved instruction pointer of a trap points to the instruction following the trapping
+    /// instruction.
+    pub const fn is_trap(self) -> bool {
+        matches!(self, Self::Debug | Self::Breakpoint | Self::Overflow)
+    }
+
+    /// Returns whether this exception is an **abort**.
+    ///
+    /// Aborts cannot be recovered from and their handlers must not return.
+    pub const fn is_abort(self) -> bool {
+        matches!(self, Self::DoubleFault | Self::MachineCheck)
+    }
+}
+
 impl Index<CpuException> for Idt {
     type Output = GateDescriptor;
 
@@ -513,6 +628,24 @@ impl fmt::Debug for TableEntryError {
     }
 }
 
+impl fmt::Display for TableEntryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let table = match self.table() {
+            TableEntryKind::Gdt => "GDT",
+            TableEntryKind::Idt => "IDT",
+            TableEntryKind::Ldt => "LDT",
+        };
+
+        write!(f, "{} index {}", table, self.index())?;
+
+        if self.external() {
+            f.write_str(" (external)")?;
+        }
+
+        Ok(())
+    }
+}
+
 bitflags! {
     /// An error specified by the CPU on [page fault](CpuException::PageFault).
     #[derive(Debug, Clone, Copy)]
@@ -544,27 +677,238 @@ bitflags! {
     }
 }
 
+impl fmt::Display for PageFaultError {
+    /// Describes the fault in a short sentence, such as `user write to non-present page`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = if self.contains(Self::USER) {
+            "user"
+        } else {
+            "kernel"
+        };
+
+        let access = if self.contains(Self::INSTRUCTION_FETCH) {
+            "instruction fetch from"
+        } else if self.contains(Self::WRITE) {
+            "write to"
+        } else {
+            "read from"
+        };
+
+        let page = if self.contains(Self::PRESENT) {
+            "protected"
+        } else {
+            "non-present"
+        };
+
+        write!(f, "{} {} {} page", mode, access, page)?;
+
+        let causes = [
+            (Self::INSTRUCTION_FETCH | Self::PRESENT, "NX"),
+            (Self::RESERVED_WRITE, "reserved bit set"),
+            (Self::PROTECTION_KEY, "protection key"),
+            (Self::SHADOW_STACK, "shadow stack"),
+            (Self::SOFTWARE_GUARD_EXT, "SGX"),
+        ];
+
+        let mut first = true;
+        for (flags, name) in causes {
+            if !self.contains(flags) {
+                continue;
+            }
+
+            f.write_str(if first { " (" } else { ", " })?;
+            f.write_str(name)?;
+            first = false;
+        }
+
+        if !first {
+            f.write_str(")")?;
+        }
+
+        Ok(())
+    }
+}
+
 /// The values that are always pushed onto the stack when an interrupt is called.
 #[derive(Debug, Clone, Copy)]
 #[repr(C)]
 pub struct InterruptStackFrame {
-    ip: u64,
-    cs: u64,
-    flags: u64,
-    sp: u64,
-    ss: u64,
+    /// The instruction pointer at the time of the interrupt.
+    ///
+    /// Depending on the exception, this is either the address of the faulting instruction, or
+    /// the address of the next instruction.
+    pub rip: VirtAddr,
+    /// The code segment selector at the time of the interrupt, zero-extended to 64 bits.
+    pub cs: u64,
+    /// The value of the **RFLAGS** register at the time of the interrupt.
+    pub rflags: RFlags,
+    /// The stack pointer at the time of the interrupt.
+    pub rsp: VirtAddr,
+    /// The stack segment selector at the time of the interrupt, zero-extended to 64 bits.
+    pub ss: u64,
 }
 
-impl InterruptStackFrame {
-    /// Returns the saved instruction pointer.
-    #[inline(always)]
-    pub fn instruction_pointer(&self) -> VirtAddr {
-        self.ip
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::DescriptorTable;
+    use std::string::ToString;
+
+    #[test]
+    fn gate_descriptor_round_trip() {
+        let offset = 0xFFFF_FFFF_8012_3456;
+        let selector = SegmentSelector::new(1, DescriptorTable::Gdt, PrivilegeLevel::Ring0);
+        let gate = GateDescriptor::new(
+            offset,
+            selector,
+            Some(IstIndex::Three),
+            GateType::Trap,
+            PrivilegeLevel::Ring3,
+            true,
+        );
+
+        assert_eq!(gate.offset(), offset);
+        assert_eq!(gate.selector().to_raw(), selector.to_raw());
+        assert_eq!(gate.ist_index(), Some(IstIndex::Three));
+        assert_eq!(gate.ty(), Some(GateType::Trap));
+        assert_eq!(gate.dpl(), PrivilegeLevel::Ring3);
+        assert!(gate.present());
     }
 
-    /// Returns the saved stack pointer.
-    #[inline(always)]
-    pub fn stack_pointer(&self) -> VirtAddr {
-        self.sp
+    #[test]
+    fn gate_descriptor_offset_round_trip() {
+        let selector = SegmentSelector::new(1, DescriptorTable::Gdt, PrivilegeLevel::Ring0);
+        let offsets = [
+            0,
+            0x0000_0000_0000_FFFF,
+            0x0000_0000_FFFF_0000,
+            0xFFFF_FFFF_0000_0000,
+            0xFFFF_FFFF_FFFF_FFFF,
+            0x0123_4567_89AB_CDEF,
+            0xFFFF_FFFF_8000_1234,
+            0xFFFF_8000_DEAD_BEEF,
+        ];
+
+        for offset in offsets {
+            let gate = GateDescriptor::new(
+                offset,
+                selector,
+                Some(IstIndex::Seven),
+                GateType::Interrupt,
+                PrivilegeLevel::Ring0,
+                true,
+            );
+
+            assert_eq!(gate.offset(), offset, "offset {offset:#x}");
+            // The offset must not leak into the other fields.
+            assert_eq!(gate.selector().to_raw(), selector.to_raw());
+            assert_eq!(gate.ist_index(), Some(IstIndex::Seven));
+            assert_eq!(gate.ty(), Some(GateType::Interrupt));
+            assert_eq!(gate.dpl(), PrivilegeLevel::Ring0);
+            assert!(gate.present());
+        }
+    }
+
+    #[test]
+    fn gate_descriptor_without_ist() {
+        let selector = SegmentSelector::new(3, DescriptorTable::Gdt, PrivilegeLevel::Ring0);
+        let gate = GateDescriptor::new(
+            0x1234,
+            selector,
+            None,
+            GateType::Interrupt,
+            PrivilegeLevel::Ring0,
+            false,
+        );
+
+        assert_eq!(gate.offset(), 0x1234);
+        assert_eq!(gate.ist_index(), None);
+        assert_eq!(gate.ty(), Some(GateType::Interrupt));
+        assert_eq!(gate.dpl(), PrivilegeLevel::Ring0);
+        assert!(!gate.present());
+    }
+
+    #[test]
+    fn null_gate_descriptor() {
+        assert_eq!(GateDescriptor::NULL.to_raw(), [0, 0]);
+        assert_eq!(GateDescriptor::NULL.ty(), None);
+        assert!(!GateDescriptor::NULL.present());
+    }
+
+    #[test]
+    fn table_entry_error() {
+        let err = TableEntryError(42 << 3 | 0b01 << 1 | 1);
+        assert_eq!(err.index(), 42);
+        assert_eq!(err.table(), TableEntryKind::Idt);
+        assert!(err.external());
+
+        let err = TableEntryError(7 << 3 | 0b10 << 1);
+        assert_eq!(err.index(), 7);
+        assert_eq!(err.table(), TableEntryKind::Ldt);
+        assert!(!err.external());
+
+        assert_eq!(TableEntryError(0b11 << 1).table(), TableEntryKind::Idt);
+        assert_eq!(TableEntryError(0).table(), TableEntryKind::Gdt);
+    }
+
+    #[test]
+    fn table_entry_error_display() {
+        assert_eq!(
+            TableEntryError(5 << 3 | 1).to_string(),
+            "GDT index 5 (external)"
+        );
+        assert_eq!(
+            TableEntryError(13 << 3 | 0b01 << 1).to_string(),
+            "IDT index 13"
+        );
+        assert_eq!(
+            TableEntryError(2 << 3 | 0b10 << 1).to_string(),
+            "LDT index 2"
+        );
+    }
+
+    #[test]
+    fn page_fault_error_display() {
+        let err = PageFaultError::USER | PageFaultError::WRITE;
+        assert_eq!(err.to_string(), "user write to non-present page");
+
+        let err = PageFaultError::PRESENT;
+        assert_eq!(err.to_string(), "kernel read from protected page");
+
+        let err = PageFaultError::PRESENT | PageFaultError::INSTRUCTION_FETCH;
+        assert_eq!(
+            err.to_string(),
+            "kernel instruction fetch from protected page (NX)"
+        );
+
+        let err = PageFaultError::PRESENT | PageFaultError::RESERVED_WRITE | PageFaultError::USER;
+        assert_eq!(
+            err.to_string(),
+            "user read from protected page (reserved bit set)"
+        );
+    }
+
+    #[test]
+    fn cpu_exception_from_u8() {
+        assert_eq!(CpuException::from_u8(0), Some(CpuException::DivisionError));
+        assert_eq!(CpuException::from_u8(14), Some(CpuException::PageFault));
+        assert_eq!(CpuException::from_u8(32), None);
+        assert!(CpuException::PageFault.pushes_error_code());
+        assert!(!CpuException::Breakpoint.pushes_error_code());
+    }
+
+    #[test]
+    fn interrupt_stack_frame_layout() {
+        // The frame pushed by the CPU: RIP, CS, RFLAGS, RSP, SS.
+        let raw: [u64; 5] = [0x1000, 0x08, 0x202, 0x2000, 0x10];
+        let frame = unsafe { core::mem::transmute::<[u64; 5], InterruptStackFrame>(raw) };
+
+        assert_eq!(frame.rip, 0x1000);
+        assert_eq!(frame.cs, 0x08);
+        assert_eq!(frame.rflags.bits(), 0x202);
+        assert_eq!(frame.rsp, 0x2000);
+        assert_eq!(frame.ss, 0x10);
     }
 }
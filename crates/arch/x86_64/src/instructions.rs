@@ -1,6 +1,7 @@
 #![allow(clippy::missing_safety_doc)]
 
 use core::arch::asm;
+use core::fmt;
 
 use crate::{SegmentSelector, VirtAddr};
 
@@ -12,6 +13,39 @@ pub unsafe fn hlt() {
     }
 }
 
+/// Disables interrupts and halts the CPU forever.
+///
+/// Non-maskable interrupts may still wake the CPU up, in which case it is halted again.
+#[inline]
+pub fn hlt_loop() -> ! {
+    unsafe {
+        cli();
+
+        loop {
+            hlt();
+        }
+    }
+}
+
+/// Enables interrupts and halts the CPU forever.
+///
+/// Unlike [`hlt_loop`], interrupt handlers keep running, but the code calling this function never
+/// resumes.
+///
+/// # Safety
+///
+/// Enabling interrupts requires a valid *Interrupt Descriptor Table* to be loaded.
+#[inline]
+pub unsafe fn hang() -> ! {
+    unsafe {
+        sti();
+
+        loop {
+            hlt();
+        }
+    }
+}
+
 /// Raises a breakpoint exception by invoking the **INT3** instruction.
 #[inline(always)]
 pub unsafe fn int3() {
@@ -36,28 +70,10 @@ pub unsafe fn cli() {
     }
 }
 
-/// Performs a write to the provided I/O port.
-#[inline(always)]
-pub unsafe fn outb(port: u16, value: u8) {
-    unsafe {
-        asm!("out dx, al", in("dx") port, in("al") value, options(nomem, nostack, preserves_flags));
-    }
-}
-
-/// Performs a read on the provided I/O port.
-#[inline(always)]
-pub unsafe fn inb(port: u16) -> u8 {
-    unsafe {
-        let ret: u8;
-        asm!("in al, dx", out("al") ret, in("dx") port, options(nomem, nostack, preserves_flags));
-        ret
-    }
-}
-
 /// References a table which may be loaded into the CPU with instructions such as [`lidt`] or
 /// [`lgdt`].
 #[repr(packed)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Clone, Copy)]
 pub struct TablePtr {
     /// The size of the table, usually minus one.
     pub limit: u16,
@@ -65,6 +81,18 @@ pub struct TablePtr {
     pub base: VirtAddr,
 }
 
+impl fmt::Debug for TablePtr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let base = self.base;
+        let limit = self.limit;
+
+        f.debug_struct("TablePtr")
+            .field("base", &format_args!("{base:#x}"))
+            .field("limit", &format_args!("{limit:#x}"))
+            .finish()
+    }
+}
+
 /// Loads a new *Interrupt Descriptor Table*.
 #[inline(always)]
 pub unsafe fn lidt(p: &TablePtr) {
@@ -75,12 +103,12 @@ pub unsafe fn lidt(p: &TablePtr) {
 
 /// Returns the currently loaded *Interrupt Descriptor Table*.
 #[inline]
-pub unsafe fn sidt() -> TablePtr {
+pub fn sidt() -> TablePtr {
+    let mut ret = TablePtr { limit: 0, base: 0 };
     unsafe {
-        let mut ret = TablePtr { limit: 0, base: 0 };
         asm!("sidt [{}]", in(reg) &mut ret, options(nostack, preserves_flags));
-        ret
     }
+    ret
 }
 
 /// Loads a new *Global Descriptor Table*.
@@ -93,12 +121,12 @@ pub unsafe fn lgdt(p: &TablePtr) {
 
 /// Returns the currently loaded *Global Descriptor Table*.
 #[inline]
-pub unsafe fn sgdt() -> TablePtr {
+pub fn sgdt() -> TablePtr {
+    let mut ret = TablePtr { limit: 0, base: 0 };
     unsafe {
-        let mut ret = TablePtr { limit: 0, base: 0 };
         asm!("sgdt [{}]", in(reg) &mut ret, options(nostack, preserves_flags));
-        ret
     }
+    ret
 }
 
 /// Sets the value of the *Task State Register*.
@@ -109,23 +137,217 @@ pub unsafe fn ltr(sel: SegmentSelector) {
     }
 }
 
-/// Reads the value of a specific *Model Specific Register* (MSR).
+/// Invalidates the TLB entries associated with the page containing `addr`.
+///
+/// Only the entries of the current *Process-Context Identifier* are invalidated, with the
+/// exception of global pages, which are invalidated regardless of their PCID.
+#[inline(always)]
+pub unsafe fn invlpg(addr: VirtAddr) {
+    unsafe {
+        asm!("invlpg [{}]", in(reg) addr, options(nostack, preserves_flags));
+    }
+}
+
+/// Invalidates all the TLB entries of the current *Process-Context Identifier* by reloading the
+/// **CR3** register.
+///
+/// # Global Pages
+///
+/// Entries for pages marked as [`GLOBAL`](crate::PageTableFlags::GLOBAL) are **not** invalidated
+/// by this function. They must be invalidated individually with [`invlpg`], or by toggling the
+/// [`PAGE_GLOBAL`](crate::Cr4::PAGE_GLOBAL) bit of **CR4**.
+#[inline(always)]
+pub unsafe fn flush_tlb_all() {
+    unsafe {
+        asm!(
+            "mov {0}, cr3",
+            "mov cr3, {0}",
+            out(reg) _,
+            options(nostack, preserves_flags),
+        );
+    }
+}
+
+/// The number of pages above which [`flush_range`] reloads the whole TLB instead of invalidating
+/// pages one by one.
+pub const FLUSH_RANGE_THRESHOLD: u64 = 32;
+
+/// Invalidates the TLB entries of the `count` pages starting at `start`.
+///
+/// Small ranges are invalidated page by page with [`invlpg`]. Ranges of more than
+/// [`FLUSH_RANGE_THRESHOLD`] pages are invalidated at once with [`flush_tlb_all`], which is
+/// cheaper than invalidating a lot of pages individually.
+///
+/// Note that in the latter case, global pages are not invalidated (see [`flush_tlb_all`]).
+#[inline]
+pub unsafe fn flush_range(start: VirtAddr, count: u64) {
+    unsafe {
+        if count > FLUSH_RANGE_THRESHOLD {
+            flush_tlb_all();
+        } else {
+            for i in 0..count {
+                invlpg(start + i * 0x1000);
+            }
+        }
+    }
+}
+
+/// Swaps the base address of the **GS** segment with the value of the
+/// [**IA32_KERNEL_GS_BASE**](crate::kernel_gs_base) register.
+///
+/// This is normally executed when entering and leaving the kernel, so that the **GS** segment
+/// references per-CPU kernel data while in ring 0.
 #[inline(always)]
-pub unsafe fn rdmsr(port: u32) -> u64 {
+pub unsafe fn swapgs() {
+    unsafe {
+        asm!("swapgs", options(nostack, preserves_flags));
+    }
+}
+
+/// Reads the base address of the **FS** segment using the **RDFSBASE** instruction.
+///
+/// # Safety
+///
+/// The [`FSGSBASE`](crate::Cr4::FSGSBASE) bit of **CR4** must be set. Otherwise, use
+/// [`fs_base`](crate::fs_base), which is always available in ring 0.
+#[inline(always)]
+pub unsafe fn rdfsbase() -> VirtAddr {
+    let ret: u64;
+    unsafe {
+        asm!("rdfsbase {}", out(reg) ret, options(nomem, nostack, preserves_flags));
+    }
+    ret
+}
+
+/// Sets the base address of the **FS** segment using the **WRFSBASE** instruction.
+///
+/// # Safety
+///
+/// The [`FSGSBASE`](crate::Cr4::FSGSBASE) bit of **CR4** must be set. Otherwise, use
+/// [`set_fs_base`](crate::set_fs_base), which is always available in ring 0.
+#[inline(always)]
+pub unsafe fn wrfsbase(base: VirtAddr) {
+    unsafe {
+        asm!("wrfsbase {}", in(reg) base, options(nostack, preserves_flags));
+    }
+}
+
+/// Reads the base address of the **GS** segment using the **RDGSBASE** instruction.
+///
+/// # Safety
+///
+/// The [`FSGSBASE`](crate::Cr4::FSGSBASE) bit of **CR4** must be set. Otherwise, use
+/// [`gs_base`](crate::gs_base), which is always available in ring 0.
+#[inline(always)]
+pub unsafe fn rdgsbase() -> VirtAddr {
+    let ret: u64;
+    unsafe {
+        asm!("rdgsbase {}", out(reg) ret, options(nomem, nostack, preserves_flags));
+    }
+    ret
+}
+
+/// Sets the base address of the **GS** segment using the **WRGSBASE** instruction.
+///
+/// # Safety
+///
+/// The [`FSGSBASE`](crate::Cr4::FSGSBASE) bit of **CR4** must be set. Otherwise, use
+/// [`set_gs_base`](crate::set_gs_base), which is always available in ring 0.
+#[inline(always)]
+pub unsafe fn wrgsbase(base: VirtAddr) {
+    unsafe {
+        asm!("wrgsbase {}", in(reg) base, options(nostack, preserves_flags));
+    }
+}
+
+/// Reads the *Time-Stamp Counter*.
+///
+/// # Monotonicity
+///
+/// The TSC is only guaranteed to increase at a constant rate, regardless of the power state of
+/// the CPU, when it is *invariant*. This is reported by bit 8 of EDX for the CPUID leaf
+/// `0x80000007` (see [`cpuid`](crate::cpuid())).
+///
+/// The instruction is not serializing: it may execute before the instructions that precede it.
+/// See [`rdtsc_serialized`] for measurements.
+#[inline(always)]
+pub fn rdtsc() -> u64 {
+    let low: u32;
+    let high: u32;
+    unsafe {
+        asm!("rdtsc", out("eax") low, out("edx") high, options(nomem, nostack, preserves_flags));
+    }
+    (high as u64) << 32 | low as u64
+}
+
+/// Reads the *Time-Stamp Counter*, after every preceding instruction has completed.
+///
+/// An `lfence` is executed before the counter is read, preventing out-of-order execution from
+/// skewing measurements. See [`rdtsc`] for more information.
+#[inline(always)]
+pub fn rdtsc_serialized() -> u64 {
     let low: u32;
     let high: u32;
     unsafe {
-        asm!("rdmsr", in("ecx") port, out("eax") low, out("edx") high, options(nomem, nostack, preserves_flags));
+        asm!(
+            "lfence",
+            "rdtsc",
+            out("eax") low,
+            out("edx") high,
+            options(nomem, nostack, preserves_flags),
+        );
     }
-    ((high as u64) << 32) | (low as u64)
+    (high as u64) << 32 | low as u64
 }
 
-/// Writes a value to a specific *Model Specific Register* (MSR).
+/// Reads the *Time-Stamp Counter*, along with the value of the `IA32_TSC_AUX` MSR.
+///
+/// The operating system usually stores the identifier of the current processor in
+/// `IA32_TSC_AUX`, allowing callers to detect that two readings were made on different CPUs.
+///
+/// Unlike [`rdtsc`], this instruction waits for the preceding instructions to complete before
+/// reading the counter. See [`rdtsc`] for more information.
 #[inline(always)]
-pub unsafe fn wrmsr(port: u32, value: u64) {
-    let low = value as u32;
-    let high = (value >> 32) as u32;
+pub fn rdtscp() -> (u64, u32) {
+    let low: u32;
+    let high: u32;
+    let aux: u32;
     unsafe {
-        asm!("wrmsr", in("ecx") port, in("eax") low, in("edx") high, options(nostack, preserves_flags));
+        asm!(
+            "rdtscp",
+            out("eax") low,
+            out("edx") high,
+            out("ecx") aux,
+            options(nomem, nostack, preserves_flags),
+        );
+    }
+    ((high as u64) << 32 | low as u64, aux)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::format;
+
+    #[test]
+    fn table_ptr_debug() {
+        let ptr = TablePtr {
+            limit: 0xFFF,
+            base: 0xFFFF_8000_0000_1000,
+        };
+        assert_eq!(
+            format!("{ptr:?}"),
+            "TablePtr { base: 0xffff800000001000, limit: 0xfff }"
+        );
+    }
+
+    #[test]
+    fn tsc_increases() {
+        let a = rdtsc_serialized();
+        let b = rdtsc_serialized();
+        assert!(b >= a);
+        assert_ne!(rdtsc(), 0);
     }
 }
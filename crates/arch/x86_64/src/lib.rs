@@ -12,16 +12,24 @@
 #[cfg(not(target_arch = "x86_64"))]
 compile_error!("The `x86_64` crate can only be used on x86_64 machines.");
 
+mod cpuid;
+mod debug_registers;
 mod gdt;
 mod idt;
 mod instructions;
+mod msr;
 mod paging;
+mod port;
 mod registers;
 
+pub use self::cpuid::*;
+pub use self::debug_registers::*;
 pub use self::gdt::*;
 pub use self::idt::*;
 pub use self::instructions::*;
+pub use self::msr::*;
 pub use self::paging::*;
+pub use self::port::*;
 pub use self::registers::*;
 
 /// A virtual address.
@@ -0,0 +1,98 @@
+//! Access to *Model Specific Registers* (MSRs).
+
+#![allow(clippy::missing_safety_doc)]
+
+use core::arch::asm;
+use core::fmt;
+
+/// Reads the value of a specific *Model Specific Register* (MSR).
+#[inline(always)]
+pub unsafe fn rdmsr(port: u32) -> u64 {
+    let low: u32;
+    let high: u32;
+    unsafe {
+        asm!("rdmsr", in("ecx") port, out("eax") low, out("edx") high, options(nomem, nostack, preserves_flags));
+    }
+    ((high as u64) << 32) | (low as u64)
+}
+
+/// Writes a value to a specific *Model Specific Register* (MSR).
+#[inline(always)]
+pub unsafe fn wrmsr(port: u32, value: u64) {
+    let low = value as u32;
+    let high = (value >> 32) as u32;
+    unsafe {
+        asm!("wrmsr", in("ecx") port, in("eax") low, in("edx") high, options(nostack, preserves_flags));
+    }
+}
+
+/// A *Model Specific Register*, identified by its address.
+///
+/// The most commonly used registers are available as associated constants.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct Msr(u32);
+
+impl Msr {
+    /// The `IA32_APIC_BASE` register, which stores the base address and the state of the local
+    /// APIC.
+    pub const IA32_APIC_BASE: Self = Self(0x1B);
+    /// The `IA32_TSC_DEADLINE` register, used by the local APIC timer in TSC-deadline mode.
+    pub const TSC_DEADLINE: Self = Self(0x6E0);
+    /// The `IA32_EFER` register (see [`Efer`](crate::Efer)).
+    pub const IA32_EFER: Self = Self(0xC000_0080);
+    /// The `STAR` register (see [`Star`](crate::Star)).
+    pub const STAR: Self = Self(0xC000_0081);
+    /// The `LSTAR` register, which stores the entry point of the `syscall` instruction.
+    pub const LSTAR: Self = Self(0xC000_0082);
+    /// The `FMASK` register, which stores the **RFLAGS** bits cleared by the `syscall`
+    /// instruction.
+    pub const FMASK: Self = Self(0xC000_0084);
+    /// The `IA32_FS_BASE` register, which stores the base address of the **FS** segment.
+    pub const IA32_FS_BASE: Self = Self(0xC000_0100);
+    /// The `IA32_GS_BASE` register, which stores the base address of the **GS** segment.
+    pub const IA32_GS_BASE: Self = Self(0xC000_0101);
+    /// The `IA32_KERNEL_GS_BASE` register, swapped with the base address of the **GS** segment
+    /// by the `swapgs` instruction.
+    pub const IA32_KERNEL_GS_BASE: Self = Self(0xC000_0102);
+
+    /// Creates a new [`Msr`] referencing the register at the provided address.
+    #[inline(always)]
+    pub const fn new(addr: u32) -> Self {
+        Self(addr)
+    }
+
+    /// Returns the address of the register.
+    #[inline(always)]
+    pub const fn addr(self) -> u32 {
+        self.0
+    }
+
+    /// Reads the value of the register.
+    ///
+    /// # Safety
+    ///
+    /// The register must exist on the current CPU. Otherwise, a general protection fault is
+    /// raised.
+    #[inline(always)]
+    pub unsafe fn read(self) -> u64 {
+        unsafe { rdmsr(self.0) }
+    }
+
+    /// Writes a value to the register.
+    ///
+    /// # Safety
+    ///
+    /// The register must exist on the current CPU, and `value` must be valid for it. Writing to
+    /// some registers may change the way the CPU behaves.
+    #[inline(always)]
+    pub unsafe fn write(self, value: u64) {
+        unsafe { wrmsr(self.0, value) }
+    }
+}
+
+impl fmt::Debug for Msr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Msr({:#x})", self.0)
+    }
+}
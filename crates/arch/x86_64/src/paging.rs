@@ -81,6 +81,9 @@ impl PageTableEntry {
     /// Creates a new null [`PageTableEntry`].
     pub const UNUSED: Self = Self(0);
 
+    /// The bits of an entry that store the physical address it references.
+    const ADDR_MASK: u64 = 0x000f_ffff_ffff_f000;
+
     /// Creates a new [`PageTableEntry`].
     ///
     /// # Notes
@@ -90,7 +93,7 @@ impl PageTableEntry {
     #[inline(always)]
     pub const fn new(addr: PhysAddr, flags: PageTableFlags) -> Self {
         debug_assert!(
-            addr & 0x000f_ffff_ffff_f000 == addr,
+            addr & Self::ADDR_MASK == addr,
             "address must be aligned to a page boundary"
         );
 
@@ -112,7 +115,7 @@ impl PageTableEntry {
     /// Returns the physical address specified by this entry.
     #[inline(always)]
     pub const fn addr(self) -> PhysAddr {
-        self.0 & 0x000f_ffff_ffff_f000
+        self.0 & Self::ADDR_MASK
     }
 
     /// Returns the flags of this entry.
@@ -120,11 +123,57 @@ impl PageTableEntry {
     pub const fn flags(self) -> PageTableFlags {
         PageTableFlags::from_bits_truncate(self.0)
     }
+
+    /// Returns whether the [`PageTableFlags::PRESENT`] flag of this entry is set.
+    #[inline(always)]
+    pub const fn is_present(self) -> bool {
+        self.0 & PageTableFlags::PRESENT.bits() != 0
+    }
+
+    /// Returns whether the [`PageTableFlags::HUGE_PAGE`] flag of this entry is set.
+    #[inline(always)]
+    pub const fn is_huge(self) -> bool {
+        self.0 & PageTableFlags::HUGE_PAGE.bits() != 0
+    }
+
+    /// Replaces the physical address referenced by this entry, preserving its flags.
+    ///
+    /// # Notes
+    ///
+    /// The given address must be aligned to a page boundary (4 KiB), or its lower bits will be
+    /// mixed-up with the flags.
+    #[inline(always)]
+    pub fn set_addr(&mut self, addr: PhysAddr) {
+        debug_assert!(
+            addr & Self::ADDR_MASK == addr,
+            "address must be aligned to a page boundary"
+        );
+
+        self.0 = (self.0 & !Self::ADDR_MASK) | addr;
+    }
+
+    /// Replaces the flags of this entry, preserving the physical address it references.
+    #[inline(always)]
+    pub fn set_flags(&mut self, flags: PageTableFlags) {
+        self.0 = (self.0 & Self::ADDR_MASK) | flags.bits();
+    }
+
+    /// Sets the provided flags on this entry, leaving the other flags unchanged.
+    #[inline(always)]
+    pub fn insert_flags(&mut self, flags: PageTableFlags) {
+        self.0 |= flags.bits();
+    }
+
+    /// Clears the provided flags from this entry, leaving the other flags unchanged.
+    #[inline(always)]
+    pub fn remove_flags(&mut self, flags: PageTableFlags) {
+        self.0 &= !flags.bits();
+    }
 }
 
 impl fmt::Debug for PageTableEntry {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if !self.flags().contains(PageTableFlags::PRESENT) {
+        if !self.is_present() {
             write!(f, "PageTableEntry::NULL")
         } else {
             f.debug_struct("PageTableEntry")
@@ -148,6 +197,42 @@ impl PageTable {
     pub const fn new() -> Self {
         PageTable([PageTableEntry::UNUSED; 512])
     }
+
+    /// Creates a new page table with all of its bits set to zero.
+    ///
+    /// This is the same as [`PageTable::new`], as [`PageTableEntry::UNUSED`] is all zeros.
+    #[inline(always)]
+    pub const fn zeroed() -> Self {
+        Self::new()
+    }
+
+    /// Marks every entry of the page table as unused.
+    ///
+    /// Any bit pattern is a valid [`PageTable`], which means that this function may be used to
+    /// initialize a freshly allocated page by casting it to a [`PageTable`] first.
+    #[inline(always)]
+    pub fn clear(&mut self) {
+        self.0 = [PageTableEntry::UNUSED; 512];
+    }
+
+    /// Returns whether every entry of the page table is unused.
+    ///
+    /// Note that an entry that is not present but has other bits set is *not* considered unused.
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|&e| e == PageTableEntry::UNUSED)
+    }
+
+    /// Returns an iterator over the entries of the page table.
+    #[inline(always)]
+    pub fn iter(&self) -> core::slice::Iter<'_, PageTableEntry> {
+        self.0.iter()
+    }
+
+    /// Returns an iterator over exclusive references to the entries of the page table.
+    #[inline(always)]
+    pub fn iter_mut(&mut self) -> core::slice::IterMut<'_, PageTableEntry> {
+        self.0.iter_mut()
+    }
 }
 
 impl Deref for PageTable {
@@ -166,43 +251,375 @@ impl DerefMut for PageTable {
     }
 }
 
-/// Translates a virtual address into a physical address.
+/// Returns the index of the PML4 (level 4) entry responsible for the given virtual address.
+#[inline(always)]
+pub const fn pml4_index(virt: VirtAddr) -> usize {
+    ((virt >> 39) & 0o777) as usize
+}
+
+/// Returns the index of the PDPT (level 3) entry responsible for the given virtual address.
+#[inline(always)]
+pub const fn pdpt_index(virt: VirtAddr) -> usize {
+    ((virt >> 30) & 0o777) as usize
+}
+
+/// Returns the index of the page directory (level 2) entry responsible for the given virtual
+/// address.
+#[inline(always)]
+pub const fn pd_index(virt: VirtAddr) -> usize {
+    ((virt >> 21) & 0o777) as usize
+}
+
+/// Returns the index of the page table (level 1) entry responsible for the given virtual address.
+#[inline(always)]
+pub const fn pt_index(virt: VirtAddr) -> usize {
+    ((virt >> 12) & 0o777) as usize
+}
+
+/// Returns the offset of the given virtual address within its 4 KiB page.
+#[inline(always)]
+pub const fn page_offset(virt: VirtAddr) -> u64 {
+    virt & 0xfff
+}
+
+/// Sign-extends bit 47 of the provided address, making it canonical.
+#[inline(always)]
+const fn canonicalize(virt: VirtAddr) -> VirtAddr {
+    (((virt << 16) as i64) >> 16) as u64
+}
+
+/// Gives access to the page tables through a recursive entry of the PML4.
+///
+/// When the entry at index `index` of the PML4 references the PML4 itself, every page table of
+/// the hierarchy is accessible at a well-known virtual address. This type computes those
+/// addresses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecursiveMapping {
+    index: u64,
+}
+
+impl RecursiveMapping {
+    /// Creates a new [`RecursiveMapping`] for the given PML4 slot.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `index` is not a valid PML4 index (i.e. greater than 511).
+    #[inline(always)]
+    pub const fn new(index: usize) -> Self {
+        assert!(index < 512, "invalid PML4 index");
+        Self {
+            index: index as u64,
+        }
+    }
+
+    /// Returns the index of the recursive entry in the PML4.
+    #[inline(always)]
+    pub const fn index(self) -> usize {
+        self.index as usize
+    }
+
+    /// Returns the virtual address of the PML4.
+    #[inline(always)]
+    pub const fn pml4(self) -> VirtAddr {
+        let r = self.index;
+        canonicalize((r << 39) | (r << 30) | (r << 21) | (r << 12))
+    }
+
+    /// Returns the virtual address of the PDPT responsible for `virt`.
+    #[inline(always)]
+    pub const fn pdpt(self, virt: VirtAddr) -> VirtAddr {
+        let r = self.index;
+        canonicalize((r << 39) | (r << 30) | (r << 21) | ((pml4_index(virt) as u64) << 12))
+    }
+
+    /// Returns the virtual address of the page directory responsible for `virt`.
+    #[inline(always)]
+    pub const fn pd(self, virt: VirtAddr) -> VirtAddr {
+        let r = self.index;
+        canonicalize(
+            (r << 39)
+                | (r << 30)
+                | ((pml4_index(virt) as u64) << 21)
+                | ((pdpt_index(virt) as u64) << 12),
+        )
+    }
+
+    /// Returns the virtual address of the page table responsible for `virt`.
+    #[inline(always)]
+    pub const fn pt(self, virt: VirtAddr) -> VirtAddr {
+        let r = self.index;
+        canonicalize(
+            (r << 39)
+                | ((pml4_index(virt) as u64) << 30)
+                | ((pdpt_index(virt) as u64) << 21)
+                | ((pd_index(virt) as u64) << 12),
+        )
+    }
+}
+
+/// Translates a virtual address into a physical address, using the currently loaded page tables.
 ///
 /// The `get_table` function is used to convert the physical address of a page table into a
 /// reference to it (e.g. a `&PageTable`).
-pub fn virtual_to_physical<'a, F>(virt: VirtAddr, mut get_table: F) -> Option<PhysAddr>
+pub fn virtual_to_physical<'a, F>(virt: VirtAddr, get_table: F) -> Option<PhysAddr>
+where
+    F: FnMut(PhysAddr) -> &'a PageTable,
+{
+    translate(crate::cr3().addr(), virt, get_table)
+}
+
+/// Translates a virtual address into a physical address, using the page table rooted at `pml4`.
+///
+/// Huge pages (1 GiB and 2 MiB) are supported. The returned address includes the offset of
+/// `virt` within its page.
+///
+/// The `get_table` function is used to convert the physical address of a page table into a
+/// reference to it.
+///
+/// If `virt` is not mapped, [`None`] is returned.
+pub fn translate<'a, F>(pml4: PhysAddr, virt: VirtAddr, mut get_table: F) -> Option<PhysAddr>
 where
     F: FnMut(PhysAddr) -> &'a PageTable,
 {
-    let l4_idx = (virt >> 39) & 0o777;
-    let l3_idx = (virt >> 30) & 0o777;
-    let l2_idx = (virt >> 21) & 0o777;
-    let l1_idx = (virt >> 12) & 0o777;
-    let offset = virt & 0xfff;
-
-    let l4 = get_table(crate::cr3().addr());
-    let l3_entry = unsafe { l4.get_unchecked(l4_idx as usize) };
-    if !l3_entry.flags().contains(PageTableFlags::PRESENT) {
+    let pml4e = get_table(pml4)[pml4_index(virt)];
+    if !pml4e.is_present() {
+        return None;
+    }
+
+    let pdpte = get_table(pml4e.addr())[pdpt_index(virt)];
+    if !pdpte.is_present() {
+        return None;
+    }
+    if pdpte.is_huge() {
+        // The PAT bit of huge pages is bit 12, which is part of `addr()`.
+        return Some((pdpte.addr() & !0x3FFF_FFFF) | (virt & 0x3FFF_FFFF));
+    }
+
+    let pde = get_table(pdpte.addr())[pd_index(virt)];
+    if !pde.is_present() {
+        return None;
+    }
+    if pde.is_huge() {
+        return Some((pde.addr() & !0x1F_FFFF) | (virt & 0x1F_FFFF));
+    }
+
+    let pte = get_table(pde.addr())[pt_index(virt)];
+    if !pte.is_present() {
+        return None;
+    }
+
+    Some(pte.addr() | page_offset(virt))
+}
+
+/// Returns the last-level entry mapping `virt` in the page table rooted at `pml4`.
+///
+/// This is usually a page table entry, but if `virt` is part of a huge page, the page directory
+/// entry (2 MiB pages) or page directory pointer table entry (1 GiB pages) is returned instead.
+///
+/// The `get_table` function is used to convert the physical address of a page table into a
+/// pointer to it. Intermediate tables are only read, and the returned entry is the only part of
+/// the page tables which is borrowed for `'a`.
+///
+/// If `virt` is not mapped, [`None`] is returned.
+///
+/// # Safety
+///
+/// The pointers returned by `get_table` must be valid for reads and writes, and the returned entry
+/// must not be accessed through any other reference for `'a`.
+pub unsafe fn leaf_entry_mut<'a, F>(
+    pml4: PhysAddr,
+    virt: VirtAddr,
+    mut get_table: F,
+) -> Option<&'a mut PageTableEntry>
+where
+    F: FnMut(PhysAddr) -> *mut PageTable,
+{
+    let pml4e = unsafe { (*get_table(pml4))[pml4_index(virt)] };
+    if !pml4e.is_present() {
         return None;
     }
 
-    let l3 = get_table(l3_entry.addr());
-    let l2_entry = unsafe { l3.get_unchecked(l3_idx as usize) };
-    if !l2_entry.flags().contains(PageTableFlags::PRESENT) {
+    let pdpt = get_table(pml4e.addr());
+    let pdpte = unsafe { (*pdpt)[pdpt_index(virt)] };
+    if !pdpte.is_present() {
         return None;
     }
+    if pdpte.is_huge() {
+        return Some(unsafe { &mut (*pdpt)[pdpt_index(virt)] });
+    }
 
-    let l2 = get_table(l2_entry.addr());
-    let l1_entry = unsafe { l2.get_unchecked(l2_idx as usize) };
-    if !l1_entry.flags().contains(PageTableFlags::PRESENT) {
+    let pd = get_table(pdpte.addr());
+    let pde = unsafe { (*pd)[pd_index(virt)] };
+    if !pde.is_present() {
         return None;
     }
+    if pde.is_huge() {
+        return Some(unsafe { &mut (*pd)[pd_index(virt)] });
+    }
 
-    let l1 = get_table(l1_entry.addr());
-    let l1_entry = unsafe { l1.get_unchecked(l1_idx as usize) };
-    if !l1_entry.flags().contains(PageTableFlags::PRESENT) {
+    let pt = get_table(pde.addr());
+    let pte = unsafe { (*pt)[pt_index(virt)] };
+    if !pte.is_present() {
         return None;
     }
 
-    Some((l1_entry.addr() & !0xfff) | offset)
+    Some(unsafe { &mut (*pt)[pt_index(virt)] })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indices() {
+        assert_eq!(pml4_index(0), 0);
+        assert_eq!(pt_index(0x1000), 1);
+        assert_eq!(pd_index(0x20_0000), 1);
+        assert_eq!(pdpt_index(0x4000_0000), 1);
+        assert_eq!(pml4_index(0x80_0000_0000), 1);
+        assert_eq!(page_offset(0x1234_5678), 0x678);
+
+        // The last address of the lower half, and the first of the higher half.
+        let lower = 0x0000_7fff_ffff_ffff;
+        assert_eq!(pml4_index(lower), 255);
+        assert_eq!(pdpt_index(lower), 511);
+        assert_eq!(pd_index(lower), 511);
+        assert_eq!(pt_index(lower), 511);
+        assert_eq!(page_offset(lower), 0xfff);
+        assert_eq!(pml4_index(0xffff_8000_0000_0000), 256);
+
+        // The address at which the kernel is loaded.
+        let kernel = 0xffff_ffff_8000_0000;
+        assert_eq!(pml4_index(kernel), 511);
+        assert_eq!(pdpt_index(kernel), 510);
+        assert_eq!(pd_index(kernel), 0);
+        assert_eq!(pt_index(kernel), 0);
+    }
+
+    #[test]
+    fn recursive_mapping() {
+        let r = RecursiveMapping::new(510);
+        assert_eq!(r.pml4(), 0xffff_ff7f_bfdf_e000);
+        assert_eq!(r.pdpt(0), 0xffff_ff7f_bfc0_0000);
+        assert_eq!(r.pd(0), 0xffff_ff7f_8000_0000);
+        assert_eq!(r.pt(0), 0xffff_ff00_0000_0000);
+        assert_eq!(r.pt(0x20_0000), 0xffff_ff00_0000_1000);
+
+        let r = RecursiveMapping::new(1);
+        assert_eq!(r.pml4(), 0x0000_0080_4020_1000);
+    }
+
+    #[test]
+    fn page_table_clear() {
+        let mut table = PageTable::zeroed();
+        assert!(table.is_empty());
+        assert_eq!(table.iter().count(), 512);
+
+        table[3] = PageTableEntry::new(0x1000, PageTableFlags::PRESENT);
+        assert!(!table.is_empty());
+
+        table.clear();
+        assert!(table.is_empty());
+
+        // Non-present entries with other bits set are not unused.
+        table[511] = PageTableEntry::new(0x2000, PageTableFlags::empty());
+        assert!(!table.is_empty());
+    }
+
+    #[test]
+    fn page_table_iter_mut() {
+        let mut table = PageTable::new();
+        for (i, entry) in table.iter_mut().enumerate().skip(256) {
+            *entry = PageTableEntry::new(i as u64 * 0x1000, PageTableFlags::PRESENT);
+        }
+
+        assert_eq!(table.iter().filter(|e| e.is_present()).count(), 256);
+        assert_eq!(table[300].addr(), 300 * 0x1000);
+        assert!(!table[255].is_present());
+    }
+
+    /// A set of page tables, where the physical address of the table at index `i` is
+    /// `(i + 1) * 0x1000`.
+    struct Tables([PageTable; 4]);
+
+    impl Tables {
+        fn get(&mut self) -> impl FnMut(PhysAddr) -> *mut PageTable {
+            let base = self.0.as_mut_ptr();
+            move |phys| unsafe { base.add(phys as usize / 0x1000 - 1) }
+        }
+    }
+
+    #[test]
+    fn leaf_entry() {
+        let mut tables = Tables([PageTable::new(); 4]);
+        let parent = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+        let virt = 0x40_0000_3000;
+
+        // Map `virt` read-write to 0x1234_5000.
+        tables.0[0][pml4_index(virt)] = PageTableEntry::new(0x2000, parent);
+        tables.0[1][pdpt_index(virt)] = PageTableEntry::new(0x3000, parent);
+        tables.0[2][pd_index(virt)] = PageTableEntry::new(0x4000, parent);
+        tables.0[3][pt_index(virt)] = PageTableEntry::new(0x1234_5000, parent);
+
+        unsafe {
+            assert!(leaf_entry_mut(0x1000, virt + 0x1000, tables.get()).is_none());
+
+            // Writes through the returned entry modify the page table itself.
+            let entry = leaf_entry_mut(0x1000, virt, tables.get()).unwrap();
+            assert_eq!(entry.addr(), 0x1234_5000);
+            entry.set_flags(PageTableFlags::PRESENT);
+        }
+
+        let entry = tables.0[3][pt_index(virt)];
+        assert_eq!(entry.addr(), 0x1234_5000);
+        assert!(!entry.flags().contains(PageTableFlags::WRITABLE));
+    }
+
+    #[test]
+    fn huge_leaf_entry() {
+        let mut tables = Tables([PageTable::new(); 4]);
+        let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+
+        tables.0[0][0] = PageTableEntry::new(0x2000, flags);
+        tables.0[1][1] = PageTableEntry::new(0x4000_0000, flags | PageTableFlags::HUGE_PAGE);
+        tables.0[1][0] = PageTableEntry::new(0x3000, flags);
+        tables.0[2][3] = PageTableEntry::new(0x60_0000, flags | PageTableFlags::HUGE_PAGE);
+
+        unsafe {
+            let entry = leaf_entry_mut(0x1000, 0x4123_4567, tables.get()).unwrap();
+            assert_eq!(entry.addr(), 0x4000_0000);
+
+            let entry = leaf_entry_mut(0x1000, 0x67_8000, tables.get()).unwrap();
+            assert_eq!(entry.addr(), 0x60_0000);
+
+            assert!(leaf_entry_mut(0x1000, 0x80_0000, tables.get()).is_none());
+        }
+    }
+
+    #[test]
+    fn translate_pages() {
+        let mut tables = Tables([PageTable::new(); 4]);
+        let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+
+        // 1 GiB page at 0x4000_0000.
+        tables.0[0][0] = PageTableEntry::new(0x2000, flags);
+        tables.0[1][1] = PageTableEntry::new(0x1_C000_0000, flags | PageTableFlags::HUGE_PAGE);
+        // 2 MiB page at 0x60_0000, with the PAT bit (bit 12) set.
+        tables.0[1][0] = PageTableEntry::new(0x3000, flags);
+        tables.0[2][3] = PageTableEntry::new(0x8020_1000, flags | PageTableFlags::HUGE_PAGE);
+        // 4 KiB page at 0x20_5000.
+        tables.0[2][1] = PageTableEntry::new(0x4000, flags);
+        tables.0[3][5] = PageTableEntry::new(0xABCD_E000, flags);
+
+        let mut get = tables.get();
+        let mut translate = |virt| translate(0x1000, virt, |phys| unsafe { &*get(phys) });
+
+        assert_eq!(translate(0x4123_4567), Some(0x1_C123_4567));
+        assert_eq!(translate(0x67_8ABC), Some(0x8027_8ABC));
+        assert_eq!(translate(0x20_5123), Some(0xABCD_E123));
+
+        assert_eq!(translate(0x20_6000), None);
+        assert_eq!(translate(0x80_0000), None);
+        assert_eq!(translate(0x80_0000_0000), None);
+    }
 }
@@ -0,0 +1,170 @@
+//! Access to port-mapped I/O devices, such as the PIC, the PIT or serial ports.
+
+#![allow(clippy::missing_safety_doc)]
+
+use core::arch::asm;
+use core::fmt;
+use core::marker::PhantomData;
+
+/// Performs a write to the provided I/O port.
+#[inline(always)]
+pub unsafe fn outb(port: u16, value: u8) {
+    unsafe {
+        asm!("out dx, al", in("dx") port, in("al") value, options(nomem, nostack, preserves_flags));
+    }
+}
+
+/// Performs a read on the provided I/O port.
+#[inline(always)]
+pub unsafe fn inb(port: u16) -> u8 {
+    unsafe {
+        let ret: u8;
+        asm!("in al, dx", out("al") ret, in("dx") port, options(nomem, nostack, preserves_flags));
+        ret
+    }
+}
+
+/// Performs a 16-bit write to the provided I/O port.
+#[inline(always)]
+pub unsafe fn outw(port: u16, value: u16) {
+    unsafe {
+        asm!("out dx, ax", in("dx") port, in("ax") value, options(nomem, nostack, preserves_flags));
+    }
+}
+
+/// Performs a 16-bit read on the provided I/O port.
+#[inline(always)]
+pub unsafe fn inw(port: u16) -> u16 {
+    unsafe {
+        let ret: u16;
+        asm!("in ax, dx", out("ax") ret, in("dx") port, options(nomem, nostack, preserves_flags));
+        ret
+    }
+}
+
+/// Performs a 32-bit write to the provided I/O port.
+#[inline(always)]
+pub unsafe fn outl(port: u16, value: u32) {
+    unsafe {
+        asm!("out dx, eax", in("dx") port, in("eax") value, options(nomem, nostack, preserves_flags));
+    }
+}
+
+/// Performs a 32-bit read on the provided I/O port.
+#[inline(always)]
+pub unsafe fn inl(port: u16) -> u32 {
+    unsafe {
+        let ret: u32;
+        asm!("in eax, dx", out("eax") ret, in("dx") port, options(nomem, nostack, preserves_flags));
+        ret
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for u8 {}
+    impl Sealed for u16 {}
+    impl Sealed for u32 {}
+}
+
+/// A value which can be read from or written to an I/O port.
+///
+/// This trait is implemented for [`u8`], [`u16`] and [`u32`], and cannot be implemented outside
+/// of this crate.
+pub trait PortValue: Copy + sealed::Sealed {
+    /// Reads a value from the provided I/O port.
+    unsafe fn read_from(port: u16) -> Self;
+
+    /// Writes a value to the provided I/O port.
+    unsafe fn write_to(port: u16, value: Self);
+}
+
+impl PortValue for u8 {
+    #[inline(always)]
+    unsafe fn read_from(port: u16) -> Self {
+        unsafe { inb(port) }
+    }
+
+    #[inline(always)]
+    unsafe fn write_to(port: u16, value: Self) {
+        unsafe { outb(port, value) }
+    }
+}
+
+impl PortValue for u16 {
+    #[inline(always)]
+    unsafe fn read_from(port: u16) -> Self {
+        unsafe { inw(port) }
+    }
+
+    #[inline(always)]
+    unsafe fn write_to(port: u16, value: Self) {
+        unsafe { outw(port, value) }
+    }
+}
+
+impl PortValue for u32 {
+    #[inline(always)]
+    unsafe fn read_from(port: u16) -> Self {
+        unsafe { inl(port) }
+    }
+
+    #[inline(always)]
+    unsafe fn write_to(port: u16, value: Self) {
+        unsafe { outl(port, value) }
+    }
+}
+
+/// An I/O port through which values of type `T` are read and written.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct Port<T> {
+    port: u16,
+    _marker: PhantomData<T>,
+}
+
+impl<T> Port<T> {
+    /// Creates a new [`Port<T>`] referencing the provided port number.
+    #[inline(always)]
+    pub const fn new(port: u16) -> Self {
+        Self {
+            port,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the port number.
+    #[inline(always)]
+    pub const fn number(self) -> u16 {
+        self.port
+    }
+}
+
+impl<T: PortValue> Port<T> {
+    /// Reads a value from the port.
+    ///
+    /// # Safety
+    ///
+    /// Reading from an I/O port may have side effects on the device behind it.
+    #[inline(always)]
+    pub unsafe fn read(self) -> T {
+        unsafe { T::read_from(self.port) }
+    }
+
+    /// Writes a value to the port.
+    ///
+    /// # Safety
+    ///
+    /// Writing to an I/O port may have side effects on the device behind it.
+    #[inline(always)]
+    pub unsafe fn write(self, value: T) {
+        unsafe { T::write_to(self.port, value) }
+    }
+}
+
+impl<T> fmt::Debug for Port<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Port({:#x})", self.port)
+    }
+}
@@ -2,7 +2,7 @@
 
 use bitflags::bitflags;
 
-use crate::{PhysAddr, PrivilegeLevel, SegmentSelector, VirtAddr};
+use crate::{Msr, PhysAddr, PrivilegeLevel, SegmentSelector, VirtAddr};
 
 use core::arch::asm;
 use core::fmt;
@@ -20,6 +20,7 @@ pub fn rip() -> u64 {
 bitflags! {
     /// The flags that the CPU keeps track of.
     #[derive(Debug, Clone, Copy)]
+    #[repr(transparent)]
     pub struct RFlags: u64 {
         /// Set by the CPU if the last arithmetic operation resulted in a carry out of the
         /// most-significant bit of the result.
@@ -120,6 +121,7 @@ pub fn ss() -> SegmentSelector {
 
 bitflags! {
     /// The flags that the **CR0** register might hold.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub struct Cr0: u64 {
         /// Whether the CPU is running in protected mode.
         const PROTECTED_MODE = 1 << 0;
@@ -155,7 +157,7 @@ bitflags! {
         const CACHE_DISABLE = 1 << 30;
         /// Enables paging.
         ///
-        /// If this bit is set, [`PROTECTED_MODE_ENABLE`](Cr0Flags::PROTECTED_MODE_ENABLE) must be
+        /// If this bit is set, [`PROTECTED_MODE`](Cr0::PROTECTED_MODE) must be
         /// set as well.
         const PAGING = 1 << 31;
     }
@@ -192,7 +194,7 @@ pub fn cr2() -> VirtAddr {
 bitflags! {
     /// The flags that the **CR3** register might hold.
     ///
-    /// Those flags are only applicable if the [`Cr4::PCID`] flag is set.
+    /// Those flags are only applicable if the [`Cr4::PCID`] flag is clear.
     #[derive(Debug, Clone, Copy)]
     pub struct Cr3Flags: u64 {
         /// Use a writethrough cache policy for the P4 table. When left clear, a writeback policy
@@ -208,6 +210,13 @@ bitflags! {
 pub struct Cr3(u64);
 
 impl Cr3 {
+    /// When this bit is set while [`Cr4::PCID`] is enabled, writing to **CR3** does not
+    /// invalidate the TLB entries associated with the new PCID.
+    pub const NO_FLUSH: u64 = 1 << 63;
+
+    /// The largest valid process-context identifier.
+    pub const MAX_PCID: u16 = 0xfff;
+
     /// Creates a new instance of the [`Cr3`] structure from the raw value of the register.
     #[inline(always)]
     pub fn from_raw(raw: u64) -> Self {
@@ -227,6 +236,21 @@ impl Cr3 {
         Self(addr | flags.bits())
     }
 
+    /// Creates a new instance of the structure, tagged with a process-context identifier.
+    ///
+    /// When `no_flush` is set, loading the value into **CR3** keeps the TLB entries associated
+    /// with `pcid`. Otherwise, they are invalidated.
+    ///
+    /// This is only applicable if the [`Cr4::PCID`] flag is set.
+    #[inline(always)]
+    pub fn new_with_pcid(addr: PhysAddr, pcid: u16, no_flush: bool) -> Self {
+        debug_assert!(addr & 0xFFF == 0, "CR3 address must be page aligned");
+        debug_assert!(pcid <= Self::MAX_PCID, "PCID must fit in 12 bits");
+
+        let no_flush = if no_flush { Self::NO_FLUSH } else { 0 };
+        Self(addr | pcid as u64 | no_flush)
+    }
+
     /// Returns the address of the P4 table.
     #[inline(always)]
     pub fn addr(self) -> PhysAddr {
@@ -246,7 +270,7 @@ impl Cr3 {
     /// Note that this is only applicable if the [`Cr4::PCID`] flag is set.
     #[inline(always)]
     pub fn pcid(self) -> u16 {
-        (self.0 >> 12) as u16 & 0xfff
+        self.0 as u16 & Self::MAX_PCID
     }
 }
 
@@ -285,6 +309,7 @@ pub unsafe fn set_cr3(cr3: Cr3) {
 
 bitflags! {
     /// The flag that may be set in the **CR4** register.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub struct Cr4: u64 {
         /// Enables hardware-supported performance enhancements for software running in
         /// virtual-8086 mode.
@@ -297,7 +322,7 @@ bitflags! {
         /// as reserved.
         const DEBUGGING_EXTENSIONS = 1 << 3;
         /// Enables the use of 4MB physical frames; ignored if
-        /// [`PHYSICAL_ADDRESS_EXTENSION`](Cr4Flags::PHYSICAL_ADDRESS_EXTENSION)
+        /// [`PHYSICAL_ADDRESS_EXTENSION`](Cr4::PHYSICAL_ADDRESS_EXTENSION)
         /// is set.
         ///
         /// This is always ignored in long mode.
@@ -382,13 +407,93 @@ pub unsafe fn set_cr4(cr4: Cr4) {
     }
 }
 
+/// Reads the value of the extended control register `xcr` using the **XGETBV** instruction.
+///
+/// This requires the [`OSXSAVE`](Cr4::OSXSAVE) bit of **CR4** to be set.
+#[inline(always)]
+pub fn xgetbv(xcr: u32) -> u64 {
+    debug_assert!(cr4().contains(Cr4::OSXSAVE), "XGETBV requires CR4.OSXSAVE");
+
+    let low: u32;
+    let high: u32;
+    unsafe {
+        asm!("xgetbv", in("ecx") xcr, out("eax") low, out("edx") high, options(nomem, nostack, preserves_flags));
+    }
+    ((high as u64) << 32) | (low as u64)
+}
+
+/// Writes `val` to the extended control register `xcr` using the **XSETBV** instruction.
+///
+/// This requires the [`OSXSAVE`](Cr4::OSXSAVE) bit of **CR4** to be set.
+#[inline(always)]
+pub unsafe fn xsetbv(xcr: u32, val: u64) {
+    debug_assert!(cr4().contains(Cr4::OSXSAVE), "XSETBV requires CR4.OSXSAVE");
+
+    let low = val as u32;
+    let high = (val >> 32) as u32;
+    unsafe {
+        asm!("xsetbv", in("ecx") xcr, in("eax") low, in("edx") high, options(nomem, nostack, preserves_flags));
+    }
+}
+
+bitflags! {
+    /// The flags of the **XCR0** register, selecting the processor state components managed by
+    /// the `XSAVE` feature set.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Xcr0: u64 {
+        /// The x87 FPU state. This bit is always set.
+        const X87 = 1 << 0;
+        /// The SSE state (the `xmm` registers and `MXCSR`).
+        const SSE = 1 << 1;
+        /// The upper halves of the `ymm` registers. Requires [`SSE`](Xcr0::SSE).
+        const AVX = 1 << 2;
+        /// The MPX bound registers.
+        const BNDREG = 1 << 3;
+        /// The MPX configuration and status registers.
+        const BNDCSR = 1 << 4;
+        /// The AVX-512 opmask registers.
+        const OPMASK = 1 << 5;
+        /// The upper halves of the first 16 `zmm` registers.
+        const ZMM_HI256 = 1 << 6;
+        /// The last 16 `zmm` registers.
+        const HI16_ZMM = 1 << 7;
+        /// The protection key rights register for user pages.
+        const PKRU = 1 << 9;
+    }
+}
+
+/// Returns the value of the **XCR0** register.
+///
+/// This requires the [`OSXSAVE`](Cr4::OSXSAVE) bit of **CR4** to be set.
+#[inline(always)]
+pub fn xcr0() -> Xcr0 {
+    Xcr0::from_bits_retain(xgetbv(0))
+}
+
+/// Sets the value of the **XCR0** register.
+///
+/// This requires the [`OSXSAVE`](Cr4::OSXSAVE) bit of **CR4** to be set. Setting a bit which is
+/// not supported by the CPU (as reported by CPUID leaf `0xD`) raises a general protection fault.
+#[inline(always)]
+pub unsafe fn set_xcr0(xcr0: Xcr0) {
+    unsafe { xsetbv(0, xcr0.bits()) }
+}
+
+/// Enables the processor state components of `mask`, keeping those which are already enabled.
+///
+/// This requires the [`OSXSAVE`](Cr4::OSXSAVE) bit of **CR4** to be set.
+#[inline]
+pub unsafe fn enable_extended_states(mask: Xcr0) {
+    unsafe { set_xcr0(xcr0() | mask) }
+}
+
 /// The value of **AMD**'s **STAR** register.
 #[derive(Clone, Copy)]
 pub struct Star(u64);
 
 impl Star {
     /// The value of the **STAR** register.
-    pub const MSR: u32 = 0xC000_0081;
+    pub const MSR: u32 = Msr::STAR.addr();
 
     /// Creates a new `Star` value.
     #[inline(always)]
@@ -484,33 +589,51 @@ impl fmt::Debug for Star {
 /// The value of **AMD**'s **STAR** register.
 #[inline(always)]
 pub fn star() -> Star {
-    unsafe { Star::from_raw(crate::rdmsr(Star::MSR)) }
+    unsafe { Star::from_raw(Msr::STAR.read()) }
 }
 
 /// Sets the value of the **STAR** register.
 #[inline(always)]
 pub unsafe fn set_star(star: Star) {
     unsafe {
-        crate::wrmsr(Star::MSR, star.to_raw());
+        Msr::STAR.write(star.to_raw());
     }
 }
 
-const LSTAR: u32 = 0xC000_0082;
-
 /// The value of **AMD**'s **LSTAR** register.
 ///
 /// This is the instruction pointer that will be loaded when the **SYSCALL** instruction is
 /// executed.
 #[inline(always)]
 pub fn lstar() -> VirtAddr {
-    unsafe { crate::rdmsr(LSTAR) }
+    unsafe { Msr::LSTAR.read() }
 }
 
 /// Sets the value of the **LSTAR** register.
 #[inline(always)]
 pub unsafe fn set_lstar(lstar: VirtAddr) {
     unsafe {
-        crate::wrmsr(LSTAR, lstar);
+        Msr::LSTAR.write(lstar);
+    }
+}
+
+/// Returns the value of **AMD**'s **FMASK** register.
+///
+/// The flags set in this mask are cleared from **RFLAGS** when the **SYSCALL** instruction is
+/// executed. The original value of **RFLAGS** is saved in `r11`.
+#[inline(always)]
+pub fn fmask() -> RFlags {
+    unsafe { RFlags::from_bits_retain(Msr::FMASK.read()) }
+}
+
+/// Sets the value of the **FMASK** register.
+///
+/// The flags set in `mask` will be cleared from **RFLAGS** when the **SYSCALL** instruction is
+/// executed.
+#[inline(always)]
+pub unsafe fn set_fmask(mask: RFlags) {
+    unsafe {
+        Msr::FMASK.write(mask.bits());
     }
 }
 
@@ -528,23 +651,125 @@ bitflags! {
         /// Set when the IA32e mode is active.
         const IA32_MODE_ENABLE_ACTIVE = 1 << 10;
 
-        ///
+        /// Enables the [`PageTableFlags::NO_EXECUTE`](crate::PageTableFlags::NO_EXECUTE) bit of
+        /// page table entries.
         const EXECUTE_DISABLE = 1 << 11;
     }
 }
 
-const IA32_EFER: u32 = 0xC000_0080;
-
 /// Returns the value of the **EFER** register.
 #[inline(always)]
 pub fn efer() -> Efer {
-    unsafe { Efer::from_bits_retain(crate::rdmsr(IA32_EFER)) }
+    unsafe { Efer::from_bits_retain(Msr::IA32_EFER.read()) }
 }
 
 /// Sets the value of the **EFER** register.
 #[inline(always)]
 pub unsafe fn set_efer(efer: Efer) {
     unsafe {
-        crate::wrmsr(IA32_EFER, efer.bits());
+        Msr::IA32_EFER.write(efer.bits());
+    }
+}
+
+/// Returns the base address of the **FS** segment, read from the **IA32_FS_BASE** register.
+///
+/// Unlike [`rdfsbase`](crate::rdfsbase), this is always available, but it can only be used in
+/// ring 0.
+#[inline(always)]
+pub fn fs_base() -> VirtAddr {
+    unsafe { Msr::IA32_FS_BASE.read() }
+}
+
+/// Sets the base address of the **FS** segment through the **IA32_FS_BASE** register.
+///
+/// Unlike [`wrfsbase`](crate::wrfsbase), this is always available, but it can only be used in
+/// ring 0.
+#[inline(always)]
+pub unsafe fn set_fs_base(base: VirtAddr) {
+    unsafe {
+        Msr::IA32_FS_BASE.write(base);
+    }
+}
+
+/// Returns the base address of the **GS** segment, read from the **IA32_GS_BASE** register.
+///
+/// Unlike [`rdgsbase`](crate::rdgsbase), this is always available, but it can only be used in
+/// ring 0.
+#[inline(always)]
+pub fn gs_base() -> VirtAddr {
+    unsafe { Msr::IA32_GS_BASE.read() }
+}
+
+/// Sets the base address of the **GS** segment through the **IA32_GS_BASE** register.
+///
+/// Unlike [`wrgsbase`](crate::wrgsbase), this is always available, but it can only be used in
+/// ring 0.
+#[inline(always)]
+pub unsafe fn set_gs_base(base: VirtAddr) {
+    unsafe {
+        Msr::IA32_GS_BASE.write(base);
+    }
+}
+
+/// Returns the value of the **IA32_KERNEL_GS_BASE** register.
+///
+/// This is the value that will be swapped with the base address of the **GS** segment when the
+/// [`swapgs`](crate::swapgs) instruction is executed.
+#[inline(always)]
+pub fn kernel_gs_base() -> VirtAddr {
+    unsafe { Msr::IA32_KERNEL_GS_BASE.read() }
+}
+
+/// Sets the value of the **IA32_KERNEL_GS_BASE** register.
+///
+/// This is the value that will be swapped with the base address of the **GS** segment when the
+/// [`swapgs`](crate::swapgs) instruction is executed.
+#[inline(always)]
+pub unsafe fn set_kernel_gs_base(base: VirtAddr) {
+    unsafe {
+        Msr::IA32_KERNEL_GS_BASE.write(base);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DescriptorTable;
+
+    #[test]
+    fn star_round_trip() {
+        let sysret_base = SegmentSelector::new(2, DescriptorTable::Gdt, PrivilegeLevel::Ring3);
+        let syscall_base = SegmentSelector::new(1, DescriptorTable::Gdt, PrivilegeLevel::Ring0);
+        let star = Star::new(sysret_base, syscall_base);
+
+        assert_eq!(star.cs_syscall().to_raw(), syscall_base.to_raw());
+        assert_eq!(star.ss_syscall().index(), 2);
+        assert_eq!(
+            star.ss_syscall().requested_privilege_level(),
+            PrivilegeLevel::Ring0
+        );
+        assert_eq!(star.cs_sysret().index(), 4);
+        assert_eq!(
+            star.cs_sysret().requested_privilege_level(),
+            PrivilegeLevel::Ring3
+        );
+        assert_eq!(star.ss_sysret().index(), 3);
+        assert_eq!(
+            star.ss_sysret().requested_privilege_level(),
+            PrivilegeLevel::Ring3
+        );
+    }
+
+    #[test]
+    fn cr3_with_pcid() {
+        let cr3 = Cr3::new_with_pcid(0x1234_5000, 0xABC, true);
+        assert_eq!(cr3.addr(), 0x1234_5000);
+        assert_eq!(cr3.pcid(), 0xABC);
+        assert_ne!(cr3.to_raw() & Cr3::NO_FLUSH, 0);
+
+        let cr3 = Cr3::new_with_pcid(0x1000, 1, false);
+        assert_eq!(cr3.addr(), 0x1000);
+        assert_eq!(cr3.pcid(), 1);
+        assert_eq!(cr3.to_raw() & Cr3::NO_FLUSH, 0);
     }
 }
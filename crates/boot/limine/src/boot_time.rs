@@ -0,0 +1,44 @@
+use core::fmt;
+
+use crate::Feature;
+
+/// Requests the Limine bootloader to provide the time at which the system was booted.
+///
+/// <https://github.com/limine-bootloader/limine/blob/v4.x-branch/PROTOCOL.md#boot-time-feature>
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct BootTime;
+
+/// The response to the [`BootTime`] request.
+///
+/// Revision 0 of the response only contains the boot time.
+#[repr(C)]
+pub struct BootTimeResponse {
+    /// The UNIX timestamp at which the system was booted, in seconds.
+    boot_time: i64,
+}
+
+impl BootTimeResponse {
+    /// Returns the UNIX timestamp, in seconds, at which the system was booted.
+    ///
+    /// This value is read by the bootloader from the real-time clock.
+    #[inline(always)]
+    pub fn boot_time(&self) -> i64 {
+        self.boot_time
+    }
+}
+
+impl fmt::Debug for BootTimeResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BootTimeResponse")
+            .field("boot_time", &self.boot_time)
+            .finish()
+    }
+}
+
+impl Feature for BootTime {
+    type Response = BootTimeResponse;
+    const MAGIC: [u64; 2] = [0x502746e184c088aa, 0xfbc5ec83e6327893];
+    const EXPECTED_REVISION: u64 = 0;
+    const REVISION: u64 = 0;
+}
@@ -3,13 +3,20 @@ use core::fmt;
 use crate::Feature;
 
 /// Requests some information about the bootloader responding to Limine requests.
+///
+/// <https://github.com/limine-bootloader/limine/blob/v4.x-branch/PROTOCOL.md#bootloader-info-feature>
 #[derive(Debug)]
 #[repr(transparent)]
 pub struct BootloaderInfo;
 
 /// The response to the [`BootloaderInfo`] request.
+///
+/// Revision 0 of the response only contains the name and the version of the bootloader.
+#[repr(C)]
 pub struct BootloaderInfoResponse {
+    /// A null-terminated ASCII string containing the name of the bootloader.
     name: *const i8,
+    /// A null-terminated ASCII string containing the version of the bootloader.
     version: *const i8,
 }
 
@@ -20,6 +20,10 @@ impl FramebufferResponse {
     /// Returns a slice over the framebuffers reported by Limine.
     #[inline(always)]
     pub fn framebuffers(&self) -> &[&Framebuffer] {
+        if self.framebuffer_count == 0 || self.framebuffers.is_null() {
+            return &[];
+        }
+
         unsafe {
             core::slice::from_raw_parts(
                 self.framebuffers as *const &Framebuffer,
@@ -31,6 +35,10 @@ impl FramebufferResponse {
     /// Returns a slice over the framebuffers reported by Limine.
     #[inline(always)]
     pub fn framebuffers_mut(&mut self) -> &mut [&mut Framebuffer] {
+        if self.framebuffer_count == 0 || self.framebuffers.is_null() {
+            return &mut [];
+        }
+
         unsafe {
             core::slice::from_raw_parts_mut(
                 self.framebuffers as *mut &mut Framebuffer,
@@ -38,6 +46,42 @@ impl FramebufferResponse {
             )
         }
     }
+
+    /// Returns an iterator over the framebuffers reported by Limine.
+    ///
+    /// Unlike [`FramebufferResponse::framebuffers`], this function does not assume that every
+    /// entry of the list is non-null: null entries are skipped.
+    pub fn iter(&self) -> impl Iterator<Item = &Framebuffer> {
+        let raw: &[*const Framebuffer] =
+            if self.framebuffer_count == 0 || self.framebuffers.is_null() {
+                &[]
+            } else {
+                unsafe {
+                    core::slice::from_raw_parts(
+                        self.framebuffers as *const *const Framebuffer,
+                        self.framebuffer_count as usize,
+                    )
+                }
+            };
+
+        raw.iter().filter_map(|&fb| unsafe { fb.as_ref() })
+    }
+
+    /// Returns the first framebuffer reported by Limine, if any.
+    #[inline(always)]
+    pub fn first(&self) -> Option<&Framebuffer> {
+        self.iter().next()
+    }
+
+    /// Returns the best video mode supported by any of the framebuffers, along with the
+    /// framebuffer supporting it.
+    ///
+    /// See [`Framebuffer::best_mode`] for more information.
+    pub fn best_mode(&self, max_width: u64, max_height: u64) -> Option<(&Framebuffer, &VideoMode)> {
+        self.iter()
+            .filter_map(|fb| Some((fb, fb.best_mode(max_width, max_height)?)))
+            .max_by_key(|(_, mode)| mode.sort_key())
+    }
 }
 
 impl fmt::Debug for FramebufferResponse {
@@ -86,23 +130,22 @@ impl Framebuffer {
         self.address
     }
 
+    /// Returns the size of the video memory owned by this framebuffer, in bytes.
+    #[inline(always)]
+    pub fn byte_len(&self) -> usize {
+        self.pitch as usize * self.height as usize
+    }
+
     /// Returns a slice over the video memory owned by this framebuffer.
     #[inline(always)]
     pub fn data(&self) -> &[u8] {
-        unsafe {
-            core::slice::from_raw_parts(self.address, self.pitch as usize * self.height as usize)
-        }
+        unsafe { core::slice::from_raw_parts(self.address, self.byte_len()) }
     }
 
     /// Returns a slice over the video memory owned by this framebuffer.
     #[inline(always)]
     pub fn data_mut(&mut self) -> &mut [u8] {
-        unsafe {
-            core::slice::from_raw_parts_mut(
-                self.address,
-                self.pitch as usize * self.height as usize,
-            )
-        }
+        unsafe { core::slice::from_raw_parts_mut(self.address, self.byte_len()) }
     }
 
     /// Returns the width of the framebuffer, in pixels.
@@ -186,6 +229,22 @@ impl Framebuffer {
             core::slice::from_raw_parts(self.modes as *const &VideoMode, self.mode_count as usize)
         }
     }
+
+    /// Returns the largest video mode supported by this framebuffer whose dimensions do not
+    /// exceed `max_width` and `max_height`.
+    ///
+    /// Modes are compared by their number of pixels first, then by their number of bits per
+    /// pixel.
+    ///
+    /// Note that the Limine protocol does not allow the kernel to change the current video mode.
+    /// This is mainly useful to report which mode the kernel would have preferred.
+    pub fn best_mode(&self, max_width: u64, max_height: u64) -> Option<&VideoMode> {
+        self.video_modes()
+            .iter()
+            .copied()
+            .filter(|mode| mode.width <= max_width && mode.height <= max_height)
+            .max_by_key(|mode| mode.sort_key())
+    }
 }
 
 impl fmt::Debug for Framebuffer {
@@ -218,6 +277,12 @@ pub struct VideoMode {
 }
 
 impl VideoMode {
+    /// Returns the key used to compare video modes when looking for the best one.
+    #[inline(always)]
+    fn sort_key(&self) -> (u64, u64) {
+        (self.width * self.height, self.bpp)
+    }
+
     /// Returns the number of bytes each row of pixels in this video mode occupies.
     #[inline(always)]
     pub fn pitch(&self) -> u64 {
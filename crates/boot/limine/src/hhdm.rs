@@ -3,13 +3,21 @@ use core::fmt;
 use crate::Feature;
 
 /// Requests the Limine bootloader to provide the physical address of *Higher Half Direct Map*.
+///
+/// <https://github.com/limine-bootloader/limine/blob/v4.x-branch/PROTOCOL.md#hhdm-higher-half-direct-map-feature>
 #[derive(Debug)]
 #[repr(transparent)]
 pub struct Hhdm;
 
 /// The response to the [`Hhdm`] request.
+///
+/// Revision 0 of the response only contains the offset of the direct map. The revision of the
+/// response itself can be checked with [`Request::response_revision`].
+///
+/// [`Request::response_revision`]: crate::Request::response_revision
 #[repr(C)]
 pub struct HhdmResponse {
+    /// The virtual address offset of the beginning of the higher half direct map.
     offset: u64,
 }
 
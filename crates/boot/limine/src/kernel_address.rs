@@ -3,14 +3,20 @@ use core::fmt;
 use crate::Feature;
 
 /// Requests the Limine bootloader to provide the physical address of the kernel.
+///
+/// <https://github.com/limine-bootloader/limine/blob/v4.x-branch/PROTOCOL.md#kernel-address-feature>
 #[derive(Debug)]
 #[repr(transparent)]
 pub struct KernelAddress;
 
 /// The response to the [`KernelAddress`] request.
+///
+/// Revision 0 of the response contains the physical and virtual base addresses of the kernel.
 #[repr(C)]
 pub struct KernelAddressResponse {
+    /// The physical base address of the kernel.
     physical_base: u64,
+    /// The virtual base address of the kernel.
     virtual_base: u64,
 }
 
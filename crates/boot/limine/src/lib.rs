@@ -14,6 +14,7 @@
 #![warn(missing_debug_implementations)]
 #![warn(missing_docs)]
 
+mod boot_time;
 mod bootloader_info;
 mod entry_point;
 mod framebuffer;
@@ -21,8 +22,10 @@ mod hhdm;
 mod kernel_address;
 mod memory_map;
 mod module;
+mod rsdp;
 mod smp;
 
+pub use self::boot_time::*;
 pub use self::bootloader_info::*;
 pub use self::entry_point::*;
 pub use self::framebuffer::*;
@@ -30,6 +33,7 @@ pub use self::hhdm::*;
 pub use self::kernel_address::*;
 pub use self::memory_map::*;
 pub use self::module::*;
+pub use self::rsdp::*;
 pub use self::smp::*;
 
 use core::fmt;
@@ -106,6 +110,15 @@ impl<Feat: Feature> Request<Feat> {
         unsafe { self.response.get().as_ref() }
     }
 
+    /// Returns the revision number of the response, if the bootloader responded to the request.
+    ///
+    /// Unlike [`Request::response`], this is available even if the revision is too old for the
+    /// payload to be read, making it possible to detect protocol-version mismatches.
+    #[inline(always)]
+    pub fn response_revision(&self) -> Option<u64> {
+        self.raw_response().map(Response::revision)
+    }
+
     /// Returns an exclusive reference to the raw response object of this request.
     ///
     /// This is useful if you want to check the revision number of the request yourself.
@@ -3,20 +3,41 @@ use core::fmt;
 use crate::Feature;
 
 /// Requests the Limine bootloader to provide a map of the available physical memory.
+///
+/// <https://github.com/limine-bootloader/limine/blob/v4.x-branch/PROTOCOL.md#memory-map-feature>
 #[derive(Debug)]
 #[repr(transparent)]
 pub struct MemoryMap;
 
 /// The response of to the [`MemoryMap`] request.
+///
+/// Revision 0 of the response only contains the list of entries.
 #[repr(C)]
 pub struct MemoryMapResponse {
+    /// The number of entries in the memory map.
     entry_count: u64,
+    /// A pointer to an array of `entry_count` pointers to [`MemMapEntry`] structures.
     entries: *mut *mut MemMapEntry,
 }
 
 impl MemoryMapResponse {
     /// Returns the number of entries in the memory map.
     #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.entry_count as usize
+    }
+
+    /// Returns whether the memory map contains no entries.
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.entry_count == 0
+    }
+
+    /// Returns the entries of the memory map.
+    ///
+    /// The entries are guaranteed to be sorted by base address, lowest to highest, and usable
+    /// entries never overlap.
+    #[inline(always)]
     pub fn entries(&self) -> &[&MemMapEntry] {
         unsafe {
             core::slice::from_raw_parts(
@@ -42,8 +63,11 @@ unsafe impl Sync for MemoryMapResponse {}
 #[repr(C)]
 #[derive(Clone, Copy)]
 pub struct MemMapEntry {
+    /// The physical base address of the region.
     base: u64,
+    /// The length of the region, in bytes.
     length: u64,
+    /// The type of the region.
     ty: MemMapEntryType,
 }
 
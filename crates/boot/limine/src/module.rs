@@ -102,9 +102,18 @@ impl Module {
     /// Returns the internal modules referenced by the structure.
     #[inline(always)]
     pub fn internal_modules(&self) -> &'static [&'static InternalModule] {
+        debug_assert!(
+            self.internal_module_count == 0 || !self.internal_modules.is_null(),
+            "`Module` has a non-zero module count but a null module list",
+        );
+
+        if self.internal_module_count == 0 {
+            return &[];
+        }
+
         unsafe {
             core::slice::from_raw_parts(
-                self.internal_module_count as *const &'static InternalModule,
+                self.internal_modules as *const &'static InternalModule,
                 self.internal_module_count as usize,
             )
         }
@@ -141,6 +150,16 @@ impl ModuleResponse {
         }
     }
 
+    /// Returns the first module whose file name (the last component of its path) is `filename`.
+    ///
+    /// Modules whose [`FileResponse`] has an unsupported revision are ignored.
+    pub fn find_by_filename(&self, filename: &[u8]) -> Option<&File> {
+        self.modules()
+            .iter()
+            .filter_map(|m| m.file())
+            .find(|f| f.filename().to_bytes() == filename)
+    }
+
     /// Returns an exclusive slice over the files that were loaded as kernel modules.
     #[inline(always)]
     pub fn modules_mut(&mut self) -> &mut [&mut FileResponse] {
@@ -172,6 +191,39 @@ pub struct Uuid {
     pub d: [u8; 8],
 }
 
+/// The type of media a [`File`] was loaded from.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct MediaType(u32);
+
+impl MediaType {
+    /// The file was loaded from a generic storage device.
+    pub const GENERIC: Self = Self(0);
+    /// The file was loaded from an optical disk.
+    pub const OPTICAL: Self = Self(1);
+    /// The file was loaded over the network, using TFTP.
+    pub const TFTP: Self = Self(2);
+}
+
+impl MediaType {
+    /// Returns a string representation of this value.
+    pub const fn name(&self) -> &'static str {
+        match *self {
+            Self::GENERIC => "GENERIC",
+            Self::OPTICAL => "OPTICAL",
+            Self::TFTP => "TFTP",
+            _ => "UNKNOWN",
+        }
+    }
+}
+
+impl fmt::Debug for MediaType {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
 /// A response containing a file.
 ///
 /// Because this structure contains a *revision number*, it has to be checked before accessing
@@ -270,7 +322,7 @@ pub struct File {
     size: u64,
     path: *mut i8,
     cmdline: *mut i8,
-    media_type: u32,
+    media_type: MediaType,
     _unused: u32,
     tftp_ip: u32,
     tftp_port: u32,
@@ -298,11 +350,35 @@ impl File {
     }
 
     /// Returns a slice over the memory backing this file.
+    ///
+    /// # Correctness
+    ///
+    /// This function assumes that the bootloader provided a valid address. If the address is
+    /// null, the returned slice is invalid. Use [`File::try_data`] to check this.
     #[inline(always)]
     pub fn data(&self) -> &[u8] {
         unsafe { core::slice::from_raw_parts(self.address, self.size as usize) }
     }
 
+    /// Returns a slice over the memory backing this file.
+    ///
+    /// If the file is empty, or if the bootloader provided a null address (which might happen
+    /// when the module failed to load), [`None`] is returned.
+    #[inline]
+    pub fn try_data(&self) -> Option<&[u8]> {
+        if self.address.is_null() || self.size == 0 {
+            None
+        } else {
+            Some(self.data())
+        }
+    }
+
+    /// Returns the content of the file if it starts with the ELF magic number.
+    #[inline]
+    pub fn as_elf(&self) -> Option<ElfFile> {
+        self.try_data().and_then(ElfFile::new)
+    }
+
     /// Returns a slice over the memory backing this file.
     #[inline(always)]
     pub fn data_mut(&mut self) -> &mut [u8] {
@@ -321,11 +397,118 @@ impl File {
         unsafe { CStr::from_ptr(self.path) }
     }
 
+    /// Returns the name of the file, without the directories leading to it.
+    ///
+    /// This is the part of [`File::path`] after the last `/` character. If the path ends with a
+    /// `/`, the returned name is empty.
+    pub fn filename(&self) -> &CStr {
+        let path = self.path().to_bytes_with_nul();
+
+        let start = match path.iter().rposition(|&b| b == b'/') {
+            Some(slash) => slash + 1,
+            None => 0,
+        };
+
+        // The suffix of a nul-terminated string is always nul-terminated.
+        CStr::from_bytes_until_nul(&path[start..]).unwrap_or_default()
+    }
+
     /// Returns the cmdline associated with the file.
     #[inline(always)]
     pub fn cmdline(&self) -> &CStr {
         unsafe { CStr::from_ptr(self.cmdline) }
     }
+
+    /// Returns the type of media the file was loaded from.
+    #[inline(always)]
+    pub fn media_type(&self) -> MediaType {
+        self.media_type
+    }
+
+    /// If the file was loaded over TFTP, returns the IP address and port of the server it was
+    /// loaded from.
+    ///
+    /// Both values are in network byte order.
+    #[inline]
+    pub fn tftp_info(&self) -> Option<(u32, u32)> {
+        if self.media_type == MediaType::TFTP {
+            Some((self.tftp_ip, self.tftp_port))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the 1-based index of the partition the file was loaded from.
+    ///
+    /// If the file was not loaded from a partitioned volume, this is `0`.
+    #[inline(always)]
+    pub fn partition_index(&self) -> u32 {
+        self.partition_index
+    }
+
+    /// Returns the MBR disk ID of the volume the file was loaded from.
+    ///
+    /// If the volume is not MBR-formatted, this is `0`.
+    #[inline(always)]
+    pub fn mbr_disk_id(&self) -> u32 {
+        self.mbr_disk_id
+    }
+
+    /// Returns the GPT disk UUID of the volume the file was loaded from.
+    ///
+    /// If the volume is not GPT-formatted, this is all zeroes.
+    #[inline(always)]
+    pub fn gpt_disk_uuid(&self) -> Uuid {
+        self.gpt_disk_uuid
+    }
+
+    /// Returns the GPT partition UUID of the partition the file was loaded from.
+    ///
+    /// If the volume is not GPT-formatted, this is all zeroes.
+    #[inline(always)]
+    pub fn gpt_partition_uuid(&self) -> Uuid {
+        self.gpt_part_uuid
+    }
+
+    /// Returns the filesystem UUID of the partition the file was loaded from.
+    ///
+    /// If the filesystem does not have a UUID, this is all zeroes.
+    #[inline(always)]
+    pub fn partition_uuid(&self) -> Uuid {
+        self.part_uuid
+    }
+}
+
+/// The content of a [`File`] which starts with the ELF magic number.
+///
+/// Nothing else is checked: the header of the file must be validated by the ELF loader before it
+/// is trusted.
+#[derive(Clone, Copy)]
+pub struct ElfFile<'a>(&'a [u8]);
+
+impl<'a> ElfFile<'a> {
+    /// The magic number at the start of every ELF file.
+    const MAGIC: &'static [u8] = b"\x7fELF";
+
+    /// Returns [`None`] if `data` does not start with the ELF magic number.
+    #[inline]
+    pub fn new(data: &'a [u8]) -> Option<Self> {
+        data.starts_with(Self::MAGIC).then_some(Self(data))
+    }
+
+    /// Returns the whole content of the file.
+    #[inline(always)]
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+}
+
+impl<'a> fmt::Debug for ElfFile<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ElfFile")
+            .field("size", &self.0.len())
+            .finish()
+    }
 }
 
 impl fmt::Debug for File {
@@ -335,6 +518,7 @@ impl fmt::Debug for File {
             .field("size", &self.size)
             .field("path", &self.path())
             .field("cmdline", &self.cmdline())
+            .field("media_type", &self.media_type)
             .finish_non_exhaustive()
     }
 }
@@ -345,3 +529,92 @@ impl Feature for Module {
     const EXPECTED_REVISION: u64 = 1;
     type Response = ModuleResponse;
 }
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::boxed::Box;
+
+    /// Returns a [`File`] with the provided path, and no content.
+    fn file(path: &'static CStr) -> File {
+        const NIL: Uuid = Uuid {
+            a: 0,
+            b: 0,
+            c: 0,
+            d: [0; 8],
+        };
+
+        File {
+            address: core::ptr::null_mut(),
+            size: 0,
+            path: path.as_ptr() as *mut i8,
+            cmdline: path.as_ptr() as *mut i8,
+            media_type: MediaType::GENERIC,
+            _unused: 0,
+            tftp_ip: 0,
+            tftp_port: 0,
+            partition_index: 0,
+            mbr_disk_id: 0,
+            gpt_disk_uuid: NIL,
+            gpt_part_uuid: NIL,
+            part_uuid: NIL,
+        }
+    }
+
+    #[test]
+    fn internal_modules() {
+        let cstr = |bytes| CStr::from_bytes_with_nul(bytes).unwrap();
+        let init: &'static InternalModule = Box::leak(Box::new(InternalModule::new(
+            cstr(b"nd_init\0"),
+            cstr(b"init\0"),
+            InternalModuleFlags::REQUIRED,
+        )));
+        let initrd: &'static InternalModule = Box::leak(Box::new(InternalModule::new(
+            cstr(b"initrd\0"),
+            cstr(b"\0"),
+            InternalModuleFlags::empty(),
+        )));
+        let modules: &'static [&'static InternalModule] = Box::leak(Box::new([init, initrd]));
+
+        let module = Module::new(modules);
+        let round_trip = module.internal_modules();
+        assert_eq!(round_trip.len(), 2);
+        assert!(core::ptr::eq(round_trip[0], init));
+        assert!(core::ptr::eq(round_trip[1], initrd));
+        assert_eq!(round_trip[0].path().to_bytes(), b"nd_init");
+        assert_eq!(round_trip[0].cmdline().to_bytes(), b"init");
+        assert_eq!(round_trip[1].path().to_bytes(), b"initrd");
+
+        assert!(Module::new(&[]).internal_modules().is_empty());
+    }
+
+    #[test]
+    fn filename() {
+        let cases: [(&[u8], &[u8]); 7] = [
+            (b"/boot/nd_init\0", b"nd_init"),
+            (b"\0", b""),
+            (b"nd_init\0", b"nd_init"),
+            (b"/\0", b""),
+            (b"/boot/\0", b""),
+            (b"//boot//nd_init\0", b"nd_init"),
+            (b"/boot//\0", b""),
+        ];
+
+        for (path, expected) in cases {
+            let path = CStr::from_bytes_with_nul(path).unwrap();
+            assert_eq!(file(path).filename().to_bytes(), expected, "{path:?}");
+        }
+    }
+
+    #[test]
+    fn elf_file() {
+        let data = b"\x7fELF\x02\x01\x01";
+        assert_eq!(ElfFile::new(data).unwrap().as_bytes(), data);
+
+        assert!(ElfFile::new(b"\x7fEL").is_none());
+        assert!(ElfFile::new(b"").is_none());
+        assert!(ElfFile::new(b"\x7fELG\x02\x01\x01").is_none());
+    }
+}
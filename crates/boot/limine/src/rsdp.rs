@@ -0,0 +1,47 @@
+use core::fmt;
+
+use crate::Feature;
+
+/// Requests the Limine bootloader to provide the address of the ACPI *Root System Description
+/// Pointer*.
+///
+/// <https://github.com/limine-bootloader/limine/blob/v4.x-branch/PROTOCOL.md#rsdp-feature>
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct Rsdp;
+
+/// The response to the [`Rsdp`] request.
+///
+/// Revision 0 of the response only contains the address of the RSDP.
+#[repr(C)]
+pub struct RsdpResponse {
+    /// The address of the RSDP.
+    address: u64,
+}
+
+impl RsdpResponse {
+    /// Returns the address of the RSDP.
+    ///
+    /// Like every other pointer provided by version 4 of the protocol, this address lies within
+    /// the *Higher Half Direct Map*. The physical address of the RSDP can be recovered by
+    /// subtracting the offset provided by the [`Hhdm`](crate::Hhdm) request.
+    #[inline(always)]
+    pub fn address(&self) -> u64 {
+        self.address
+    }
+}
+
+impl fmt::Debug for RsdpResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RsdpResponse")
+            .field("address", &format_args!("{:#x}", self.address))
+            .finish()
+    }
+}
+
+impl Feature for Rsdp {
+    type Response = RsdpResponse;
+    const MAGIC: [u64; 2] = [0xc5e77b6b397e7b43, 0x27637845accdcf3c];
+    const EXPECTED_REVISION: u64 = 0;
+    const REVISION: u64 = 0;
+}
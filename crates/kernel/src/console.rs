@@ -0,0 +1,33 @@
+//! Text output devices.
+
+use core::fmt;
+
+/// An output device to which the kernel can write text.
+///
+/// Formatting macros such as [`write!`] can be used directly on consoles through
+/// [`Console::write_fmt`].
+pub trait Console {
+    /// Writes the provided bytes to the console.
+    fn write_bytes(&mut self, bytes: &[u8]);
+
+    /// Writes formatted text to the console.
+    ///
+    /// This is the method that [`write!`] and [`writeln!`] call.
+    fn write_fmt(&mut self, args: fmt::Arguments) -> fmt::Result {
+        fmt::write(&mut ConsoleWriter(self), args)
+    }
+}
+
+/// Implements [`fmt::Write`] for a [`Console`].
+///
+/// A blanket implementation of [`fmt::Write`] for every console is not allowed by the coherence
+/// rules, as both the trait and the implementing types would be generic.
+struct ConsoleWriter<'a, C: ?Sized>(&'a mut C);
+
+impl<'a, C: ?Sized + Console> fmt::Write for ConsoleWriter<'a, C> {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
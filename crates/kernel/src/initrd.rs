@@ -0,0 +1,259 @@
+//! The initial ramdisk.
+//!
+//! The initial ramdisk is an optional [TAR](https://wiki.osdev.org/Tar) archive loaded by the
+//! bootloader alongside the kernel. It gives the kernel access to files other than the init
+//! program without requiring any file system driver.
+//!
+//! The archive is never copied: the slices returned by [`open`] point directly into the memory
+//! in which the bootloader loaded it.
+
+use core::fmt;
+
+/// The size of a TAR block. Headers and file contents are aligned to this size.
+const BLOCK_SIZE: usize = 512;
+
+/// An error which might occur when parsing the initial ramdisk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitrdError {
+    /// The archive ended in the middle of a header or of the contents of a file.
+    Truncated,
+    /// A header does not have the `ustar` magic number.
+    InvalidMagic,
+    /// The checksum of a header does not match its content.
+    InvalidChecksum,
+    /// A numeric field of a header is not a valid octal number.
+    InvalidNumber,
+}
+
+impl fmt::Display for InitrdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Truncated => "the archive is truncated",
+            Self::InvalidMagic => "invalid header magic",
+            Self::InvalidChecksum => "invalid header checksum",
+            Self::InvalidNumber => "invalid numeric field",
+        })
+    }
+}
+
+/// A file stored in the initial ramdisk.
+#[derive(Debug, Clone, Copy)]
+pub struct Entry<'a> {
+    /// The `prefix` field of the header. When non-empty, the full path of the entry is
+    /// `prefix/name`.
+    prefix: &'a [u8],
+    /// The `name` field of the header.
+    name: &'a [u8],
+    /// The type of the entry.
+    ty: u8,
+    /// The contents of the entry.
+    data: &'a [u8],
+}
+
+impl<'a> Entry<'a> {
+    /// Returns whether this entry is a regular file.
+    #[inline(always)]
+    pub fn is_file(&self) -> bool {
+        self.ty == b'0' || self.ty == 0
+    }
+
+    /// Returns the contents of the entry.
+    #[inline(always)]
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Returns whether the path of this entry is `path`.
+    ///
+    /// Leading `/` and `./` components are ignored on both sides.
+    pub fn has_path(&self, path: &[u8]) -> bool {
+        let path = trim_path(path);
+
+        if self.prefix.is_empty() {
+            return trim_path(self.name) == path;
+        }
+
+        let prefix = trim_path(self.prefix);
+        let Some(rest) = path.strip_prefix(prefix) else {
+            return false;
+        };
+
+        // The prefix may or may not end with a slash.
+        let rest = rest.strip_prefix(b"/").unwrap_or(rest);
+        let name = self.name.strip_prefix(b"/").unwrap_or(self.name);
+        rest == name
+    }
+}
+
+/// Removes the leading `/` and `./` components of a path.
+fn trim_path(mut path: &[u8]) -> &[u8] {
+    loop {
+        if let Some(rest) = path.strip_prefix(b"/") {
+            path = rest;
+        } else if let Some(rest) = path.strip_prefix(b"./") {
+            path = rest;
+        } else {
+            return path;
+        }
+    }
+}
+
+/// Returns the part of a NUL-padded header field that comes before the first NUL byte.
+fn field(bytes: &[u8]) -> &[u8] {
+    match bytes.iter().position(|&b| b == 0) {
+        Some(end) => &bytes[..end],
+        None => bytes,
+    }
+}
+
+/// Parses an octal header field, which may be padded with spaces or NUL bytes.
+fn parse_octal(bytes: &[u8]) -> Result<usize, InitrdError> {
+    let mut ret: usize = 0;
+
+    for &b in bytes {
+        match b {
+            b'0'..=b'7' => {
+                ret = ret
+                    .checked_mul(8)
+                    .and_then(|r| r.checked_add((b - b'0') as usize))
+                    .ok_or(InitrdError::InvalidNumber)?;
+            }
+            b' ' | 0 => (),
+            _ => return Err(InitrdError::InvalidNumber),
+        }
+    }
+
+    Ok(ret)
+}
+
+/// Iterates over the entries of a TAR archive.
+///
+/// The iterator stops at the end-of-archive marker (a block full of zeros), or at the first
+/// malformed header.
+#[derive(Debug, Clone)]
+pub struct Entries<'a> {
+    remainder: &'a [u8],
+}
+
+impl<'a> Entries<'a> {
+    /// Creates a new [`Entries`] iterator over the provided archive.
+    #[inline(always)]
+    pub fn new(archive: &'a [u8]) -> Self {
+        Self { remainder: archive }
+    }
+
+    /// Parses the header at the start of the remaining data.
+    fn parse_next(&mut self) -> Result<Option<Entry<'a>>, InitrdError> {
+        if self.remainder.is_empty() {
+            return Ok(None);
+        }
+
+        let header = self
+            .remainder
+            .get(..BLOCK_SIZE)
+            .ok_or(InitrdError::Truncated)?;
+
+        if header.iter().all(|&b| b == 0) {
+            return Ok(None);
+        }
+
+        // Both the POSIX ("ustar\0") and the old GNU ("ustar ") magic numbers are accepted.
+        if &header[257..262] != b"ustar" {
+            return Err(InitrdError::InvalidMagic);
+        }
+
+        // The checksum is computed as if the checksum field itself was filled with spaces.
+        let expected = parse_octal(&header[148..156])?;
+        let actual: usize = header
+            .iter()
+            .enumerate()
+            .map(|(i, &b)| if (148..156).contains(&i) { b' ' } else { b } as usize)
+            .sum();
+        if expected != actual {
+            return Err(InitrdError::InvalidChecksum);
+        }
+
+        let size = parse_octal(&header[124..136])?;
+        let padded_size = size
+            .checked_add(BLOCK_SIZE - 1)
+            .ok_or(InitrdError::Truncated)?
+            & !(BLOCK_SIZE - 1);
+
+        let data = self.remainder[BLOCK_SIZE..]
+            .get(..size)
+            .ok_or(InitrdError::Truncated)?;
+
+        let entry = Entry {
+            name: field(&header[0..100]),
+            ty: header[156],
+            prefix: field(&header[345..500]),
+            data,
+        };
+
+        // The last block of the file might not be padded if it is the last thing in the archive.
+        self.remainder = self.remainder[BLOCK_SIZE..]
+            .get(padded_size..)
+            .unwrap_or_default();
+
+        Ok(Some(entry))
+    }
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = Result<Entry<'a>, InitrdError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.parse_next() {
+            Ok(Some(entry)) => Some(Ok(entry)),
+            Ok(None) => None,
+            Err(err) => {
+                self.remainder = &[];
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// The initial ramdisk. This is empty until [`initialize`] is called.
+static mut ARCHIVE: &[u8] = &[];
+
+/// Sets the archive used as the initial ramdisk.
+///
+/// Every header of the archive is checked beforehand, ensuring that [`open`] never has to deal
+/// with a malformed archive. On error, the initial ramdisk is left empty.
+///
+/// # Safety
+///
+/// This function must be called before any other function of this module, and must not be
+/// called concurrently with them.
+pub unsafe fn initialize(archive: &'static [u8]) -> Result<(), InitrdError> {
+    let mut count = 0;
+    for entry in Entries::new(archive) {
+        entry?;
+        count += 1;
+    }
+
+    nd_log::trace!("Initial ramdisk: {} entries", count);
+
+    unsafe { ARCHIVE = archive };
+
+    Ok(())
+}
+
+/// Returns an iterator over the entries of the initial ramdisk.
+pub fn entries() -> Entries<'static> {
+    // SAFETY:
+    //  `ARCHIVE` is only modified by `initialize`, which cannot run concurrently with this
+    //  function.
+    Entries::new(unsafe { ARCHIVE })
+}
+
+/// Returns the contents of the regular file at `path` in the initial ramdisk.
+///
+/// Returns `None` if no such file exists.
+pub fn open(path: &str) -> Option<&'static [u8]> {
+    entries()
+        .filter_map(Result::ok)
+        .find(|e| e.is_file() && e.has_path(path.as_bytes()))
+        .map(|e| e.data())
+}
@@ -15,42 +15,77 @@
 #![feature(allocator_api)]
 #![feature(naked_functions)]
 #![feature(asm_const)]
+#![cfg_attr(all(test, feature = "kernel-test"), feature(custom_test_frameworks))]
+#![cfg_attr(
+    all(test, feature = "kernel-test"),
+    test_runner(crate::testing::test_runner)
+)]
+#![cfg_attr(
+    all(test, feature = "kernel-test"),
+    reexport_test_harness_main = "test_main"
+)]
 
 #[cfg(target_arch = "x86_64")]
 mod x86_64;
 
+mod console;
+mod initrd;
+#[cfg(all(test, feature = "kernel-test"))]
+mod testing;
+
+use core::panic::PanicInfo;
+
+use self::console::Console;
+
 /// Disables interrupts and halts the CPU.
 ///
 /// This function can be called when an unrecoverable error occurs.
 fn die() -> ! {
-    unsafe {
-        nd_x86_64::cli();
-
-        loop {
-            nd_x86_64::hlt();
-        }
-    }
+    nd_x86_64::hlt_loop()
 }
 
 /// This function is called when something in our code panics. This should be considered a serious
 /// bug in the kernel.
+///
+/// The report is written directly to the console rather than through `nd_log`, ensuring that it
+/// is displayed even if the global logger is in a bad state.
 #[panic_handler]
-fn handle_panic(info: &core::panic::PanicInfo) -> ! {
-    nd_log::error!("KERNEL PANIC!");
-    nd_log::error!("");
-    nd_log::error!("  This is a serious bug in the kernel.");
-    nd_log::error!("  Please report this issue at");
-    nd_log::error!("");
-    nd_log::error!("      https://github.com/nils-mathieu/neodym/issues/new");
-    nd_log::error!("");
+fn handle_panic(info: &PanicInfo) -> ! {
+    #[cfg(target_arch = "x86_64")]
+    x86_64::with_console_forced(|console| report_panic(console, info));
+
+    #[cfg(all(test, feature = "kernel-test", target_arch = "x86_64"))]
+    x86_64::qemu_exit(x86_64::QEMU_EXIT_FAILURE);
+
+    die();
+}
+
+/// Writes a report about the provided panic to `console`.
+fn report_panic(console: &mut dyn Console, info: &PanicInfo) {
+    let _ = writeln!(console, "\x1B[31mKERNEL PANIC!\x1B[0m");
+    let _ = writeln!(console);
+    let _ = writeln!(console, "  This is a serious bug in the kernel.");
+    let _ = writeln!(console, "  Please report this issue at");
+    let _ = writeln!(console);
+    let _ = writeln!(
+        console,
+        "      https://github.com/nils-mathieu/neodym/issues/new"
+    );
+    let _ = writeln!(console);
 
     if let Some(message) = info.message() {
-        nd_log::error!("> Message: {}", message);
+        let _ = writeln!(console, "> Message: {}", message);
     }
 
     if let Some(location) = info.location() {
-        nd_log::error!(">      At: {}:{}", location.file(), location.line());
+        let _ = writeln!(
+            console,
+            ">      At: {}:{}",
+            location.file(),
+            location.line()
+        );
     }
 
-    die();
+    #[cfg(target_arch = "x86_64")]
+    let _ = writeln!(console, ">  Uptime: {:?}", x86_64::timer::uptime());
 }
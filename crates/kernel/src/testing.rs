@@ -0,0 +1,67 @@
+//! A test harness running `#[test_case]` functions within the kernel.
+//!
+//! This module is only compiled when the `kernel-test` feature is enabled and the kernel is built
+//! as a test binary:
+//!
+//! ```bash
+//! cargo test --package neodym --target targets/x86_64.json --features kernel-test --no-run
+//! ```
+//!
+//! The produced executable replaces the kernel in the bootable image (see `INSTALLATION.md`).
+//! Once the kernel is initialized, the tests are run instead of the init program. Their results
+//! are reported to the host through the `isa-debug-exit` device of QEMU, which must be enabled:
+//!
+//! ```txt
+//! -device isa-debug-exit,iobase=0xf4,iosize=0x04
+//! ```
+//!
+//! QEMU then exits with the status `33` if every test passed, and `35` if one of them panicked.
+
+/// Returns the global page allocator.
+#[cfg(target_arch = "x86_64")]
+pub fn page_allocator() -> crate::x86_64::PageAllocatorTok {
+    // SAFETY:
+    //  Tests run once the kernel is initialized.
+    unsafe { crate::x86_64::PageAllocatorTok::unchecked() }
+}
+
+/// Returns the address space shared by every process.
+#[cfg(target_arch = "x86_64")]
+pub fn kernel_address_space() -> crate::x86_64::KernelAddressSpaceTok {
+    // SAFETY:
+    //  Tests run once the kernel is initialized.
+    unsafe { crate::x86_64::KernelAddressSpaceTok::unchecked() }
+}
+
+/// A function which can be run as a test.
+pub trait Testable {
+    /// Runs the test, panicking if it fails.
+    fn run(&self);
+}
+
+impl<F: Fn()> Testable for F {
+    fn run(&self) {
+        nd_log::info!("test {} ...", core::any::type_name::<F>());
+        self();
+        nd_log::info!("  > ok");
+    }
+}
+
+/// Runs the provided tests, then exits QEMU.
+///
+/// A failing test panics, and the panic handler exits QEMU with a failure code. If QEMU cannot be
+/// exited, the CPU is halted.
+pub fn test_runner(tests: &[&dyn Testable]) -> ! {
+    nd_log::info!("Running {} tests...", tests.len());
+
+    for test in tests {
+        test.run();
+    }
+
+    nd_log::info!("All tests passed.");
+
+    #[cfg(target_arch = "x86_64")]
+    crate::x86_64::qemu_exit(crate::x86_64::QEMU_EXIT_SUCCESS);
+
+    crate::die();
+}
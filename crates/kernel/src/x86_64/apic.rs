@@ -1,4 +1,6 @@
-use nd_apic::{TimerDivisor, TimerMode, XApic};
+use nd_apic::XApic;
+
+use super::timer;
 
 /// Initializes the local APIC of the current CPU.
 ///
@@ -7,6 +9,9 @@ use nd_apic::{TimerDivisor, TimerMode, XApic};
 /// This function should only be called once per CPU.
 ///
 /// The local APIC must be identiy mapped.
+///
+/// The [`SysInfo::tsc_frequency`](super::SysInfo::tsc_frequency) must be initialized, as it is
+/// used to calibrate the timer.
 pub unsafe fn initialize_lapic() {
     unsafe {
         nd_apic::hardware_enable_xapic();
@@ -14,9 +19,16 @@ pub unsafe fn initialize_lapic() {
 
     let mut lapic = unsafe { XApic::identity_mapped() };
 
-    lapic.configure_spurious(39, true);
+    lapic.enable(39);
+    lapic.configure_error(40);
+
+    // Reading the error status also clears it, so that the errors reported from now on are not
+    // left over from the firmware or the bootloader.
+    let errors = lapic.error_status();
+    if !errors.is_empty() {
+        nd_log::warn!("The local APIC reported errors during boot: {:?}", errors);
+    }
 
-    lapic.configure_timer(32, TimerMode::Periodic);
-    lapic.set_timer_divisor(TimerDivisor::Div2);
-    lapic.set_timer_initial_count(u32::MAX / 8);
+    unsafe { timer::calibrate(&mut lapic) };
+    timer::set_frequency(&mut lapic, timer::DEFAULT_FREQUENCY);
 }
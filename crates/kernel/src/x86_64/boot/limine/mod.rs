@@ -2,52 +2,153 @@
 //! [Limine](https://github.com/limine-bootloader/limine/blob/v4.x-branch/PROTOCOL.md) bootloader.
 //!
 
-use nd_limine::{File, MemMapEntryType};
-use nd_x86_64::{Cr3, Cr3Flags, PageTable, PageTableFlags, VirtAddr};
+use core::mem::MaybeUninit;
+
+use nd_limine::{Feature, File, MemMapEntryType, MemoryMapResponse, Request};
+use nd_x86_64::{Cr3, Cr3Flags, Cr4, Efer, PageTableFlags, PhysAddr, RFlags, VirtAddr};
 
 use crate::x86_64::mapping::MappingError;
 use crate::x86_64::{
-    MemorySegment, OwnedMapper, PageAllocatorTok, PageProvider, SysInfo, SysInfoTok,
+    CpuFeatures, Instant, KernelAddressSpaceTok, LoadSegment, MemorySegment, OwnedMapper,
+    PageAllocatorTok, PageProvider, ProgramHeaders, SysInfo, SysInfoTok,
 };
 
 mod req;
 
-/// Removes the begining of a path, only keeping the what's after the last `/` character.
-fn get_filename(bytes: &[u8]) -> &[u8] {
-    let start_idx = match bytes.iter().rposition(|&b| b == b'/') {
-        Some(slash) => slash + 1,
-        None => 0,
-    };
+/// The maximum number of bootloader-reclaimable memory regions that can be reclaimed once the
+/// kernel is initialized.
+const MAX_RECLAIMABLE_SEGMENTS: usize = 32;
+
+/// The first address past the lower half of the address space, which belongs to userspace.
+const USER_SPACE_END: VirtAddr = 0x0000_8000_0000_0000;
 
-    unsafe { bytes.get_unchecked(start_idx..) }
+/// The command line which marks a module as the init program, regardless of its file name.
+const INIT_CMDLINE: &[u8] = b"init";
+
+/// Returns whether `file` has been explicitly marked as the init program through its command line.
+fn is_marked_init(file: &File) -> bool {
+    file.cmdline().to_bytes() == INIT_CMDLINE
 }
 
-/// Reads The content of the "MODULE" request and returns the file that has been loaded.
-///
-/// # Panics
+/// Returns whether `file` could be the init program.
+fn is_init_candidate(file: &File) -> bool {
+    is_marked_init(file) || file.filename().to_bytes() == b"nd_init"
+}
+
+/// Reads The content of the "MODULE" request and returns the file that has been loaded as the
+/// init program.
 ///
-/// If the init program is not present, this function panics with an appropriate error message.
+/// A module whose command line is `init` is preferred over a module that's simply named
+/// `nd_init`. If several modules match, the first one is used and a warning is logged.
 fn find_init_program() -> Option<&'static File> {
     nd_log::trace!("Enumerating kernel modules...");
 
     let response = req::MODULE.response()?;
 
-    let mut found = None;
+    let mut marked = None;
+    let mut named = None;
+    let mut candidates = 0;
 
-    for module in response.modules().iter().filter_map(|x| x.file()) {
-        nd_log::trace!(" - {:?}", module.path());
+    for module in response.modules() {
+        let Some(file) = module.file() else {
+            nd_log::trace!(" - <unsupported file revision {}>", module.revision());
+            continue;
+        };
 
-        // We're looking for a file named 'nd_init'.
-        if get_filename(module.path().to_bytes()) == b"nd_init" {
-            found = Some(module);
+        nd_log::trace!(" - {:?}", file.path());
+
+        if !is_init_candidate(file) {
+            continue;
+        }
+
+        candidates += 1;
+        if is_marked_init(file) {
+            marked.get_or_insert(file);
+        } else {
+            named.get_or_insert(file);
+        }
+    }
+
+    let found = marked.or(named);
+
+    if candidates > 1 {
+        nd_log::warn!("Found {} candidates for the init program:", candidates);
+        for file in response
+            .modules()
+            .iter()
+            .filter_map(|m| m.file())
+            .filter(|f| is_init_candidate(f))
+        {
+            nd_log::warn!("  > {:?} (cmdline: {:?})", file.path(), file.cmdline());
+        }
+        if let Some(file) = found {
+            nd_log::warn!("Using {:?}.", file.path());
         }
     }
 
     found
 }
 
-const KERNEL_STACK_SIZE: usize = 4096 * 16;
-static mut KERNEL_STACK: [u8; KERNEL_STACK_SIZE] = [0; KERNEL_STACK_SIZE];
+/// Explains why the bootloader did not provide a usable response to `request`.
+///
+/// A response with an older revision than the one we expect means that the bootloader implements
+/// an older version of the Limine protocol.
+fn log_missing_response<F: Feature>(request: &Request<F>) {
+    match request.response_revision() {
+        Some(revision) => nd_log::error!(
+            "  > The response has revision {}, but at least revision {} is required.",
+            revision,
+            F::EXPECTED_REVISION,
+        ),
+        None => nd_log::error!("  > The bootloader did not respond to the request."),
+    }
+}
+
+/// Logs the memory map provided by the bootloader.
+///
+/// Only the first few entries are logged individually, but the summary takes every entry into
+/// account.
+fn log_memory_map(memmap: &MemoryMapResponse) {
+    const MAX_LOGGED_ENTRIES: usize = 64;
+
+    let entries = memmap.entries();
+
+    nd_log::trace!("Memory map ({} entries):", entries.len());
+
+    let mut usable = 0;
+    let mut reclaimable = 0;
+    let mut reserved = 0;
+
+    for (i, entry) in entries.iter().enumerate() {
+        if i < MAX_LOGGED_ENTRIES {
+            nd_log::trace!(
+                "  > {:#018x} {} {:?}",
+                entry.base(),
+                nd_fmt::HumanBytes(entry.length()),
+                entry.ty(),
+            );
+        }
+
+        match entry.ty() {
+            MemMapEntryType::USABLE => usable += entry.length(),
+            MemMapEntryType::BOOTLOADER_RECLAIMABLE | MemMapEntryType::ACPI_RECLAIMABLE => {
+                reclaimable += entry.length()
+            }
+            _ => reserved += entry.length(),
+        }
+    }
+
+    if entries.len() > MAX_LOGGED_ENTRIES {
+        nd_log::trace!("  > ... ({} more)", entries.len() - MAX_LOGGED_ENTRIES);
+    }
+
+    nd_log::trace!(
+        "  > {} usable, {} reclaimable, {} reserved",
+        nd_fmt::HumanBytes(usable),
+        nd_fmt::HumanBytes(reclaimable),
+        nd_fmt::HumanBytes(reserved),
+    );
+}
 
 /// The entry point of the kernel when booted by the Limine bootloader on **x86_64**.
 #[naked]
@@ -64,8 +165,8 @@ extern "C" fn entry_point() -> ! {
             mov rbp, rsp
             jmp {}
             "#,
-            sym KERNEL_STACK,
-            const KERNEL_STACK_SIZE,
+            sym crate::x86_64::KERNEL_STACK,
+            const crate::x86_64::KERNEL_STACK_SIZE,
             sym entry_point_inner,
             options(noreturn),
         );
@@ -75,8 +176,16 @@ extern "C" fn entry_point() -> ! {
 extern "C" fn entry_point_inner() -> ! {
     // SAFETY:
     //  We're in the entry point, this function won't be called ever again.
+    unsafe { crate::x86_64::setup_bootstrap_idt() };
+
+    let boot_start = Instant::now();
+
+    // SAFETY:
+    //  Same as above.
     unsafe { crate::x86_64::initialize_logger() };
 
+    crate::x86_64::assert_long_mode_ready();
+
     //
     // Gather the responses from the Limine bootloader.
     // Some are necessary, others are just nice information to have.
@@ -88,6 +197,10 @@ extern "C" fn entry_point_inner() -> ! {
         nd_log::info!("Loaded by a Limine-compliant bootloader.");
     }
 
+    if let Some(boot_time) = req::BOOT_TIME.response() {
+        nd_log::info!("Booted at UNIX time {}.", boot_time.boot_time());
+    }
+
     if req::ENTRY_POINT.response().is_none() {
         nd_log::warn!("The Limine bootloader did not respond to the entry point request.");
         nd_log::warn!("  > This is just a sanity check.");
@@ -96,20 +209,58 @@ extern "C" fn entry_point_inner() -> ! {
 
     let Some(kernel_addr) = req::KERNEL_ADDR.response() else {
         nd_log::error!("The Limine bootloader did not provide the address of the kernel.");
+        log_missing_response(&req::KERNEL_ADDR);
         crate::die();
     };
 
     let Some(memmap) = req::MEMORY_MAP.response() else {
         nd_log::error!("The Limine bootloader did not provide a map of the usable memory.");
+        log_missing_response(&req::MEMORY_MAP);
         crate::die();
     };
 
+    log_memory_map(memmap);
+
     let Some(hhdm) = req::HHDM.response() else {
         nd_log::error!("The Limine bootloader did not provide the HHDM address.");
+        log_missing_response(&req::HHDM);
         crate::die();
     };
 
-    let Some(nd_init) = find_init_program() else {
+    // The initial ramdisk is optional.
+    match req::MODULE
+        .response()
+        .and_then(|r| r.find_by_filename(b"initrd"))
+    {
+        // SAFETY:
+        //  We're in the entry point, nothing else uses the initial ramdisk yet.
+        Some(initrd) => match initrd.try_data() {
+            Some(data) => match unsafe { crate::initrd::initialize(data) } {
+                Ok(()) => (),
+                Err(err) => nd_log::warn!("The initial ramdisk is malformed: {}", err),
+            },
+            None => nd_log::warn!("The initial ramdisk is empty."),
+        },
+        None => nd_log::trace!("No initial ramdisk was provided."),
+    }
+
+    // The init program is either loaded as its own module, or stored in the initial ramdisk.
+    //
+    // The `File` structure lives in bootloader-reclaimable memory, only keep the data (which is
+    // part of the kernel and modules).
+    let Some(nd_init) = find_init_program()
+        .and_then(|file| {
+            let elf = file.as_elf();
+            if elf.is_none() {
+                nd_log::warn!(
+                    "The init program module {:?} is not an ELF file.",
+                    file.path()
+                );
+            }
+            elf.map(|elf| elf.as_bytes())
+        })
+        .or_else(|| crate::initrd::open("nd_init"))
+    else {
         nd_log::error!("An `nd_init` module is expected along with the kernel.");
         nd_log::error!("Check your Limine config!");
         nd_log::error!("");
@@ -119,9 +270,29 @@ extern "C" fn entry_point_inner() -> ! {
         nd_log::error!("    KERNEL_PATH=boot:///neodym");
         nd_log::error!("    MODULE_PATH=boot:///nd_init");
         nd_log::error!("");
+        nd_log::error!("Alternatively, `nd_init` may be stored in an `initrd` TAR archive module.");
         crate::die();
     };
 
+    // Make sure that the init program is a valid executable before trusting anything it
+    // contains.
+    let (nd_init_entry, nd_init_segments) = match crate::x86_64::find_entry_point(nd_init)
+        .and_then(|entry| Ok((entry, crate::x86_64::program_headers(nd_init)?)))
+    {
+        Ok((entry, segments)) => {
+            nd_log::trace!("`nd_init` entry point: {:#x}", entry);
+            (entry, segments)
+        }
+        Err(crate::x86_64::EntryPointError::WrongArchitecture) => {
+            nd_log::error!("`nd_init` was not compiled for the x86_64 architecture.");
+            crate::die();
+        }
+        Err(err) => {
+            nd_log::error!("`nd_init` is not a valid x86_64 executable: {:?}", err);
+            crate::die();
+        }
+    };
+
     let kernel_virt_addr = SysInfo::read_kernel_virt_addr();
 
     if kernel_virt_addr != kernel_addr.virtual_base() {
@@ -135,21 +306,59 @@ extern "C" fn entry_point_inner() -> ! {
     }
 
     // This iterator goes over every memory segment that is available for the kernel to use.
+    //
+    // Bootloader-reclaimable memory is not part of it: it contains the responses of the
+    // bootloader (including the memory map we're iterating over), which we're still reading.
+    // Those segments are remembered and given to the page allocator at the end of the boot
+    // sequence, once nothing references them anymore.
     let mut available_mem = memmap
         .entries()
         .iter()
         .map(|&&e| e)
-        .filter(|e| {
-            e.ty() == MemMapEntryType::USABLE || e.ty() == MemMapEntryType::BOOTLOADER_RECLAIMABLE
-        })
+        .filter(|e| e.ty() == MemMapEntryType::USABLE)
         .map(|e| MemorySegment {
             base: e.base(),
             length: e.length(),
         });
 
-    let page_provider = PageProvider::new(&mut available_mem);
+    let mut page_provider = PageProvider::new(&mut available_mem);
+
+    // This must be copied out of the memory map, as the memory map itself will be reclaimed.
+    let reclaimable = memmap
+        .entries()
+        .iter()
+        .filter(|e| e.ty() == MemMapEntryType::BOOTLOADER_RECLAIMABLE)
+        .map(|e| MemorySegment {
+            base: e.base(),
+            length: e.length(),
+        });
+    let reclaimable = match nd_array::Vec::<MemorySegment, MAX_RECLAIMABLE_SEGMENTS>::try_from_iter(
+        reclaimable,
+    ) {
+        Ok(reclaimable) => reclaimable,
+        Err((reclaimable, rest)) => {
+            let ignored: u64 = rest.map(|segment| segment.length).sum();
+            nd_log::warn!(
+                "Too many bootloader-reclaimable regions, {} have been ignored.",
+                nd_fmt::HumanBytes(ignored),
+            );
+            reclaimable
+        }
+    };
 
     let kernel_virt_end_addr = SysInfo::read_kernel_virt_end_addr();
+    let kernel_phys_addr = kernel_addr.physical_base();
+    let hhdm_offset = hhdm.offset();
+
+    // Some of the memory we've given to the page provider is still in use. Make sure that it
+    // won't be handed out until we're done with it.
+    page_provider
+        .reserve(kernel_phys_addr..kernel_phys_addr + (kernel_virt_end_addr - kernel_virt_addr));
+    let nd_init_phys = nd_init.as_ptr() as VirtAddr - hhdm_offset;
+    page_provider.reserve(nd_init_phys..nd_init_phys + nd_init.len() as PhysAddr);
+    // The page tables of the bootloader do not need to be reserved: they live in
+    // bootloader-reclaimable memory, which is only given to the page allocator once we are done
+    // with them.
 
     let physical_memory_size = match memmap
         .entries()
@@ -161,8 +370,25 @@ extern "C" fn entry_point_inner() -> ! {
         None => 0,
     };
 
-    let kernel_phys_addr = kernel_addr.physical_base();
-    let hhdm_start = hhdm.offset();
+    let cpu_features = CpuFeatures::read();
+    nd_log::info!("CPU features: {}", cpu_features);
+
+    nd_log::trace!("Calibrating the TSC...");
+    // SAFETY:
+    //  Nothing else uses the PIT.
+    let tsc_frequency = match unsafe { crate::x86_64::calibrate_tsc() } {
+        Some(frequency) => frequency,
+        None => {
+            nd_log::error!("Failed to calibrate the TSC.");
+            nd_log::error!("  > The TSC did not advance while the PIT was counting down.");
+            nd_log::error!("  > The kernel has no other clock source to fall back on.");
+            crate::die();
+        }
+    };
+    nd_log::trace!("  > {} MHz", tsc_frequency / 1_000_000);
+    if !cpu_features.invariant_tsc() {
+        nd_log::warn!("The TSC is not invariant. Time measurements may drift with power states.");
+    }
 
     // Initialize the global kernel info object.
     //
@@ -173,7 +399,9 @@ extern "C" fn entry_point_inner() -> ! {
             kernel_phys_addr,
             kernel_virt_addr,
             kernel_virt_end_addr,
-            hhdm_start,
+            hhdm_offset,
+            cpu_features,
+            tsc_frequency,
         })
     };
 
@@ -190,14 +418,30 @@ extern "C" fn entry_point_inner() -> ! {
 
         match crate::x86_64::mapping::generate_page_table(
             &page_provider,
-            &mut |phys| phys + hhdm_start,
+            &mut |phys| phys + hhdm_offset,
             physical_memory_size,
             kernel_phys_addr,
             kernel_virt_addr,
-            kernel_virt_end_addr - kernel_virt_addr,
-            hhdm_start,
+            sys_info.kernel_size(),
+            hhdm_offset,
+            cpu_features.pages_1gib(),
+            SysInfo::read_kernel_stack_guard_addr(),
         ) {
             Ok(pml4) => pml4,
+            Err(MappingError::InvalidLayout) => {
+                nd_log::error!("The bootloader provided an invalid memory layout.");
+                nd_log::error!(
+                    "  > Direct map: {:#x} ({:#x} bytes)",
+                    hhdm_offset,
+                    physical_memory_size,
+                );
+                nd_log::error!(
+                    "  > Kernel: {:#x}..{:#x}",
+                    kernel_virt_addr,
+                    kernel_virt_end_addr,
+                );
+                crate::die();
+            }
             Err(_err) => {
                 nd_log::error!("Not enough memory to setup paging.");
                 #[cfg(debug_assertions)]
@@ -207,94 +451,215 @@ extern "C" fn entry_point_inner() -> ! {
         }
     };
 
+    // SAFETY:
+    //  We're still in the entry point, and the page table has just been generated.
+    let kernel_address_space =
+        match unsafe { KernelAddressSpaceTok::initialize(sys_info, &page_provider, pml4) } {
+            Ok(tok) => tok,
+            Err(_) => {
+                nd_log::error!("Not enough memory to setup the kernel address space.");
+                crate::die();
+            }
+        };
+
     let page_allocator = unsafe { PageAllocatorTok::initialize(sys_info, page_provider) };
 
     unsafe {
         nd_log::trace!("Switching up address space...");
-        nd_x86_64::set_cr3(Cr3::new(pml4, Cr3Flags::empty()));
+        nd_x86_64::set_cr3(Cr3::new(kernel_address_space.pml4(), Cr3Flags::empty()));
+
+        // Enabling PCIDs requires the current PCID to be 0, which is the case now that the
+        // kernel address space is loaded.
+        if cpu_features.pcid() {
+            nd_log::trace!("Enabling process-context identifiers...");
+            nd_x86_64::set_cr4(nd_x86_64::cr4() | Cr4::PCID);
+        }
+
+        // The kernel never relies on the FS or GS base, userland is free to change them.
+        if cpu_features.fsgsbase() {
+            nd_x86_64::set_cr4(nd_x86_64::cr4() | Cr4::FSGSBASE);
+        }
+
+        if cpu_features.no_execute() {
+            nd_x86_64::set_efer(nd_x86_64::efer() | Efer::EXECUTE_DISABLE);
+        }
     }
 
+    // SAFETY:
+    //  We're done reading the responses of the bootloader and we've switched to our own page
+    //  tables, GDT, IDT and stack. `reclaimable` lives on the kernel stack.
+    //
+    //  From now on, the responses of the bootloader (`req::*`) must not be accessed.
+    unsafe { page_allocator.add_reclaimed(&reclaimable) };
+
+    let stats = page_allocator.stats();
+    nd_log::trace!(
+        "Physical memory: {} used, {} free, {} total.",
+        nd_fmt::HumanBytes(stats.used * 4096),
+        nd_fmt::HumanBytes(stats.free * 4096),
+        nd_fmt::HumanBytes(stats.total * 4096),
+    );
+
     unsafe {
         // Enable interrupts. We're ready to be interrupted x).
         nd_x86_64::sti();
     }
 
-    match spawn_init_process(page_allocator, nd_init.data()) {
+    nd_log::info!("Kernel initialized in {:?}.", boot_start.elapsed());
+
+    // When built as a test binary, run the tests instead of the init program.
+    #[cfg(all(test, feature = "kernel-test"))]
+    crate::test_main();
+
+    match spawn_init_process(
+        page_allocator,
+        kernel_address_space,
+        nd_init,
+        nd_init_entry,
+        nd_init_segments,
+    ) {
         Ok(()) => (),
-        Err(MappingError::AlreadyMapped) => {
-            debug_assert!(
-                false,
-                "something is already mapped at the init process address"
-            );
-            unsafe { core::hint::unreachable_unchecked() };
-        }
         Err(MappingError::OutOfPhysicalMemory) => {
             nd_log::error!("Not enough physical memory to load `nd_init`.");
             crate::die();
         }
+        Err(err) => {
+            nd_log::error!("Failed to map the segments of `nd_init`: {:?}", err);
+            nd_log::error!("  > Its segments must be in the lower half and must not share pages.");
+            nd_log::error!("  > Its entry point must be in an executable segment.");
+            crate::die();
+        }
     }
 
     todo!();
 }
 
 /// Initializes the `nd_init` process.
+///
+/// The loadable segments of `nd_init` are mapped at the addresses they request, and the process
+/// starts executing at `entry`.
 fn spawn_init_process(
     page_allocator: PageAllocatorTok,
+    kernel_address_space: KernelAddressSpaceTok,
     nd_init: &[u8],
+    entry: VirtAddr,
+    segments: ProgramHeaders,
 ) -> Result<(), MappingError> {
-    let mut owned_mapper = OwnedMapper::new(page_allocator)?;
+    const STACK_SIZE: u64 = 64 * 1024;
 
-    // Map the kernel and the init process into the address space.
-    // We know that those are always present regardless of the current address space, so we can
-    // just copy those entries from the current address space.
-    let current = unsafe {
-        &mut *((nd_x86_64::cr3().addr() + page_allocator.sys_info().hhdm_start) as *mut PageTable)
-    };
+    // Validate the layout of the process before mapping anything. The entry point is loaded in
+    // `rcx` by `sysretq`, which raises a #GP in ring 0 on Intel CPUs when it is not canonical.
+    // At this point, the stack pointer already points to the user stack.
+    let mut lowest = USER_SPACE_END;
+    let mut entry_is_executable = false;
+
+    for segment in segments.clone() {
+        // The process must not be able to map anything over the kernel.
+        let (start, _) = segment_pages(&segment).ok_or(MappingError::InvalidLayout)?;
 
-    for i in 256..512 {
-        let entry = unsafe { current.get_unchecked_mut(i) };
+        lowest = lowest.min(start);
 
-        if entry.flags().contains(PageTableFlags::PRESENT) {
-            let dst = unsafe { owned_mapper.pml4_mut().get_unchecked_mut(i) };
-            *dst = *entry;
+        if segment.executable() && (segment.vaddr..segment.vaddr + segment.memsz).contains(&entry) {
+            entry_is_executable = true;
         }
     }
 
-    // Map the `nd_init` process at address `0x10_0000`.
-    const LOAD_ADDR: VirtAddr = 0x10_0000;
-    const STACK_SIZE: u64 = 64 * 1024;
-    const STACK_TOP: VirtAddr = LOAD_ADDR - 0x1000;
+    if entry >= USER_SPACE_END || !entry_is_executable {
+        return Err(MappingError::InvalidLayout);
+    }
 
-    owned_mapper.load(
-        LOAD_ADDR,
-        nd_init,
-        PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE,
-        PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE,
-    )?;
+    // The stack is placed right below the lowest segment, with a guard page in between. The
+    // first page of the address space is kept unmapped to catch null pointer dereferences.
+    if lowest < STACK_SIZE + 0x2000 {
+        return Err(MappingError::InvalidLayout);
+    }
+    let stack_top = lowest - 0x1000;
+
+    let mut owned_mapper = OwnedMapper::new_user(page_allocator, kernel_address_space)?;
+
+    let parent_flags =
+        PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE;
+    let no_execute = page_allocator.sys_info().cpu_features.no_execute();
+
+    for segment in segments {
+        // SAFETY:
+        //  The layout of every segment has been checked above.
+        let (start, end) = unsafe { segment_pages(&segment).unwrap_unchecked() };
+
+        let mut flags = PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE;
+        if segment.writable() {
+            flags |= PageTableFlags::WRITABLE;
+        }
+        if no_execute && !segment.executable() {
+            flags |= PageTableFlags::NO_EXECUTE;
+        }
+
+        let data = segment.data(nd_init);
+        let data_end = segment.vaddr + segment.filesz;
+        let mut page_start = start;
 
+        owned_mapper.load_with(start, (end - start) / 0x1000, flags, parent_flags, |page| {
+            // Zero the whole page first: this takes care of the `.bss` part of the segment, as
+            // well as of the bytes that precede the segment in its first page.
+            page.fill(MaybeUninit::new(0));
+
+            let copy_start = segment.vaddr.max(page_start);
+            let copy_end = data_end.min(page_start + 0x1000);
+
+            if copy_start < copy_end {
+                let src = &data[(copy_start - segment.vaddr) as usize..]
+                    [..(copy_end - copy_start) as usize];
+                let dst = &mut page[(copy_start - page_start) as usize..][..src.len()];
+
+                for (dst, &src) in dst.iter_mut().zip(src) {
+                    *dst = MaybeUninit::new(src);
+                }
+            }
+
+            page_start += 0x1000;
+        })?;
+    }
+
+    // Create a 64 KiB stack for the process.
     owned_mapper.load_uninit(
-        STACK_TOP - STACK_SIZE,
+        stack_top - STACK_SIZE,
         STACK_SIZE / 0x1000,
-        PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE,
-        PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE,
+        parent_flags,
+        parent_flags,
     )?;
 
-    // Create a 64 KiB stack for the process.
-
     unsafe { owned_mapper.switch() };
 
+    // `sysretq` loads RFLAGS from `r11`: the process starts with interrupts enabled.
     unsafe {
         core::arch::asm!(
             r#"
-            mov rcx, {}
             mov rsp, {}
             mov rbp, rsp
             sysretq
             "#,
-            const LOAD_ADDR,
-            const STACK_TOP,
+            in(reg) stack_top,
+            in("rcx") entry,
+            in("r11") RFlags::INTERRUPT.bits(),
         );
     }
 
     Ok(())
 }
+
+/// Returns the page-aligned range of virtual addresses covered by `segment`.
+///
+/// [`None`] is returned if the segment does not fit in the lower half of the address space.
+fn segment_pages(segment: &LoadSegment) -> Option<(VirtAddr, VirtAddr)> {
+    let end = segment
+        .vaddr
+        .checked_add(segment.memsz)?
+        .checked_add(0xFFF)?
+        & !0xFFF;
+
+    if end > USER_SPACE_END {
+        return None;
+    }
+
+    Some((segment.vaddr & !0xFFF, end))
+}
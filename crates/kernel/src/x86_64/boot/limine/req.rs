@@ -1,9 +1,15 @@
-use nd_limine::{BootloaderInfo, EntryPoint, Hhdm, KernelAddress, MemoryMap, Module, Request};
+use nd_limine::{
+    BootTime, BootloaderInfo, EntryPoint, Hhdm, KernelAddress, MemoryMap, Module, Request,
+};
 
 /// Requests the bootloader to provide information about itself, such as its name and version.
 /// Those information will be logged at startup.
 pub static BOOTLOADER_INFO: Request<BootloaderInfo> = Request::new(BootloaderInfo);
 
+/// Requests the bootloader to provide the time at which the system was booted. It will be logged
+/// at startup.
+pub static BOOT_TIME: Request<BootTime> = Request::new(BootTime);
+
 /// Requests the Limine bootloader to call a specific function rather than the entry point specified
 /// in the ELF header.
 pub static ENTRY_POINT: Request<EntryPoint> = Request::new(EntryPoint(super::entry_point));
@@ -27,6 +33,7 @@ pub static HHDM: Request<Hhdm> = Request::new(Hhdm);
 nd_limine::limine_reqs!(
     MEMORY_MAP,
     BOOTLOADER_INFO,
+    BOOT_TIME,
     MODULE,
     ENTRY_POINT,
     KERNEL_ADDR,
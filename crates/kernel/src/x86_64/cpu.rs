@@ -0,0 +1,45 @@
+//! Sanity checks on the state in which the bootloader left the CPU.
+
+use nd_x86_64::{Cr0, Cr4, Efer};
+
+/// Makes sure that the CPU is running in long mode, with paging enabled.
+///
+/// The bootloader is supposed to hand control to the kernel in that state. If it did not, this
+/// function logs the missing bits and stops the system rather than letting it crash in a
+/// confusing way later on.
+pub fn assert_long_mode_ready() {
+    let cr0 = nd_x86_64::cr0();
+    let cr4 = nd_x86_64::cr4();
+    let efer = nd_x86_64::efer();
+
+    let mut ok = true;
+    let mut check = |present: bool, message: &str| {
+        if !present {
+            nd_log::error!("{}", message);
+            ok = false;
+        }
+    };
+
+    check(
+        cr0.contains(Cr0::PROTECTED_MODE),
+        "Protected mode is not enabled (CR0.PE).",
+    );
+    check(cr0.contains(Cr0::PAGING), "Paging is not enabled (CR0.PG).");
+    check(
+        cr4.contains(Cr4::PHYSICAL_ADDRESS_EXTENSION),
+        "Physical address extension is not enabled (CR4.PAE).",
+    );
+    check(
+        efer.contains(Efer::IA32_MODE_ENABLE),
+        "Long mode is not enabled (EFER.LME).",
+    );
+    check(
+        efer.contains(Efer::IA32_MODE_ENABLE_ACTIVE),
+        "Long mode is not active (EFER.LMA).",
+    );
+
+    if !ok {
+        nd_log::error!("The bootloader did not leave the CPU in the expected state.");
+        crate::die();
+    }
+}
@@ -0,0 +1,160 @@
+use core::fmt;
+
+use nd_x86_64::{cpuid, CpuidFeature};
+
+/// The optional CPU features that the kernel knows how to use.
+///
+/// This is populated once during boot by [`CpuFeatures::read`], and is then available through
+/// [`SysInfo::cpu_features`](super::SysInfo::cpu_features).
+#[derive(Clone, Copy)]
+pub struct CpuFeatures {
+    no_execute: bool,
+    pages_1gib: bool,
+    x2apic: bool,
+    fsgsbase: bool,
+    pcid: bool,
+    tsc_deadline: bool,
+    xsave: bool,
+    invariant_tsc: bool,
+}
+
+impl CpuFeatures {
+    /// Queries the CPU to determine which features it supports.
+    pub fn read() -> Self {
+        // The leaves that we query are checked against the maximum supported leaf first.
+        let max_leaf = cpuid(0).eax;
+        let max_ext_leaf = cpuid(0x8000_0000).eax;
+
+        let leaf_1 = cpuid(1);
+        let leaf_7_ebx = if max_leaf >= 7 { cpuid(7).ebx } else { 0 };
+        let ext_1_edx = if max_ext_leaf >= 0x8000_0001 {
+            cpuid(0x8000_0001).edx
+        } else {
+            0
+        };
+        let ext_7_edx = if max_ext_leaf >= 0x8000_0007 {
+            cpuid(0x8000_0007).edx
+        } else {
+            0
+        };
+
+        Self {
+            no_execute: ext_1_edx & (1 << 20) != 0,
+            pages_1gib: ext_1_edx & (1 << 26) != 0,
+            x2apic: CpuidFeature::X2Apic.is_set_in(&leaf_1),
+            fsgsbase: leaf_7_ebx & (1 << 0) != 0,
+            pcid: CpuidFeature::Pcid.is_set_in(&leaf_1),
+            tsc_deadline: CpuidFeature::TscDeadline.is_set_in(&leaf_1),
+            xsave: CpuidFeature::Xsave.is_set_in(&leaf_1),
+            invariant_tsc: ext_7_edx & (1 << 8) != 0,
+        }
+    }
+
+    /// Whether pages can be marked as non-executable.
+    ///
+    /// This is reported by bit 20 of EDX for the CPUID leaf `0x80000001`.
+    #[inline(always)]
+    pub fn no_execute(&self) -> bool {
+        self.no_execute
+    }
+
+    /// Whether the CPU supports 1 GiB pages.
+    ///
+    /// This is reported by bit 26 of EDX for the CPUID leaf `0x80000001`.
+    #[inline(always)]
+    pub fn pages_1gib(&self) -> bool {
+        self.pages_1gib
+    }
+
+    /// Whether the local APIC can be used in x2APIC mode.
+    ///
+    /// This is reported by bit 21 of ECX for the CPUID leaf `1`.
+    #[inline(always)]
+    pub fn x2apic(&self) -> bool {
+        self.x2apic
+    }
+
+    /// Whether the `RDFSBASE`, `WRFSBASE`, `RDGSBASE` and `WRGSBASE` instructions are available.
+    ///
+    /// This is reported by bit 0 of EBX for the CPUID leaf `7`.
+    #[inline(always)]
+    pub fn fsgsbase(&self) -> bool {
+        self.fsgsbase
+    }
+
+    /// Whether the CPU supports process-context identifiers.
+    ///
+    /// This is reported by bit 17 of ECX for the CPUID leaf `1`.
+    #[inline(always)]
+    pub fn pcid(&self) -> bool {
+        self.pcid
+    }
+
+    /// Whether the local APIC timer supports the TSC-deadline mode.
+    ///
+    /// This is reported by bit 24 of ECX for the CPUID leaf `1`.
+    #[inline(always)]
+    pub fn tsc_deadline(&self) -> bool {
+        self.tsc_deadline
+    }
+
+    /// Whether the `XSAVE` family of instructions is available.
+    ///
+    /// This is reported by bit 26 of ECX for the CPUID leaf `1`.
+    #[inline(always)]
+    pub fn xsave(&self) -> bool {
+        self.xsave
+    }
+
+    /// Whether the *Time-Stamp Counter* runs at a constant rate, regardless of power states.
+    ///
+    /// This is reported by bit 8 of EDX for the CPUID leaf `0x80000007`.
+    #[inline(always)]
+    pub fn invariant_tsc(&self) -> bool {
+        self.invariant_tsc
+    }
+
+    /// Returns the name of each feature, along with whether it is supported.
+    fn list(&self) -> [(&'static str, bool); 8] {
+        [
+            ("nx", self.no_execute()),
+            ("1gib-pages", self.pages_1gib()),
+            ("x2apic", self.x2apic()),
+            ("fsgsbase", self.fsgsbase()),
+            ("pcid", self.pcid()),
+            ("tsc-deadline", self.tsc_deadline()),
+            ("xsave", self.xsave()),
+            ("invariant-tsc", self.invariant_tsc()),
+        ]
+    }
+}
+
+impl fmt::Debug for CpuFeatures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = f.debug_struct("CpuFeatures");
+        for (name, supported) in self.list() {
+            s.field(name, &supported);
+        }
+        s.finish()
+    }
+}
+
+/// Lists the supported features, separated by spaces.
+impl fmt::Display for CpuFeatures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        for (name, _) in self.list().into_iter().filter(|&(_, supported)| supported) {
+            if !first {
+                f.write_str(" ")?;
+            }
+            f.write_str(name)?;
+            first = false;
+        }
+
+        if first {
+            f.write_str("<none>")?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,47 @@
+//! Short busy-waiting delays, usable early during boot.
+//!
+//! Device initialization sequences often need to "wait N microseconds" before the scheduler or
+//! even the interrupts are available. Unlike [`busy_wait`](super::busy_wait), the functions of
+//! this module do not require the system info structure to be initialized: they use the best
+//! calibrated time source available when they are called.
+//!
+//! - Once [`calibrate_tsc`](super::calibrate_tsc) has been called, the *Time-Stamp Counter* is
+//!   used.
+//!
+//! - Before that, channel 2 of the *Programmable Interval Timer* is used. Its frequency is fixed,
+//!   so no calibration is needed, but every wait costs a few slow port accesses, which makes very
+//!   short delays overshoot.
+
+use nd_x86_64::rdtsc;
+
+use super::time::{calibrated_tsc_frequency, pit_delay};
+
+/// Spins for at least `us` microseconds.
+///
+/// This function works with interrupts disabled. It never sleeps or yields.
+///
+/// Before the TSC has been calibrated, this function programs channel 2 of the PIT, which must not
+/// be used by anything else at that time. This is always the case during early boot.
+pub fn udelay(us: u64) {
+    match calibrated_tsc_frequency() {
+        Some(frequency) => {
+            let ticks = (us as u128 * frequency as u128 / 1_000_000) as u64;
+            let start = rdtsc();
+
+            while rdtsc().wrapping_sub(start) < ticks {
+                core::hint::spin_loop();
+            }
+        }
+        // SAFETY:
+        //  The TSC is calibrated before anything else starts using the PIT.
+        None => unsafe { pit_delay(us) },
+    }
+}
+
+/// Spins for at least `ms` milliseconds.
+///
+/// See [`udelay`].
+#[inline]
+pub fn mdelay(ms: u64) {
+    udelay(ms.saturating_mul(1000));
+}
@@ -0,0 +1,456 @@
+//! Parsing of the ELF files loaded by the kernel.
+//!
+//! <https://refspecs.linuxfoundation.org/elf/gabi4+/ch4.eheader.html>
+
+use nd_x86_64::VirtAddr;
+
+/// The magic number at the start of every ELF file.
+pub const ELF_MAGIC: [u8; 4] = *b"\x7fELF";
+
+/// The size of an ELF64 file header.
+const ELF_HEADER_SIZE: usize = 64;
+/// The size of an ELF64 program header.
+const PROGRAM_HEADER_SIZE: usize = 56;
+
+/// `EI_CLASS` value for 64-bit objects.
+const ELFCLASS64: u8 = 2;
+/// `EI_DATA` value for little-endian objects.
+const ELFDATA2LSB: u8 = 1;
+/// `e_type` value for executable files.
+const ET_EXEC: u16 = 2;
+/// `e_type` value for shared object (position-independent) files.
+const ET_DYN: u16 = 3;
+/// `e_machine` value for AMD x86-64.
+const EM_X86_64: u16 = 62;
+/// `p_type` value for loadable segments.
+const PT_LOAD: u32 = 1;
+/// `p_flags` bit set for executable segments.
+const PF_X: u32 = 1 << 0;
+/// `p_flags` bit set for writable segments.
+const PF_W: u32 = 1 << 1;
+
+/// An error which might occur when validating an ELF file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryPointError {
+    /// The file is too small to contain an ELF header.
+    Truncated,
+    /// The file does not start with the ELF magic number.
+    InvalidMagic,
+    /// The file is not a 64-bit ELF file.
+    Not64Bit,
+    /// The file is not a little-endian ELF file.
+    NotLittleEndian,
+    /// The file is neither an executable nor a position-independent executable.
+    NotExecutable,
+    /// The file was compiled for another architecture than x86_64.
+    WrongArchitecture,
+    /// The program header table is declared with entries smaller than an ELF64 program header.
+    InvalidProgramHeaderSize,
+    /// The program header table does not fit within the file.
+    ProgramHeadersOutOfBounds,
+    /// A loadable segment is larger in the file than in memory, or does not fit in the address
+    /// space.
+    InvalidSegment,
+    /// The content of a loadable segment does not fit within the file.
+    SegmentOutOfBounds,
+}
+
+/// The validated header of an ELF file.
+#[derive(Debug, Clone, Copy)]
+pub struct ElfHeader {
+    /// The type of the file (`e_type`).
+    pub ty: u16,
+    /// The address of the entry point of the program (`e_entry`).
+    pub entry: VirtAddr,
+    /// The offset of the program header table within the file (`e_phoff`).
+    pub phoff: u64,
+    /// The size of an entry of the program header table (`e_phentsize`).
+    pub phentsize: u16,
+    /// The number of entries in the program header table (`e_phnum`).
+    pub phnum: u16,
+}
+
+/// Reads a little-endian `u16` at `offset`.
+///
+/// The caller must ensure that `file` is large enough.
+#[inline(always)]
+fn read_u16(file: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([file[offset], file[offset + 1]])
+}
+
+/// Reads a little-endian `u32` at `offset`.
+///
+/// The caller must ensure that `file` is large enough.
+#[inline(always)]
+fn read_u32(file: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&file[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+/// Reads a little-endian `u64` at `offset`.
+///
+/// The caller must ensure that `file` is large enough.
+#[inline(always)]
+fn read_u64(file: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&file[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+impl ElfHeader {
+    /// Parses and validates the header of the provided ELF file.
+    ///
+    /// The returned header is guaranteed to describe a 64-bit, little-endian, x86_64 executable
+    /// whose program header table lies entirely within `file`.
+    pub fn parse(file: &[u8]) -> Result<Self, EntryPointError> {
+        if file.len() < ELF_MAGIC.len() {
+            return Err(EntryPointError::Truncated);
+        }
+        if file[..4] != ELF_MAGIC {
+            return Err(EntryPointError::InvalidMagic);
+        }
+        if file.len() < ELF_HEADER_SIZE {
+            return Err(EntryPointError::Truncated);
+        }
+        if file[4] != ELFCLASS64 {
+            return Err(EntryPointError::Not64Bit);
+        }
+        if file[5] != ELFDATA2LSB {
+            return Err(EntryPointError::NotLittleEndian);
+        }
+
+        let ty = read_u16(file, 16);
+        if ty != ET_EXEC && ty != ET_DYN {
+            return Err(EntryPointError::NotExecutable);
+        }
+        if read_u16(file, 18) != EM_X86_64 {
+            return Err(EntryPointError::WrongArchitecture);
+        }
+
+        let header = Self {
+            ty,
+            entry: read_u64(file, 24),
+            phoff: read_u64(file, 32),
+            phentsize: read_u16(file, 54),
+            phnum: read_u16(file, 56),
+        };
+
+        if header.phnum != 0 {
+            if (header.phentsize as usize) < PROGRAM_HEADER_SIZE {
+                return Err(EntryPointError::InvalidProgramHeaderSize);
+            }
+
+            let table_end = (header.phnum as u64 * header.phentsize as u64)
+                .checked_add(header.phoff)
+                .ok_or(EntryPointError::ProgramHeadersOutOfBounds)?;
+
+            if table_end > file.len() as u64 {
+                return Err(EntryPointError::ProgramHeadersOutOfBounds);
+            }
+        }
+
+        Ok(header)
+    }
+}
+
+/// Validates the header of the provided ELF file and returns the address of its entry point.
+#[inline]
+pub fn find_entry_point(file: &[u8]) -> Result<VirtAddr, EntryPointError> {
+    ElfHeader::parse(file).map(|header| header.entry)
+}
+
+/// A loadable (`PT_LOAD`) segment of an ELF file.
+#[derive(Debug, Clone, Copy)]
+pub struct LoadSegment {
+    /// The offset of the content of the segment within the file (`p_offset`).
+    pub offset: u64,
+    /// The virtual address at which the segment must be loaded (`p_vaddr`).
+    pub vaddr: VirtAddr,
+    /// The number of bytes of the segment stored in the file (`p_filesz`).
+    pub filesz: u64,
+    /// The size of the segment in memory (`p_memsz`).
+    ///
+    /// The bytes past `filesz` must be zeroed (this is usually the `.bss` section).
+    pub memsz: u64,
+    /// The flags of the segment (`p_flags`).
+    pub flags: u32,
+}
+
+impl LoadSegment {
+    /// Returns whether the segment must be writable.
+    #[inline(always)]
+    pub fn writable(&self) -> bool {
+        self.flags & PF_W != 0
+    }
+
+    /// Returns whether the segment must be executable.
+    #[inline(always)]
+    pub fn executable(&self) -> bool {
+        self.flags & PF_X != 0
+    }
+
+    /// Returns the content of the segment stored in `file`.
+    ///
+    /// `file` must be the file from which the segment was read.
+    #[inline]
+    pub fn data<'a>(&self, file: &'a [u8]) -> &'a [u8] {
+        &file[self.offset as usize..(self.offset + self.filesz) as usize]
+    }
+}
+
+/// An iterator over the loadable segments of an ELF file.
+///
+/// This is returned by [`program_headers`].
+#[derive(Debug, Clone)]
+pub struct ProgramHeaders<'a> {
+    file: &'a [u8],
+    header: ElfHeader,
+    index: u16,
+}
+
+impl<'a> Iterator for ProgramHeaders<'a> {
+    type Item = LoadSegment;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.index < self.header.phnum {
+            let base =
+                self.header.phoff as usize + self.index as usize * self.header.phentsize as usize;
+            self.index += 1;
+
+            if read_u32(self.file, base) != PT_LOAD {
+                continue;
+            }
+
+            return Some(LoadSegment {
+                flags: read_u32(self.file, base + 4),
+                offset: read_u64(self.file, base + 8),
+                vaddr: read_u64(self.file, base + 16),
+                filesz: read_u64(self.file, base + 32),
+                memsz: read_u64(self.file, base + 40),
+            });
+        }
+
+        None
+    }
+}
+
+/// Validates the provided ELF file and returns an iterator over its loadable segments.
+///
+/// Every returned segment is guaranteed to have its content within `file`, and to be at least
+/// as large in memory as in the file.
+pub fn program_headers(file: &[u8]) -> Result<ProgramHeaders, EntryPointError> {
+    let headers = ProgramHeaders {
+        file,
+        header: ElfHeader::parse(file)?,
+        index: 0,
+    };
+
+    for segment in headers.clone() {
+        if segment.filesz > segment.memsz || segment.vaddr.checked_add(segment.memsz).is_none() {
+            return Err(EntryPointError::InvalidSegment);
+        }
+
+        match segment.offset.checked_add(segment.filesz) {
+            Some(end) if end <= file.len() as u64 => (),
+            _ => return Err(EntryPointError::SegmentOutOfBounds),
+        }
+    }
+
+    Ok(headers)
+}
+
+#[cfg(all(test, feature = "kernel-test"))]
+mod tests {
+    use super::*;
+
+    /// Builds a small ELF file with a text segment and a data segment whose tail is `.bss`.
+    fn build_elf() -> [u8; 0x200] {
+        let mut file = [0u8; 0x200];
+
+        file[..4].copy_from_slice(&ELF_MAGIC);
+        file[4] = ELFCLASS64;
+        file[5] = ELFDATA2LSB;
+        file[16..18].copy_from_slice(&ET_EXEC.to_le_bytes());
+        file[18..20].copy_from_slice(&EM_X86_64.to_le_bytes());
+        file[24..32].copy_from_slice(&0x10_0000u64.to_le_bytes());
+        file[32..40].copy_from_slice(&(ELF_HEADER_SIZE as u64).to_le_bytes());
+        file[54..56].copy_from_slice(&(PROGRAM_HEADER_SIZE as u16).to_le_bytes());
+        file[56..58].copy_from_slice(&3u16.to_le_bytes());
+
+        let mut phdr = |index: usize,
+                        ty: u32,
+                        flags: u32,
+                        offset: u64,
+                        vaddr: u64,
+                        filesz: u64,
+                        memsz: u64| {
+            let base = ELF_HEADER_SIZE + index * PROGRAM_HEADER_SIZE;
+            file[base..base + 4].copy_from_slice(&ty.to_le_bytes());
+            file[base + 4..base + 8].copy_from_slice(&flags.to_le_bytes());
+            file[base + 8..base + 16].copy_from_slice(&offset.to_le_bytes());
+            file[base + 16..base + 24].copy_from_slice(&vaddr.to_le_bytes());
+            file[base + 32..base + 40].copy_from_slice(&filesz.to_le_bytes());
+            file[base + 40..base + 48].copy_from_slice(&memsz.to_le_bytes());
+        };
+
+        // PF_R | PF_X
+        phdr(0, PT_LOAD, 0b101, 0x100, 0x10_0000, 0x80, 0x80);
+        // A `PT_GNU_STACK` segment, which must be skipped.
+        phdr(1, 0x6474_E551, 0b110, 0, 0, 0, 0);
+        // PF_R | PF_W, with 0x1000 bytes of `.bss`.
+        phdr(2, PT_LOAD, 0b110, 0x180, 0x10_1000, 0x40, 0x1040);
+
+        file
+    }
+
+    #[test_case]
+    fn load_segments() {
+        let file = build_elf();
+        let mut segments = program_headers(&file).unwrap();
+
+        let text = segments.next().unwrap();
+        assert_eq!(text.vaddr, 0x10_0000);
+        assert_eq!(text.data(&file).len(), 0x80);
+        assert!(text.executable() && !text.writable());
+
+        let data = segments.next().unwrap();
+        assert_eq!(data.offset, 0x180);
+        assert_eq!(data.vaddr, 0x10_1000);
+        assert_eq!((data.filesz, data.memsz), (0x40, 0x1040));
+        assert!(data.writable() && !data.executable());
+
+        assert!(segments.next().is_none());
+    }
+
+    #[test_case]
+    fn truncated() {
+        let file = build_elf();
+
+        assert_eq!(
+            ElfHeader::parse(&file[..2]).err(),
+            Some(EntryPointError::Truncated)
+        );
+        assert_eq!(
+            ElfHeader::parse(&file[..ELF_HEADER_SIZE - 1]).err(),
+            Some(EntryPointError::Truncated)
+        );
+    }
+
+    #[test_case]
+    fn invalid_magic() {
+        let mut file = build_elf();
+        file[0] = 0;
+
+        assert_eq!(
+            ElfHeader::parse(&file).err(),
+            Some(EntryPointError::InvalidMagic)
+        );
+    }
+
+    #[test_case]
+    fn not_64_bit() {
+        /// `EI_CLASS` value for 32-bit objects.
+        const ELFCLASS32: u8 = 1;
+
+        let mut file = build_elf();
+        file[4] = ELFCLASS32;
+
+        assert_eq!(
+            ElfHeader::parse(&file).err(),
+            Some(EntryPointError::Not64Bit)
+        );
+    }
+
+    #[test_case]
+    fn not_little_endian() {
+        /// `EI_DATA` value for big-endian objects.
+        const ELFDATA2MSB: u8 = 2;
+
+        let mut file = build_elf();
+        file[5] = ELFDATA2MSB;
+
+        assert_eq!(
+            ElfHeader::parse(&file).err(),
+            Some(EntryPointError::NotLittleEndian)
+        );
+    }
+
+    #[test_case]
+    fn not_executable() {
+        /// `e_type` value for relocatable files.
+        const ET_REL: u16 = 1;
+
+        let mut file = build_elf();
+        file[16..18].copy_from_slice(&ET_REL.to_le_bytes());
+
+        assert_eq!(
+            ElfHeader::parse(&file).err(),
+            Some(EntryPointError::NotExecutable)
+        );
+    }
+
+    #[test_case]
+    fn invalid_program_header_size() {
+        let mut file = build_elf();
+        file[54..56].copy_from_slice(&(PROGRAM_HEADER_SIZE as u16 - 1).to_le_bytes());
+
+        assert_eq!(
+            ElfHeader::parse(&file).err(),
+            Some(EntryPointError::InvalidProgramHeaderSize)
+        );
+    }
+
+    #[test_case]
+    fn program_headers_out_of_bounds() {
+        let mut file = build_elf();
+
+        // The table starts inside the file but ends past its end.
+        file[32..40].copy_from_slice(&0x1C0u64.to_le_bytes());
+        assert_eq!(
+            ElfHeader::parse(&file).err(),
+            Some(EntryPointError::ProgramHeadersOutOfBounds)
+        );
+
+        // The end of the table overflows.
+        file[32..40].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(
+            ElfHeader::parse(&file).err(),
+            Some(EntryPointError::ProgramHeadersOutOfBounds)
+        );
+    }
+
+    #[test_case]
+    fn wrong_architecture() {
+        /// `e_machine` value for ARM AArch64.
+        const EM_AARCH64: u16 = 183;
+
+        let mut file = build_elf();
+        file[18..20].copy_from_slice(&EM_AARCH64.to_le_bytes());
+
+        assert_eq!(
+            find_entry_point(&file),
+            Err(EntryPointError::WrongArchitecture)
+        );
+    }
+
+    #[test_case]
+    fn invalid_segments() {
+        let mut file = build_elf();
+        let data = ELF_HEADER_SIZE + 2 * PROGRAM_HEADER_SIZE;
+
+        // The content of the data segment goes past the end of the file.
+        file[data + 32..data + 40].copy_from_slice(&0x200u64.to_le_bytes());
+        assert_eq!(
+            program_headers(&file).err(),
+            Some(EntryPointError::SegmentOutOfBounds)
+        );
+
+        // The segment is larger in the file than in memory.
+        file[data + 40..data + 48].copy_from_slice(&0x10u64.to_le_bytes());
+        assert_eq!(
+            program_headers(&file).err(),
+            Some(EntryPointError::InvalidSegment)
+        );
+    }
+}
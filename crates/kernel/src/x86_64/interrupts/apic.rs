@@ -2,6 +2,8 @@ use nd_apic::XApic;
 use nd_x86_64::InterruptStackFrame;
 
 pub extern "x86-interrupt" fn apic_timer(_: InterruptStackFrame) {
+    crate::x86_64::timer::tick();
+
     // SAFETY:
     //  The APIC is identity mapped. Because local APICs are CPU-local, we can safely access the
     //  APIC from any CPU as long as service handlers are not recursively called (because that
@@ -12,5 +14,17 @@ pub extern "x86-interrupt" fn apic_timer(_: InterruptStackFrame) {
 }
 
 pub extern "x86-interrupt" fn apic_spurious(_: InterruptStackFrame) {
-    // We don't need to send an EOI here.
+    // Spurious interrupts are not in-service: sending an EOI here would acknowledge an unrelated
+    // interrupt.
+}
+
+pub extern "x86-interrupt" fn apic_error(_: InterruptStackFrame) {
+    // SAFETY:
+    //  Same as `apic_timer`.
+    let mut lapic = unsafe { XApic::identity_mapped() };
+
+    let errors = lapic.error_status();
+    nd_log::error!("Local APIC {} reported an error: {:?}", lapic.id(), errors);
+
+    lapic.end_of_interrupt();
 }
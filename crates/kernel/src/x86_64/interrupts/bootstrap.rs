@@ -0,0 +1,109 @@
+//! A minimal *Interrupt Descriptor Table* used until the real one is set up.
+//!
+//! Without an IDT, a CPU exception during early boot escalates to a triple fault, which resets
+//! the machine without any output. The handlers of this module write a fixed message directly to
+//! the serial port and halt the CPU. They do not rely on the logger, on locks, or on any kernel
+//! state: the serial port is polled and written byte by byte.
+
+use nd_x86_64::{
+    inb, outb, GateType, Idt, InterruptStackFrame, PageFaultError, PrivilegeLevel, TableEntryError,
+};
+
+/// The port of the serial port used to report early exceptions.
+///
+/// This must be the same port as the one used by the logger.
+const COM1: u16 = 0x3F8;
+
+/// The *Interrupt Descriptor Table* used until [`setup_idt`](crate::x86_64::setup_idt) is called.
+static mut BOOTSTRAP_IDT: Idt = Idt::new();
+
+/// Writes `message` to the serial port and halts the CPU.
+fn early_exception(message: &str) -> ! {
+    for b in "\r\nEarly CPU exception: "
+        .bytes()
+        .chain(message.bytes())
+        .chain("\r\nThe system has been halted.\r\n".bytes())
+    {
+        unsafe {
+            while inb(COM1 + 5) & 0x20 == 0 {
+                core::hint::spin_loop();
+            }
+
+            outb(COM1, b);
+        }
+    }
+
+    nd_x86_64::hlt_loop();
+}
+
+/// Defines the exception handlers of the bootstrap IDT, and the function installing them.
+macro_rules! bootstrap_handlers {
+    ($(
+        $setter:ident => fn $name:ident($($arg:ty),*) $(-> $ret:ty)? = $message:literal;
+    )*) => {
+        $(
+            extern "x86-interrupt" fn $name(_: InterruptStackFrame $(, _: $arg)*) $(-> $ret)? {
+                early_exception($message)
+            }
+        )*
+
+        /// Installs a minimal IDT reporting CPU exceptions directly on the serial port.
+        ///
+        /// This should be the very first thing the kernel does. The IDT is later replaced by the
+        /// one installed by [`setup_idt`](crate::x86_64::setup_idt).
+        ///
+        /// # Safety
+        ///
+        /// This function must only be called once, before [`setup_idt`](crate::x86_64::setup_idt).
+        pub unsafe fn setup_bootstrap_idt() {
+            // The GDT of the bootloader is still in use.
+            let cs = nd_x86_64::cs();
+
+            unsafe {
+                $(
+                    BOOTSTRAP_IDT.$setter(
+                        $name,
+                        cs,
+                        None,
+                        GateType::Interrupt,
+                        PrivilegeLevel::Ring0,
+                    );
+                )*
+
+                nd_x86_64::lidt(&BOOTSTRAP_IDT.table_ptr());
+            }
+        }
+    };
+}
+
+bootstrap_handlers! {
+    set_division_error => fn division_error() = "Division Error";
+    set_debug => fn debug() = "Debug";
+    set_non_maskable_interrupt => fn non_maskable_interrupt() = "Non-Maskable Interrupt";
+    set_breakpoint => fn breakpoint() = "Breakpoint";
+    set_overflow => fn overflow() = "Overflow";
+    set_bound_range_exceeded => fn bound_range_exceeded() = "Bound Range Exceeded";
+    set_invalid_op_code => fn invalid_op_code() = "Invalid Op Code";
+    set_device_not_available => fn device_not_available() = "Device Not Available";
+    set_double_fault => fn double_fault(u64) -> ! = "Double Fault";
+    set_invalid_tss => fn invalid_tss(TableEntryError) = "Invalid TSS";
+    set_segment_not_present => fn segment_not_present(TableEntryError) = "Segment Not Present";
+    set_stack_segment_fault => fn stack_segment_fault(TableEntryError) = "Stack Segment Fault";
+    set_general_protection_fault => fn general_protection_fault(TableEntryError) =
+        "General Protection Fault";
+    set_page_fault => fn page_fault(PageFaultError) = "Page Fault";
+    set_x87_floating_point_exception => fn x87_floating_point_exception() =
+        "x87 Floating Point Exception";
+    set_alignment_check => fn alignment_check(u64) = "Alignment Check";
+    set_machine_check => fn machine_check() -> ! = "Machine Check";
+    set_simd_floating_point_exception => fn simd_floating_point_exception() =
+        "SIMD Floating Point Exception";
+    set_virtualization_exception => fn virtualization_exception() = "Virtualization Exception";
+    set_control_protection_exception => fn control_protection_exception(u64) =
+        "Control Protection Exception";
+    set_hypervisor_injection_exception => fn hypervisor_injection_exception() =
+        "Hypervisor Injection Exception";
+    set_vmm_communication_exception => fn vmm_communication_exception(u64) =
+        "VMM Communication Exception";
+    set_security_exception => fn security_exception(u64) = "Security Exception";
+}
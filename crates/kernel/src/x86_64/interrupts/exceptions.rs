@@ -1,19 +1,35 @@
-use nd_x86_64::{InterruptStackFrame, PageFaultError, TableEntryError};
+use nd_x86_64::{InterruptStackFrame, PageFaultError, TableEntryError, VirtAddr};
+
+use crate::x86_64::SysInfo;
+
+/// Panics with a kernel stack overflow report if `addr` is part of the guard page of the kernel
+/// stack.
+///
+/// `addr` is the faulting address, usually read from **CR2**.
+fn check_kernel_stack_overflow(frame: &InterruptStackFrame, addr: VirtAddr) {
+    let guard = SysInfo::read_kernel_stack_guard_addr();
+
+    if (guard..guard + 4096).contains(&addr) {
+        panic!(
+            "kernel stack overflow (addr = {:#x}, RIP = {:#x}, RSP = {:#x})",
+            addr, frame.rip, frame.rsp
+        );
+    }
+}
 
 pub extern "x86-interrupt" fn double_fault(frame: InterruptStackFrame, code: u64) -> ! {
     nd_log::trace!("Double Fault (code = {})", code);
-    panic!(
-        "Double Fault (RIP = {:x}, RSP = {:x}",
-        frame.instruction_pointer(),
-        frame.stack_pointer()
-    );
+
+    // A kernel stack overflow usually ends up here: the CPU fails to push the page fault's
+    // interrupt frame on the (overflowed) stack.
+    let addr = nd_x86_64::cr2();
+    check_kernel_stack_overflow(&frame, addr);
+
+    panic!("Double Fault (RIP = {:x}, RSP = {:x}", frame.rip, frame.rsp);
 }
 
 pub extern "x86-interrupt" fn invalid_op_code(frame: InterruptStackFrame) {
-    panic!(
-        "Invalid Op Code (addr = {:#x})",
-        frame.instruction_pointer()
-    );
+    panic!("Invalid Op Code (addr = {:#x})", frame.rip);
 }
 
 pub extern "x86-interrupt" fn device_not_available(_: InterruptStackFrame) {
@@ -21,11 +37,11 @@ pub extern "x86-interrupt" fn device_not_available(_: InterruptStackFrame) {
 }
 
 pub extern "x86-interrupt" fn segment_not_present(_: InterruptStackFrame, err: TableEntryError) {
-    panic!("Segment Not Present (err = {err:?})");
+    panic!("Segment Not Present ({err})");
 }
 
 pub extern "x86-interrupt" fn stack_segment_fault(_: InterruptStackFrame, err: TableEntryError) {
-    panic!("Stack Segment Fault (err = {err:?})");
+    panic!("Stack Segment Fault ({err})");
 }
 
 pub extern "x86-interrupt" fn general_protection_fault(
@@ -35,23 +51,20 @@ pub extern "x86-interrupt" fn general_protection_fault(
     if err.to_raw() == 0 {
         panic!(
             "General Protection Fault (err = None, RIP = {:#x})",
-            frame.instruction_pointer()
+            frame.rip
         );
     } else {
-        panic!(
-            "General Protection Fault (err = {err:?}, RIP = {:#x})",
-            frame.instruction_pointer()
-        );
+        panic!("General Protection Fault ({err}, RIP = {:#x})", frame.rip);
     }
 }
 
 pub extern "x86-interrupt" fn page_fault(frame: InterruptStackFrame, err: PageFaultError) {
+    let addr = nd_x86_64::cr2();
+    check_kernel_stack_overflow(&frame, addr);
+
     panic!(
-        "Page Fault (err = {:?}, addr = {:#x}, RIP = {:#x}, RSP = {:#x})",
-        err,
-        nd_x86_64::cr2(),
-        frame.instruction_pointer(),
-        frame.stack_pointer()
+        "Page Fault ({}, addr = {:#x}, RIP = {:#x}, RSP = {:#x})",
+        err, addr, frame.rip, frame.rsp
     );
 }
 
@@ -68,7 +81,7 @@ pub extern "x86-interrupt" fn machine_check(_: InterruptStackFrame) -> ! {
 }
 
 pub extern "x86-interrupt" fn invalid_tss(_: InterruptStackFrame, err: TableEntryError) {
-    panic!("Invalid TSS (err = {err:?})");
+    panic!("Invalid TSS ({err})");
 }
 
 pub extern "x86-interrupt" fn x87_floating_point_exception(_: InterruptStackFrame) {
@@ -2,9 +2,11 @@
 //! are received.
 
 mod apic;
+mod bootstrap;
 mod exceptions;
 mod system_call;
 
 pub use self::apic::*;
+pub use self::bootstrap::*;
 pub use self::exceptions::*;
 pub use self::system_call::*;
@@ -46,15 +46,19 @@ pub unsafe extern "C" fn handle_syscall() {
         //  system calls, and are *coincedentally* the same as the C ABI. This means that we
         //  won't need to move any of those registers.
         //
-        //  The `rcx` register contains the return address of the system call, so we need to save
-        //  this one, as it can be clobbered by functions using the C ABI.
+        //  The `rcx` register contains the return address of the system call, and the `r11`
+        //  register contains the RFLAGS of the caller. Both are restored by `sysretq`, so we need
+        //  to save them, as they can be clobbered by functions using the C ABI. On the error path,
+        //  no function is called and both registers still hold the values set by `syscall`.
         asm!(
             r#"
             cmp       rax,   {}
             jae       1f
             push      rcx
+            push      r11
             lea       rcx,   [{} + rax * {}]
             call      [rcx]
+            pop       r11
             pop       rcx
             sysretq
         1:
@@ -69,3 +73,143 @@ pub unsafe extern "C" fn handle_syscall() {
         );
     }
 }
+
+#[cfg(all(test, feature = "kernel-test"))]
+mod tests {
+    use core::arch::global_asm;
+    use core::mem::MaybeUninit;
+
+    use nd_x86_64::{Cr3, Cr3Flags, PageTableFlags, RFlags, VirtAddr};
+
+    use super::*;
+    use crate::x86_64::OwnedMapper;
+
+    // The user program run by `user_flags_are_preserved`.
+    //
+    // It performs an invalid system call (which takes the error path of `handle_syscall`), then a
+    // valid one (which takes the success path), saving RFLAGS after each of them. The results are
+    // passed to `report` through a `Ring0` system call.
+    //
+    // `r12` and `r13` must hold the addresses of `report` and `noop`. Only `mov`, `push` and `pop`
+    // are used between the system calls, none of which modify RFLAGS.
+    global_asm!(
+        r#"
+        .global ND_SYSCALL_TEST_START
+        .global ND_SYSCALL_TEST_END
+        ND_SYSCALL_TEST_START:
+            mov eax, {invalid}
+            syscall
+            mov r14, rax
+            pushfq
+            pop r15
+            mov edi, 0
+            mov rsi, r13
+            mov eax, {ring0}
+            syscall
+            pushfq
+            push r15
+            push r14
+            mov rdi, rsp
+            mov rsi, r12
+            mov eax, {ring0}
+            syscall
+            ud2
+        ND_SYSCALL_TEST_END:
+        "#,
+        invalid = const SystemCall::COUNT,
+        ring0 = const SystemCall::Ring0.to_usize(),
+    );
+
+    extern "C" {
+        static ND_SYSCALL_TEST_START: u8;
+        static ND_SYSCALL_TEST_END: u8;
+    }
+
+    /// The stack pointer to restore when the user program is done.
+    static mut TEST_RSP: u64 = 0;
+
+    /// The values reported by the user program: the result of the invalid system call, then the
+    /// value of RFLAGS after each system call.
+    static mut REPORT: [u64; 3] = [0; 3];
+
+    extern "C" fn noop(_: *mut ()) {}
+
+    /// Saves the values reported by the user program, and jumps back into the test.
+    extern "C" fn report(data: *mut ()) {
+        unsafe {
+            REPORT = (data as *const [u64; 3]).read();
+            asm!("mov rsp, [rip + {}]", "ret", sym TEST_RSP, options(noreturn));
+        }
+    }
+
+    #[test_case]
+    fn user_flags_are_preserved() {
+        const CODE: VirtAddr = 0x40_0000;
+        const STACK_TOP: VirtAddr = 0x80_0000;
+
+        let allocator = crate::testing::page_allocator();
+        let kernel = crate::testing::kernel_address_space();
+        let mut mapper = OwnedMapper::new_user(allocator, kernel).unwrap();
+
+        let code = unsafe {
+            let start = &ND_SYSCALL_TEST_START as *const u8;
+            let end = &ND_SYSCALL_TEST_END as *const u8;
+            core::slice::from_raw_parts(start, end.offset_from(start) as usize)
+        };
+
+        let flags = PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE;
+        let parent_flags = flags | PageTableFlags::WRITABLE;
+        mapper
+            .load_with(CODE, 1, flags, parent_flags, |page| {
+                for (dst, &src) in page.iter_mut().zip(code) {
+                    *dst = MaybeUninit::new(src);
+                }
+            })
+            .unwrap();
+        mapper
+            .load_uninit(STACK_TOP - 0x1000, 1, parent_flags, parent_flags)
+            .unwrap();
+
+        // Interrupts are kept disabled in user mode: they would switch to the top of the kernel
+        // stack, which the test is using. Bit 1 of RFLAGS is always set.
+        let rflags = RFlags::CARRY | RFlags::DIRECTION;
+        let expected = rflags.bits() | 0b10;
+
+        unsafe {
+            nd_x86_64::cli();
+            mapper.switch();
+
+            // `rbx` and `rbp` cannot be marked as clobbered, they are saved on the stack. `report`
+            // returns to the label `2` with the stack pointer saved in `TEST_RSP`.
+            asm!(
+                "push rbp",
+                "push rbx",
+                "lea rax, [rip + 2f]",
+                "push rax",
+                "mov [rip + {test_rsp}], rsp",
+                "mov rsp, rdx",
+                "sysretq",
+                "2:",
+                "pop rbx",
+                "pop rbp",
+                test_rsp = sym TEST_RSP,
+                in("rdx") STACK_TOP,
+                in("rcx") CODE,
+                in("r11") rflags.bits(),
+                inout("r12") report as usize => _,
+                inout("r13") noop as usize => _,
+                out("r14") _,
+                out("r15") _,
+                clobber_abi("C"),
+            );
+
+            nd_x86_64::set_cr3(Cr3::new(kernel.pml4(), Cr3Flags::empty()));
+            nd_x86_64::sti();
+        }
+
+        let [result, after_error, after_success] = unsafe { REPORT };
+        assert_eq!(result, SysError::INVALID_ARGUMENT.0 as u64);
+        assert_eq!(after_error, expected);
+        assert_eq!(after_success, expected);
+    }
+}
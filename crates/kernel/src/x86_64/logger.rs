@@ -1,8 +1,17 @@
 use nd_log::Verbosity;
-use nd_x86_64::{inb, outb, RFlags};
+use nd_spin::Mutex;
+use nd_x86_64::{Port, RFlags};
 
-use core::fmt;
-use core::fmt::Write as _;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::Ordering::{Acquire, Release};
+
+use crate::console::Console;
+
+/// Whether the serial port has been initialized.
+static SERIAL_INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+/// Serializes the accesses to the serial port.
+static SERIAL_LOCK: Mutex<()> = Mutex::new(());
 
 /// Initializes the logging facade.
 ///
@@ -21,36 +30,78 @@ pub unsafe fn initialize_logger() {
     //
     //  This file is the only place of the code that uses the serial port, ensuring exclusivity.
     unsafe { SerialOut::init() };
+    SERIAL_INITIALIZED.store(true, Release);
 
     nd_log::set_global_logger(|record| {
         let prefix = match record.verbosity {
             Verbosity::Error => "  \x1B[31mError\x1B[0m ",
             Verbosity::Warn => "   \x1B[33mWarn\x1B[0m ",
             Verbosity::Info => "   \x1B[36mInfo\x1B[0m ",
+            Verbosity::Debug => "  \x1B[35mDebug\x1B[0m ",
             Verbosity::Trace => "  Trace ",
         };
 
-        let restore_interrupts = unsafe { nd_x86_64::rflags().contains(RFlags::INTERRUPT) };
+        with_console(|console| {
+            let _ = write!(console, "{prefix}{}", record.message);
+            for (key, value) in record.fields {
+                let _ = write!(console, " \x1B[2m{key}=\x1B[0m{value}");
+            }
+            let _ = writeln!(console);
+        });
+    });
 
-        if restore_interrupts {
-            // Prevent interrupts while we are writing, ensuring that
-            // the output is not corrupted.
-            unsafe { nd_x86_64::cli() };
-        }
+    nd_log::trace!("Logger initialized.");
+}
 
-        // SAFETY:
-        //  We're setting the global logger *after* having initialized the serial output port,
-        //  ensuring that the `get_unchecked` function is safe.
-        let mut serial_out = unsafe { SerialOut::get_unchecked() };
+/// Calls `f` with exclusive access to the serial console.
+///
+/// Interrupts are disabled while `f` runs, ensuring that the output is not corrupted.
+///
+/// If the serial port has not been initialized yet, `f` is not called.
+pub fn with_console(f: impl FnOnce(&mut dyn Console)) {
+    with_serial_out(true, f);
+}
 
-        let _ = writeln!(serial_out, "{prefix}{}", record.message);
+/// Calls `f` with access to the serial console, even if it is already in use.
+///
+/// This is meant for the panic handler: if the kernel panicked while logging a message, the
+/// console is never going to be released, and interleaved output is better than no output at all.
+///
+/// If the serial port has not been initialized yet, `f` is not called.
+pub fn with_console_forced(f: impl FnOnce(&mut dyn Console)) {
+    with_serial_out(false, f);
+}
 
-        if restore_interrupts {
-            unsafe { nd_x86_64::sti() };
-        }
-    });
+/// Calls `f` with the serial console and interrupts disabled.
+///
+/// When `wait` is `false`, `f` is called even if the console is already in use.
+fn with_serial_out(wait: bool, f: impl FnOnce(&mut dyn Console)) {
+    if !SERIAL_INITIALIZED.load(Acquire) {
+        return;
+    }
 
-    nd_log::trace!("Logger initialized.");
+    let restore_interrupts = unsafe { nd_x86_64::rflags().contains(RFlags::INTERRUPT) };
+
+    if restore_interrupts {
+        unsafe { nd_x86_64::cli() };
+    }
+
+    let guard = if wait {
+        Some(SERIAL_LOCK.lock())
+    } else {
+        SERIAL_LOCK.try_lock()
+    };
+
+    // SAFETY:
+    //  The serial port has been initialized.
+    let mut serial_out = unsafe { SerialOut::get_unchecked() };
+    f(&mut serial_out);
+
+    drop(guard);
+
+    if restore_interrupts {
+        unsafe { nd_x86_64::sti() };
+    }
 }
 
 /// Represents the output serial port.
@@ -63,6 +114,21 @@ impl SerialOut {
     /// The port that we're using to log.
     pub const COM1: u16 = 0x3F8;
 
+    /// The data register. When the DLAB bit of the line control register is set, this is the low
+    /// byte of the baud rate divisor instead.
+    const DATA: Port<u8> = Port::new(Self::COM1);
+    /// The interrupt enable register. When the DLAB bit of the line control register is set,
+    /// this is the high byte of the baud rate divisor instead.
+    const INTERRUPT_ENABLE: Port<u8> = Port::new(Self::COM1 + 1);
+    /// The FIFO control register.
+    const FIFO_CONTROL: Port<u8> = Port::new(Self::COM1 + 2);
+    /// The line control register.
+    const LINE_CONTROL: Port<u8> = Port::new(Self::COM1 + 3);
+    /// The modem control register.
+    const MODEM_CONTROL: Port<u8> = Port::new(Self::COM1 + 4);
+    /// The line status register.
+    const LINE_STATUS: Port<u8> = Port::new(Self::COM1 + 5);
+
     /// Returns a new [`SerialOut`] instance.
     ///
     /// # Safety
@@ -84,21 +150,21 @@ impl SerialOut {
 
         unsafe {
             // Disable interrupts.
-            outb(Self::COM1 + 1, 0x00);
+            Self::INTERRUPT_ENABLE.write(0x00);
 
             // Set baud rate to 38400 baud.
-            outb(Self::COM1 + 3, 0x80);
-            outb(Self::COM1, 0x03);
-            outb(Self::COM1 + 1, 0x00);
+            Self::LINE_CONTROL.write(0x80);
+            Self::DATA.write(0x03);
+            Self::INTERRUPT_ENABLE.write(0x00);
 
             // Confiture the UART. 8 bits, no parity bit, only one stop bit. This also includes
             // more configuration.
-            outb(Self::COM1 + 3, 0x03);
-            outb(Self::COM1 + 2, 0xC7);
-            outb(Self::COM1 + 4, 0x1E);
+            Self::LINE_CONTROL.write(0x03);
+            Self::FIFO_CONTROL.write(0xC7);
+            Self::MODEM_CONTROL.write(0x1E);
 
             // Normal operation mode.
-            outb(Self::COM1 + 4, 0x0F);
+            Self::MODEM_CONTROL.write(0x0F);
         }
 
         Self { _private: () }
@@ -107,7 +173,7 @@ impl SerialOut {
     /// Returns whether the transmition buffer is currently empty.
     #[inline(always)]
     pub fn is_transmit_empty(self) -> bool {
-        unsafe { inb(Self::COM1 + 5) & 0x20 != 0 }
+        unsafe { Self::LINE_STATUS.read() & 0x20 != 0 }
     }
 
     /// Writes a specific byte to the output port.
@@ -116,16 +182,14 @@ impl SerialOut {
             core::hint::spin_loop();
         }
 
-        unsafe { outb(Self::COM1, byte) };
+        unsafe { Self::DATA.write(byte) };
     }
 }
 
-impl fmt::Write for SerialOut {
-    fn write_str(&mut self, s: &str) -> fmt::Result {
-        for b in s.bytes() {
+impl Console for SerialOut {
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        for &b in bytes {
             self.write_byte(b);
         }
-
-        Ok(())
     }
 }
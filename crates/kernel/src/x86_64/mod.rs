@@ -1,15 +1,29 @@
 mod boot;
 
 mod apic;
+mod cpu;
+mod cpu_features;
+mod delay;
+mod elf;
 mod interrupts;
 mod logger;
 mod paging;
+mod power;
 mod sys_info;
 mod tables;
+mod time;
+
+pub mod timer;
 
 pub use self::apic::*;
+pub use self::cpu::*;
+pub use self::cpu_features::*;
+pub use self::delay::*;
+pub use self::elf::*;
 pub use self::interrupts::*;
 pub use self::logger::*;
 pub use self::paging::*;
+pub use self::power::*;
 pub use self::sys_info::*;
 pub use self::tables::*;
+pub use self::time::*;
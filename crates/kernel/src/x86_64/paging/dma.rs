@@ -0,0 +1,144 @@
+use core::fmt;
+
+use nd_x86_64::{PhysAddr, VirtAddr};
+
+use super::{OutOfPhysicalMemory, PageAllocator, PageAllocatorTok};
+
+/// The physical address below which 32-bit DMA engines can access memory.
+pub const DMA32_LIMIT: PhysAddr = 1 << 32;
+
+impl PageAllocator {
+    /// Allocates `count` physically contiguous pages, all located below the physical address
+    /// `below`.
+    ///
+    /// This is meant for buffers that are accessed by devices through *Direct Memory Access*.
+    /// Devices limited to 32-bit addresses should use [`DMA32_LIMIT`].
+    ///
+    /// Note that pages that have been freed are never considered, as they are not guaranteed to
+    /// be contiguous.
+    ///
+    /// The returned pages can be freed one by one with [`PageAllocator::deallocate`].
+    pub fn allocate_dma(
+        &self,
+        count: usize,
+        below: PhysAddr,
+    ) -> Result<PhysAddr, OutOfPhysicalMemory> {
+        self.page_provider()
+            .allocate_contiguous(count, 1, below, &mut |page| unsafe {
+                self.deallocate(page)
+            })
+    }
+}
+
+/// A physically contiguous buffer that can be accessed by devices through *Direct Memory Access*.
+///
+/// The pages of the buffer are returned to the page allocator when it is dropped.
+///
+/// # Caching
+///
+/// The buffer is accessed by the kernel through the *Higher Half Direct Map*, which uses the
+/// default write-back caching policy. This is fine for the devices we care about (PCIe devices
+/// such as xHCI, NVMe or virtio controllers) because x86_64 processors snoop their memory accesses,
+/// keeping the caches coherent.
+///
+/// Devices which do not snoop the caches would require the buffer to be remapped as uncached (or
+/// the relevant cache lines to be flushed with `clflush` before handing the buffer to the device).
+/// This is not supported yet.
+pub struct DmaBuffer {
+    /// The physical address of the first page of the buffer.
+    phys: PhysAddr,
+    /// The number of pages in the buffer.
+    count: usize,
+    /// The allocator that owns the pages of the buffer.
+    page_allocator: PageAllocatorTok,
+}
+
+impl DmaBuffer {
+    /// Allocates a new [`DmaBuffer`] of `count` pages, located below the physical address `below`.
+    ///
+    /// The content of the buffer is zeroed.
+    pub fn new(
+        page_allocator: PageAllocatorTok,
+        count: usize,
+        below: PhysAddr,
+    ) -> Result<Self, OutOfPhysicalMemory> {
+        let phys = page_allocator.allocate_dma(count, below)?;
+        let this = Self {
+            phys,
+            count,
+            page_allocator,
+        };
+
+        // SAFETY:
+        //  We own the pages of the buffer.
+        unsafe { core::ptr::write_bytes(this.virt() as *mut u8, 0, this.len()) };
+
+        Ok(this)
+    }
+
+    /// Returns the physical address of the buffer.
+    ///
+    /// This is the address that should be given to the device.
+    #[inline(always)]
+    pub fn phys(&self) -> PhysAddr {
+        self.phys
+    }
+
+    /// Returns the virtual address of the buffer, within the *Higher Half Direct Map*.
+    #[inline(always)]
+    pub fn virt(&self) -> VirtAddr {
+        self.page_allocator.sys_info().hhdm_offset + self.phys
+    }
+
+    /// Returns the size of the buffer, in bytes.
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.count * 4096
+    }
+
+    /// Returns the content of the buffer.
+    #[inline(always)]
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self.virt() as *mut u8, self.len()) }
+    }
+}
+
+impl Drop for DmaBuffer {
+    fn drop(&mut self) {
+        for i in 0..self.count as u64 {
+            // SAFETY:
+            //  Those pages were allocated by this allocator, and we're the only ones using them.
+            unsafe { self.page_allocator.deallocate(self.phys + i * 4096) };
+        }
+    }
+}
+
+impl fmt::Debug for DmaBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DmaBuffer")
+            .field("phys", &format_args!("{:#x}", self.phys))
+            .field("len", &nd_fmt::HumanBytes(self.len() as u64))
+            .finish()
+    }
+}
+
+#[cfg(all(test, feature = "kernel-test"))]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn dma32_buffer() {
+        let allocator = crate::testing::page_allocator();
+
+        let mut buffer = DmaBuffer::new(allocator, 4, DMA32_LIMIT).unwrap();
+        assert_eq!(buffer.phys() & 0xFFF, 0);
+        assert!(buffer.phys() + buffer.len() as u64 <= DMA32_LIMIT);
+        assert_eq!(
+            buffer.virt(),
+            allocator.sys_info().hhdm_offset + buffer.phys()
+        );
+
+        assert!(buffer.as_mut_slice().iter().all(|&b| b == 0));
+        buffer.as_mut_slice().fill(0xAA);
+    }
+}
@@ -0,0 +1,122 @@
+use core::mem::MaybeUninit;
+use core::ops::Deref;
+
+use nd_x86_64::{PageTable, PageTableEntry, PageTableFlags, PhysAddr};
+
+use crate::x86_64::SysInfoTok;
+
+use super::{OutOfPhysicalMemory, PageProvider};
+
+/// The canonical kernel address space.
+///
+/// Every process shares the higher half of its address space with the kernel. Rather than copying
+/// the kernel mappings into each new address space, the PML4 of every process *references* the
+/// same PDPT tables as the kernel PML4. Any mapping later added by the kernel below those tables
+/// is automatically visible in every address space.
+///
+/// # Invariants
+///
+/// Every higher-half entry (index 256 to 511) of the kernel PML4 is present. This is ensured
+/// when the kernel address space is initialized by allocating an empty PDPT for each entry
+/// that wasn't in use yet, which costs 1 MiB of physical memory at most.
+///
+/// After the first process has been created, the kernel must only ever *add* mappings below
+/// those top-level entries. Removing or replacing a top-level higher-half entry would not be
+/// seen by existing processes.
+///
+/// This type is normally accessed through the [`KernelAddressSpaceTok`] token type.
+pub struct KernelAddressSpace {
+    /// The physical address of the kernel PML4.
+    pml4: PhysAddr,
+
+    /// Proves that the global system info structure has been initialized.
+    sys_info: SysInfoTok,
+}
+
+impl KernelAddressSpace {
+    /// Returns the physical address of the kernel PML4.
+    #[inline(always)]
+    pub fn pml4(&self) -> PhysAddr {
+        self.pml4
+    }
+
+    /// Returns the higher-half entries of the kernel PML4.
+    ///
+    /// Those entries are all present, and may be copied into the PML4 of a new address space to
+    /// share the kernel mappings with it.
+    #[inline(always)]
+    pub fn higher_half_entries(&self) -> &[PageTableEntry] {
+        let pml4 = unsafe { &*((self.sys_info.hhdm_offset + self.pml4) as *const PageTable) };
+        &pml4[256..]
+    }
+}
+
+/// The global kernel address space, protected by [`KernelAddressSpaceTok`].
+static mut KERNEL_ADDRESS_SPACE: MaybeUninit<KernelAddressSpace> = MaybeUninit::uninit();
+
+/// A "token type" proving that the global [`KernelAddressSpace`] has been initialized.
+#[derive(Clone, Copy)]
+pub struct KernelAddressSpaceTok(());
+
+impl KernelAddressSpaceTok {
+    /// Returns an instance of [`KernelAddressSpaceTok`].
+    ///
+    /// # Safety
+    ///
+    /// The [`KernelAddressSpaceTok::initialize`] function must've been called previously.
+    #[inline(always)]
+    pub unsafe fn unchecked() -> Self {
+        Self(())
+    }
+
+    /// Initializes the kernel address space.
+    ///
+    /// `pml4` is the physical address of the page table generated for the kernel. Every
+    /// higher-half entry of that table that is not present yet is filled with an empty PDPT
+    /// allocated from `provider`.
+    ///
+    /// # Safety
+    ///
+    /// This function expects to be called only once.
+    ///
+    /// `pml4` must reference a valid PML4 table, which will be logically owned by the kernel
+    /// address space after this function has been called.
+    pub unsafe fn initialize(
+        sys_info: SysInfoTok,
+        provider: &PageProvider,
+        pml4: PhysAddr,
+    ) -> Result<Self, OutOfPhysicalMemory> {
+        nd_log::trace!("Initializing the kernel address space...");
+
+        let table = unsafe { &mut *((sys_info.hhdm_offset + pml4) as *mut PageTable) };
+
+        for entry in table.iter_mut().skip(256) {
+            if entry.is_present() {
+                continue;
+            }
+
+            let pdpt = provider.allocate()?;
+
+            unsafe { (*((sys_info.hhdm_offset + pdpt) as *mut PageTable)).clear() };
+
+            *entry = PageTableEntry::new(pdpt, PageTableFlags::PRESENT | PageTableFlags::WRITABLE);
+        }
+
+        // SAFETY:
+        //  This function can only be called once, ensuring that we're not overwriting an existing
+        //  instance of the kernel address space.
+        unsafe {
+            KERNEL_ADDRESS_SPACE.write(KernelAddressSpace { pml4, sys_info });
+            Ok(Self::unchecked())
+        }
+    }
+}
+
+impl Deref for KernelAddressSpaceTok {
+    type Target = KernelAddressSpace;
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target {
+        unsafe { KERNEL_ADDRESS_SPACE.assume_init_ref() }
+    }
+}
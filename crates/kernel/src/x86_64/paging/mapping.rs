@@ -1,4 +1,7 @@
-use nd_x86_64::{PageTable, PageTableEntry, PageTableFlags, PhysAddr, VirtAddr};
+use nd_x86_64::{
+    pd_index, pdpt_index, pml4_index, pt_index, PageTable, PageTableEntry, PageTableFlags,
+    PhysAddr, VirtAddr,
+};
 use neodym_sys_common::PageSize;
 
 use super::{OutOfPhysicalMemory, PageProvider};
@@ -7,28 +10,17 @@ const ONE_GIGABYTE: u64 = 512 * TWO_MEGABYTES;
 const TWO_MEGABYTES: u64 = 512 * FOUR_KILOBYTES;
 const FOUR_KILOBYTES: u64 = 4096;
 
-/// Returns the index of the P4 entry for the given virtual address.
-#[inline(always)]
-fn pml4e_index(virt: VirtAddr) -> usize {
-    ((virt >> 39) & 0o777) as usize
-}
-
-/// Returns the index of the P3 entry for the given virtual address.
-#[inline(always)]
-fn pdpte_index(virt: VirtAddr) -> usize {
-    ((virt >> 30) & 0o777) as usize
-}
-
-/// Returns the index of the P2 entry for the given virtual address.
-#[inline(always)]
-fn pde_index(virt: VirtAddr) -> usize {
-    ((virt >> 21) & 0o777) as usize
-}
-
-/// Returns the index of the P1 entry for the given virtual address.
-#[inline(always)]
-fn pte_index(virt: VirtAddr) -> usize {
-    ((virt >> 12) & 0o777) as usize
+/// The size of a huge page.
+// Only used by `OwnedMapper::map_huge`, which has no caller yet.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HugePageSize {
+    /// A 2 MiB page, mapped by a page directory entry.
+    Size2MiB,
+    /// A 1 GiB page, mapped by a page directory pointer table entry.
+    ///
+    /// Those are only available when the CPU supports them.
+    Size1GiB,
 }
 
 /// An error which might occur when mapping a virtual address to a physical address.
@@ -38,6 +30,11 @@ pub enum MappingError {
     OutOfPhysicalMemory,
     /// The requested virtual address is already mapped to some physical page.
     AlreadyMapped,
+    /// The requested memory layout is invalid (e.g. two regions overlap, or a region is not
+    /// in the higher half of the address space).
+    InvalidLayout,
+    /// The requested virtual address is not mapped to any physical page.
+    NotMapped,
 }
 
 impl From<OutOfPhysicalMemory> for MappingError {
@@ -64,17 +61,15 @@ unsafe fn get_directory_entry<'a>(
 
     let table = unsafe { &mut *(map(page_table) as *mut PageTable) };
     let entry = unsafe { table.get_unchecked_mut(index) };
-    if !entry.flags().contains(PageTableFlags::PRESENT) {
+    if !entry.is_present() {
         let phys_addr = provider.allocate()?;
 
-        unsafe {
-            core::ptr::write_bytes(map(phys_addr) as *mut u8, 0, FOUR_KILOBYTES as usize);
-        }
+        unsafe { (*(map(phys_addr) as *mut PageTable)).clear() };
 
         *entry = PageTableEntry::new(phys_addr, flags);
 
         Ok(entry)
-    } else if entry.flags().contains(PageTableFlags::HUGE_PAGE) {
+    } else if entry.is_huge() {
         Err(MappingError::AlreadyMapped)
     } else {
         Ok(entry)
@@ -97,7 +92,7 @@ unsafe fn get_page_entry<'a>(
 
     let table = unsafe { &mut *(map(page_table) as *mut PageTable) };
     let entry = unsafe { table.get_unchecked_mut(index) };
-    if entry.flags().contains(PageTableFlags::PRESENT) {
+    if entry.is_present() {
         Err(MappingError::AlreadyMapped)
     } else {
         Ok(entry)
@@ -118,12 +113,16 @@ pub fn map_1g(
     parent_flags: PageTableFlags,
     flags: PageTableFlags,
 ) -> Result<(), MappingError> {
-    debug_assert!(virt_addr % ONE_GIGABYTE == 0);
-    debug_assert!(phys_addr % ONE_GIGABYTE == 0);
+    nd_log::kassert!(
+        virt_addr % ONE_GIGABYTE == 0 && phys_addr % ONE_GIGABYTE == 0,
+        "cannot map {:#x} to {:#x}: addresses must be aligned to 1 GiB",
+        virt_addr,
+        phys_addr,
+    );
 
     let pml4e =
-        unsafe { get_directory_entry(p4, map, provider, pml4e_index(virt_addr), parent_flags)? };
-    let pdpte = unsafe { get_page_entry(pml4e.addr(), map, pdpte_index(virt_addr))? };
+        unsafe { get_directory_entry(p4, map, provider, pml4_index(virt_addr), parent_flags)? };
+    let pdpte = unsafe { get_page_entry(pml4e.addr(), map, pdpt_index(virt_addr))? };
 
     *pdpte = PageTableEntry::new(phys_addr, flags | PageTableFlags::HUGE_PAGE);
 
@@ -144,21 +143,25 @@ pub fn map_2m(
     parent_flags: PageTableFlags,
     flags: PageTableFlags,
 ) -> Result<(), MappingError> {
-    debug_assert!(virt_addr % TWO_MEGABYTES == 0);
-    debug_assert!(phys_addr % TWO_MEGABYTES == 0);
+    nd_log::kassert!(
+        virt_addr % TWO_MEGABYTES == 0 && phys_addr % TWO_MEGABYTES == 0,
+        "cannot map {:#x} to {:#x}: addresses must be aligned to 2 MiB",
+        virt_addr,
+        phys_addr,
+    );
 
     let pml4e =
-        unsafe { get_directory_entry(l4, map, provider, pml4e_index(virt_addr), parent_flags)? };
+        unsafe { get_directory_entry(l4, map, provider, pml4_index(virt_addr), parent_flags)? };
     let pdpte = unsafe {
         get_directory_entry(
             pml4e.addr(),
             map,
             provider,
-            pdpte_index(virt_addr),
+            pdpt_index(virt_addr),
             parent_flags,
         )?
     };
-    let pde = unsafe { get_page_entry(pdpte.addr(), map, pde_index(virt_addr))? };
+    let pde = unsafe { get_page_entry(pdpte.addr(), map, pd_index(virt_addr))? };
 
     *pde = PageTableEntry::new(phys_addr, flags | PageTableFlags::HUGE_PAGE);
 
@@ -179,17 +182,21 @@ pub fn map_4k(
     parent_flags: PageTableFlags,
     flags: PageTableFlags,
 ) -> Result<(), MappingError> {
-    debug_assert!(virt_addr % FOUR_KILOBYTES == 0);
-    debug_assert!(phys_addr % FOUR_KILOBYTES == 0);
+    nd_log::kassert!(
+        virt_addr % FOUR_KILOBYTES == 0 && phys_addr % FOUR_KILOBYTES == 0,
+        "cannot map {:#x} to {:#x}: addresses must be aligned to 4 KiB",
+        virt_addr,
+        phys_addr,
+    );
 
     let pml4e =
-        unsafe { get_directory_entry(pml4, map, provider, pml4e_index(virt_addr), parent_flags)? };
+        unsafe { get_directory_entry(pml4, map, provider, pml4_index(virt_addr), parent_flags)? };
     let pdpte = unsafe {
         get_directory_entry(
             pml4e.addr(),
             map,
             provider,
-            pdpte_index(virt_addr),
+            pdpt_index(virt_addr),
             parent_flags,
         )?
     };
@@ -198,11 +205,11 @@ pub fn map_4k(
             pdpte.addr(),
             map,
             provider,
-            pde_index(virt_addr),
+            pd_index(virt_addr),
             parent_flags,
         )?
     };
-    let pte = unsafe { get_page_entry(pde.addr(), map, pte_index(virt_addr))? };
+    let pte = unsafe { get_page_entry(pde.addr(), map, pt_index(virt_addr))? };
 
     *pte = PageTableEntry::new(phys_addr, flags);
 
@@ -210,6 +217,10 @@ pub fn map_4k(
 }
 
 /// Maps the provided physical addresses to the provided virtual addresses.
+///
+/// The largest possible pages are used, as long as both the virtual and the physical addresses
+/// are suitably aligned. 1 GiB pages are only used when `supports_1gib` is set; otherwise, 2 MiB
+/// pages are used instead.
 #[allow(clippy::too_many_arguments)]
 pub fn map_range(
     l4: PhysAddr,
@@ -220,15 +231,20 @@ pub fn map_range(
     mut amount: u64,
     parent_flags: PageTableFlags,
     flags: PageTableFlags,
+    supports_1gib: bool,
 ) -> Result<(), MappingError> {
+    let can_use = |virt_addr: VirtAddr, phys_addr: PhysAddr, amount: u64, size: u64| {
+        amount >= size && virt_addr % size == 0 && phys_addr % size == 0
+    };
+
     while amount != 0 {
-        if amount >= ONE_GIGABYTE {
+        if supports_1gib && can_use(virt_addr, phys_addr, amount, ONE_GIGABYTE) {
             map_1g(l4, provider, map, virt_addr, phys_addr, parent_flags, flags)?;
 
             amount -= ONE_GIGABYTE;
             virt_addr += ONE_GIGABYTE;
             phys_addr += ONE_GIGABYTE;
-        } else if amount >= TWO_MEGABYTES {
+        } else if can_use(virt_addr, phys_addr, amount, TWO_MEGABYTES) {
             map_2m(l4, provider, map, virt_addr, phys_addr, parent_flags, flags)?;
 
             amount -= TWO_MEGABYTES;
@@ -246,14 +262,40 @@ pub fn map_range(
     Ok(())
 }
 
+/// The first virtual address of the higher half of the address space.
+const HIGHER_HALF_START: VirtAddr = 0xFFFF_8000_0000_0000;
+
+/// Checks that the region of `size` bytes starting at `start` is entirely in the higher half of
+/// the address space, and returns its end address.
+fn higher_half_region_end(start: VirtAddr, size: u64) -> Result<VirtAddr, MappingError> {
+    if start < HIGHER_HALF_START {
+        return Err(MappingError::InvalidLayout);
+    }
+
+    // The region may end exactly at the top of the address space.
+    match start.checked_add(size) {
+        Some(end) => Ok(end),
+        None if start.wrapping_add(size) == 0 => Ok(VirtAddr::MAX),
+        None => Err(MappingError::InvalidLayout),
+    }
+}
+
 /// Sets an identiy map for the given L4 page table.
 ///
-/// - Memory from `0x0` to `upper_bound` is mapped at `hhdm_start`.
+/// - Memory from `0x0` to `upper_bound` is mapped at `hhdm_offset`.
 /// - The kernel is mapped at `0xFFFF_FFFF_8000_0000`.
 ///
+/// 1 GiB pages are only used when `supports_1gib` is set.
+///
+/// The page at `kernel_stack_guard` (which must be part of the kernel image) is left unmapped.
+///
 /// # Errors
 ///
-/// In case of error, this function leaks memory as it has no way to free allocated pages.
+/// If the direct map or the kernel is not in the higher half of the address space, or if they
+/// overlap, [`MappingError::InvalidLayout`] is returned before anything is allocated.
+///
+/// In case of any other error, this function leaks memory as it has no way to free allocated
+/// pages.
 ///
 /// # Safety
 ///
@@ -261,6 +303,7 @@ pub fn map_range(
 ///
 /// - This function should probably be called only once?
 /// - The kernel must've been compiled to be mapped at `kernel_virt`.
+#[allow(clippy::too_many_arguments)]
 pub unsafe fn generate_page_table(
     provider: &PageProvider,
     map: &mut dyn FnMut(PhysAddr) -> VirtAddr,
@@ -268,14 +311,27 @@ pub unsafe fn generate_page_table(
     kernel_phys: PhysAddr,
     kernel_virt: VirtAddr,
     kernel_size: u64,
-    hhdm_start: VirtAddr,
+    hhdm_offset: VirtAddr,
+    supports_1gib: bool,
+    kernel_stack_guard: VirtAddr,
 ) -> Result<PhysAddr, MappingError> {
     nd_log::trace!("Setting up virtual memory...");
-    let pml4 = provider.allocate()?;
 
-    unsafe {
-        core::ptr::write_bytes(map(pml4) as *mut u8, 0, FOUR_KILOBYTES as usize);
+    let hhdm_end = higher_half_region_end(hhdm_offset, upper_bound)?;
+    let kernel_end = higher_half_region_end(kernel_virt, kernel_size)?;
+    if hhdm_offset < kernel_end && kernel_virt < hhdm_end {
+        return Err(MappingError::InvalidLayout);
     }
+    if kernel_stack_guard % FOUR_KILOBYTES != 0
+        || kernel_stack_guard < kernel_virt
+        || kernel_stack_guard + FOUR_KILOBYTES > kernel_end
+    {
+        return Err(MappingError::InvalidLayout);
+    }
+
+    let pml4 = provider.allocate()?;
+
+    unsafe { (*(map(pml4) as *mut PageTable)).clear() };
 
     //
     // Direct Map
@@ -283,37 +339,116 @@ pub unsafe fn generate_page_table(
     nd_log::trace!(
         "  > Mapping physical memory up to {:#x}, starting at {:#x}...",
         upper_bound,
-        hhdm_start,
+        hhdm_offset,
     );
     map_range(
         pml4,
         provider,
         map,
-        hhdm_start,
+        hhdm_offset,
         0,
         upper_bound,
         PageTableFlags::PRESENT | PageTableFlags::WRITABLE,
         PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::GLOBAL,
+        supports_1gib,
     )?;
 
     //
     // MAP THE KERNEL AT THE REQUESTED ADDRESS
     //
+    // The kernel is mapped in two parts, around the guard page of the kernel stack.
+    //
     nd_log::trace!(
         "  > Mapping kernel from {:#x} to {:#x}...",
         kernel_phys,
         kernel_virt
     );
+    let guard_offset = kernel_stack_guard - kernel_virt;
+    let after_guard_offset = guard_offset + FOUR_KILOBYTES;
     map_range(
         pml4,
         provider,
         map,
         kernel_virt,
         kernel_phys,
-        kernel_size,
+        guard_offset,
+        PageTableFlags::PRESENT | PageTableFlags::WRITABLE,
+        PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::GLOBAL,
+        supports_1gib,
+    )?;
+    map_range(
+        pml4,
+        provider,
+        map,
+        kernel_virt + after_guard_offset,
+        kernel_phys + after_guard_offset,
+        kernel_size.saturating_sub(after_guard_offset),
         PageTableFlags::PRESENT | PageTableFlags::WRITABLE,
         PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::GLOBAL,
+        supports_1gib,
     )?;
 
     Ok(pml4)
 }
+
+#[cfg(all(test, feature = "kernel-test"))]
+mod tests {
+    use super::*;
+
+    /// Maps 1 GiB of memory with [`map_range`] in a new address space, and returns the page
+    /// directory pointer table entry covering it, along with the page directory it references
+    /// (if any).
+    fn map_one_gigabyte(supports_1gib: bool) -> (PageTableEntry, Option<&'static PageTable>) {
+        let allocator = crate::testing::page_allocator();
+        let hhdm_offset = allocator.sys_info().hhdm_offset;
+        let table = |phys: PhysAddr| unsafe { &*((hhdm_offset + phys) as *const PageTable) };
+
+        let virt = 0x40_0000_0000;
+        let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+        let pml4 = allocator.allocate_zeroed().unwrap();
+
+        map_range(
+            pml4,
+            allocator.page_provider(),
+            &mut |phys| hhdm_offset + phys,
+            virt,
+            ONE_GIGABYTE,
+            ONE_GIGABYTE,
+            flags,
+            flags,
+            supports_1gib,
+        )
+        .unwrap();
+
+        let pml4e = table(pml4)[pml4_index(virt)];
+        let pdpte = table(pml4e.addr())[pdpt_index(virt)];
+        let pd = (!pdpte.is_huge()).then(|| table(pdpte.addr()));
+        (pdpte, pd)
+    }
+
+    #[test_case]
+    fn map_range_falls_back_to_2mib_pages() {
+        // Force the fallback, even if the CPU supports 1 GiB pages.
+        let (pdpte, pd) = map_one_gigabyte(false);
+        assert!(pdpte.is_present());
+
+        let pd = pd.expect("a 1 GiB page was used");
+        for (i, pde) in pd.iter().enumerate() {
+            assert!(pde.is_huge());
+            assert_eq!(pde.addr(), ONE_GIGABYTE + i as u64 * TWO_MEGABYTES);
+        }
+    }
+
+    #[test_case]
+    fn map_range_uses_1gib_pages() {
+        let allocator = crate::testing::page_allocator();
+        if !allocator.sys_info().cpu_features.pages_1gib() {
+            return;
+        }
+
+        let (pdpte, pd) = map_one_gigabyte(true);
+        assert!(pd.is_none());
+        assert!(pdpte.is_huge());
+        assert_eq!(pdpte.addr(), ONE_GIGABYTE);
+    }
+}
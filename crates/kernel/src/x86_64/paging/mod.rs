@@ -4,13 +4,21 @@ use nd_x86_64::PhysAddr;
 
 pub mod mapping;
 
+// No device driver allocates DMA buffers yet.
+#[allow(dead_code)]
+mod dma;
+mod kernel_address_space;
 mod owned_mapper;
 mod page_allocator;
 mod page_provider;
+mod pcid;
 
+pub use self::dma::*;
+pub use self::kernel_address_space::*;
 pub use self::owned_mapper::*;
 pub use self::page_allocator::*;
 pub use self::page_provider::*;
+pub use self::pcid::*;
 
 /// The system is out of available physical memory.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -1,12 +1,13 @@
 use core::mem::MaybeUninit;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::Ordering::Relaxed;
 
 use nd_x86_64::{Cr3, Cr3Flags, PageTable, PageTableFlags, PhysAddr, VirtAddr};
-use neodym_sys_common::PageSize;
 
 use crate::x86_64::SysInfoTok;
 
-use super::mapping::MappingError;
-use super::{OutOfPhysicalMemory, PageAllocatorTok};
+use super::mapping::{HugePageSize, MappingError};
+use super::{KernelAddressSpaceTok, OutOfPhysicalMemory, PageAllocatorTok, Pcid};
 
 /// The bit to enable to indicate that a page is owned by the current process. This means that
 /// the pages used to map in virtual memory should be deallocated when the process is destroyed.
@@ -26,49 +27,115 @@ fn offset_by_hhdm(page: PhysAddr) -> VirtAddr {
     //  SysInfo global structure is not yet initialized.
     let sys_info = unsafe { SysInfoTok::unchecked() };
 
-    sys_info.hhdm_start + page
+    sys_info.hhdm_offset + page
+}
+
+/// Returns a pointer to the page table at the physical address `page`, through the HHDM.
+///
+/// # Safety
+///
+/// Same as [`offset_by_hhdm`].
+#[inline]
+fn leaf_table_ptr(page: PhysAddr) -> *mut PageTable {
+    offset_by_hhdm(page) as *mut PageTable
 }
 
 /// A virtual address space that keeps track of which pages are owned by the current process and
 /// deallocates them when the process is destroyed.
+///
+/// # Process-Context Identifiers
+///
+/// When the CPU supports them, each [`OwnedMapper`] gets its own [`Pcid`], allowing its TLB
+/// entries to survive switching to other address spaces. If the pool of identifiers is
+/// exhausted, the address space is loaded with PCID 0 and the TLB is flushed on every switch.
+///
+/// Because of this, removing a mapping or restricting its permissions requires its TLB entry to
+/// be invalidated explicitly, or a flush to be requested for the next time the address space is
+/// loaded.
 pub struct OwnedMapper {
     pml4: PhysAddr,
     page_allocator: PageAllocatorTok,
+    /// The process-context identifier of the address space, if any.
+    pcid: Option<Pcid>,
+    /// Whether the TLB entries associated with `pcid` must be flushed the next time the address
+    /// space is loaded.
+    needs_flush: AtomicBool,
 }
 
 impl OwnedMapper {
     /// Creates a new [`OwnedMapper`] instance.
     pub fn new(page_allocator: PageAllocatorTok) -> Result<Self, OutOfPhysicalMemory> {
-        let pml4 = page_allocator.allocate()?;
+        let pml4 = page_allocator.allocate_zeroed()?;
 
-        unsafe {
-            core::ptr::write_bytes(
-                (pml4 + page_allocator.sys_info().hhdm_start) as *mut u8,
-                0,
-                0x1000,
-            );
-        }
+        let pcid = if page_allocator.sys_info().cpu_features.pcid() {
+            Pcid::allocate()
+        } else {
+            None
+        };
 
         Ok(Self {
             pml4,
             page_allocator,
+            pcid,
+            // The identifier might have been used by another address space before: its stale
+            // entries must be flushed.
+            needs_flush: AtomicBool::new(true),
         })
     }
 
+    /// Creates a new [`OwnedMapper`] instance for a user process.
+    ///
+    /// The higher half of the created address space references the same tables as the kernel
+    /// address space, meaning that any mapping later added by the kernel will be visible in it.
+    pub fn new_user(
+        page_allocator: PageAllocatorTok,
+        kernel: KernelAddressSpaceTok,
+    ) -> Result<Self, OutOfPhysicalMemory> {
+        let mut this = Self::new(page_allocator)?;
+
+        this.pml4_mut()[256..].copy_from_slice(kernel.higher_half_entries());
+
+        Ok(this)
+    }
+
     /// Returns a reference to the PML4 page table.
     #[inline(always)]
     pub fn pml4_mut(&mut self) -> &mut PageTable {
-        unsafe { &mut *((self.pml4 + self.page_allocator.sys_info().hhdm_start) as *mut PageTable) }
+        unsafe {
+            &mut *((self.pml4 + self.page_allocator.sys_info().hhdm_offset) as *mut PageTable)
+        }
+    }
+
+    /// Translates `virt` into the physical address it is mapped to in this address space.
+    ///
+    /// Huge pages are supported. If `virt` is not mapped, [`None`] is returned.
+    // Nothing inspects user address spaces yet.
+    #[allow(dead_code)]
+    pub fn translate(&self, virt: VirtAddr) -> Option<PhysAddr> {
+        nd_x86_64::translate(self.pml4, virt, |phys| unsafe {
+            &*(offset_by_hhdm(phys) as *const PageTable)
+        })
     }
 
     /// Loads this address space into the CPU.
     ///
+    /// If the address space has a [`Pcid`], its TLB entries are kept unless a flush has been
+    /// requested.
+    ///
     /// # Safety
     ///
     /// Very unsafe, yes.
     #[inline(always)]
     pub unsafe fn switch(&self) {
-        unsafe { nd_x86_64::set_cr3(Cr3::new(self.pml4, Cr3Flags::empty())) };
+        let cr3 = match &self.pcid {
+            Some(pcid) => {
+                let no_flush = !self.needs_flush.swap(false, Relaxed);
+                Cr3::new_with_pcid(self.pml4, pcid.get(), no_flush)
+            }
+            None => Cr3::new(self.pml4, Cr3Flags::empty()),
+        };
+
+        unsafe { nd_x86_64::set_cr3(cr3) };
     }
 
     /// Allocates a new page and maps it into the current address space.
@@ -93,6 +160,131 @@ impl OwnedMapper {
         Ok(phys)
     }
 
+    /// Maps `size` bytes of physical memory starting at `phys` to the virtual address `virt`.
+    ///
+    /// The largest possible pages are used (1 GiB pages if the CPU supports them, then 2 MiB
+    /// pages), falling back to 4 KiB pages when the addresses or the remaining size are not
+    /// suitably aligned. This makes it possible to map large regions (such as the direct map of
+    /// the physical memory) without creating millions of entries.
+    ///
+    /// The mapped physical memory is *not* owned by the address space, and won't be deallocated
+    /// with it. Only the page tables created along the way are.
+    // Nothing maps physical memory into user address spaces yet.
+    #[allow(dead_code)]
+    pub fn map_range(
+        &mut self,
+        virt: VirtAddr,
+        phys: PhysAddr,
+        size: u64,
+        parent_flags: PageTableFlags,
+        flags: PageTableFlags,
+    ) -> Result<(), MappingError> {
+        let supports_1gib = self.page_allocator.sys_info().cpu_features.pages_1gib();
+
+        crate::x86_64::mapping::map_range(
+            self.pml4,
+            self.page_allocator.page_provider(),
+            &mut offset_by_hhdm,
+            virt,
+            phys,
+            size,
+            parent_flags | OWNED,
+            flags,
+            supports_1gib,
+        )
+    }
+
+    /// Maps a single huge page of physical memory starting at `phys` to the virtual address
+    /// `virt`.
+    ///
+    /// Both addresses must be aligned to `size`. 1 GiB pages may only be used if the CPU
+    /// supports them.
+    ///
+    /// Like with [`OwnedMapper::map_range`], the mapped physical memory is *not* owned by the
+    /// address space.
+    // Nothing maps device memory into user address spaces yet.
+    #[allow(dead_code)]
+    pub fn map_huge(
+        &mut self,
+        virt: VirtAddr,
+        phys: PhysAddr,
+        size: HugePageSize,
+        parent_flags: PageTableFlags,
+        flags: PageTableFlags,
+    ) -> Result<(), MappingError> {
+        nd_log::kassert!(
+            size != HugePageSize::Size1GiB
+                || self.page_allocator.sys_info().cpu_features.pages_1gib(),
+            "1 GiB pages are not supported by the CPU",
+        );
+
+        let map = match size {
+            HugePageSize::Size2MiB => crate::x86_64::mapping::map_2m,
+            HugePageSize::Size1GiB => crate::x86_64::mapping::map_1g,
+        };
+
+        map(
+            self.pml4,
+            self.page_allocator.page_provider(),
+            &mut offset_by_hhdm,
+            virt,
+            phys,
+            parent_flags | OWNED,
+            flags,
+        )
+    }
+
+    /// Changes the flags of the `count` pages mapped starting at `virt`.
+    ///
+    /// The physical address of each page is preserved, as well as whether it is owned by the
+    /// address space. The TLB entries of the modified pages are invalidated.
+    ///
+    /// If part of the range is mapped using huge pages, the flags of the whole huge page are
+    /// changed.
+    ///
+    /// # Errors
+    ///
+    /// If any page of the range is not mapped, [`MappingError::NotMapped`] is returned and no
+    /// page is modified.
+    // The ELF loader writes segments through the HHDM, so they are mapped with their final flags
+    // from the start and never need to be protected afterwards.
+    #[allow(dead_code)]
+    pub fn protect(
+        &mut self,
+        virt: VirtAddr,
+        count: u64,
+        flags: PageTableFlags,
+    ) -> Result<(), MappingError> {
+        let pages = (0..count).map(|i| virt + i * 0x1000);
+        // SAFETY:
+        //  The page tables of the address space are accessible through the HHDM, and no other
+        //  reference to them exists while `self` is borrowed mutably.
+        let leaf_entry =
+            |page| unsafe { nd_x86_64::leaf_entry_mut(self.pml4, page, leaf_table_ptr) };
+
+        if pages.clone().any(|page| leaf_entry(page).is_none()) {
+            return Err(MappingError::NotMapped);
+        }
+
+        for page in pages {
+            let entry = leaf_entry(page).expect("the range has been checked to be mapped");
+            let kept = entry.flags() & (OWNED | PageTableFlags::HUGE_PAGE);
+            entry.set_flags(flags | kept);
+        }
+
+        if nd_x86_64::cr3().addr() == self.pml4 {
+            // SAFETY:
+            //  Invalidating TLB entries is always safe.
+            unsafe { nd_x86_64::flush_range(virt, count) };
+        } else {
+            // The address space is not loaded. Its TLB entries may still be cached if it has a
+            // process-context identifier.
+            self.needs_flush.store(true, Relaxed);
+        }
+
+        Ok(())
+    }
+
     /// Allocates physical pages and calls the provided callback with a mutable slice of
     /// [`MaybeUninit<u8>`]s.
     ///
@@ -122,7 +314,7 @@ impl OwnedMapper {
         for _ in 0..count {
             let phys = self.allocate_mapping(virt, parent_flags, flags)?;
 
-            let in_kernel_addr_space = self.page_allocator.sys_info().hhdm_start + phys;
+            let in_kernel_addr_space = self.page_allocator.sys_info().hhdm_offset + phys;
             let in_kernel_addr_space = unsafe {
                 core::slice::from_raw_parts_mut(
                     in_kernel_addr_space as *mut MaybeUninit<u8>,
@@ -138,27 +330,6 @@ impl OwnedMapper {
         Ok(())
     }
 
-    /// Loads the provided data into the address space at the provided virtual address.
-    pub fn load(
-        &mut self,
-        virt: VirtAddr,
-        mut data: &[u8],
-        flags: PageTableFlags,
-        parent_flags: PageTableFlags,
-    ) -> Result<(), MappingError> {
-        let count = (data.len() as u64 + 0xFFF) / 0x1000;
-        self.load_with(virt, count, flags, parent_flags, |page| unsafe {
-            let to_copy = if data.len() > 0x1000 {
-                0x1000
-            } else {
-                data.len()
-            };
-
-            core::ptr::copy_nonoverlapping(data.as_ptr(), page.as_mut_ptr() as *mut u8, to_copy);
-            data = data.get_unchecked(to_copy..);
-        })
-    }
-
     /// Loads the requested number of pages into the address space at the provided virtual address.
     pub fn load_uninit(
         &mut self,
@@ -170,3 +341,103 @@ impl OwnedMapper {
         self.load_with(virt, count, flags, parent_flags, |_| ())
     }
 }
+
+#[cfg(all(test, feature = "kernel-test"))]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn protect() {
+        let allocator = crate::testing::page_allocator();
+        let mut mapper = OwnedMapper::new(allocator).unwrap();
+
+        let virt = 0x40_0000_0000;
+        let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+        mapper.load_uninit(virt, 2, flags, flags).unwrap();
+        let phys = mapper.translate(virt).unwrap();
+
+        let flags_of = |mapper: &OwnedMapper, page| {
+            unsafe { nd_x86_64::leaf_entry_mut(mapper.pml4, page, leaf_table_ptr) }
+                .unwrap()
+                .flags()
+        };
+
+        // The third page is not mapped: nothing is modified.
+        assert!(matches!(
+            mapper.protect(virt, 3, PageTableFlags::PRESENT),
+            Err(MappingError::NotMapped)
+        ));
+        assert!(flags_of(&mapper, virt).contains(PageTableFlags::WRITABLE));
+
+        mapper.protect(virt, 2, PageTableFlags::PRESENT).unwrap();
+
+        for page in [virt, virt + 0x1000] {
+            let flags = flags_of(&mapper, page);
+            assert!(!flags.contains(PageTableFlags::WRITABLE));
+            assert!(flags.contains(OWNED));
+        }
+        assert_eq!(mapper.translate(virt), Some(phys));
+    }
+
+    #[test_case]
+    fn map_range() {
+        let allocator = crate::testing::page_allocator();
+        let mut mapper = OwnedMapper::new(allocator).unwrap();
+
+        // One 4 KiB page, one 2 MiB page, then one 4 KiB page again.
+        let virt = 0x40_001F_F000;
+        let phys = 0x1F_F000;
+        let size = 0x20_2000;
+        let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+        mapper.map_range(virt, phys, size, flags, flags).unwrap();
+
+        for offset in [0, 0xFFF, 0x1000, 0x10_0123, 0x20_0FFF, 0x20_1000, 0x20_1FFF] {
+            assert_eq!(mapper.translate(virt + offset), Some(phys + offset));
+        }
+        assert_eq!(mapper.translate(virt - 1), None);
+        assert_eq!(mapper.translate(virt + size), None);
+
+        let is_huge = |page| {
+            unsafe { nd_x86_64::leaf_entry_mut(mapper.pml4, page, leaf_table_ptr) }
+                .unwrap()
+                .is_huge()
+        };
+        assert!(!is_huge(virt));
+        assert!(is_huge(virt + 0x1000));
+        assert!(!is_huge(virt + 0x20_1000));
+    }
+
+    #[test_case]
+    fn map_huge() {
+        let allocator = crate::testing::page_allocator();
+        let mut mapper = OwnedMapper::new(allocator).unwrap();
+
+        let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+
+        let virt = 0x40_0000_0000;
+        let phys = 0x4000_0000;
+        mapper
+            .map_huge(virt, phys, HugePageSize::Size2MiB, flags, flags)
+            .unwrap();
+
+        // Every address of the huge page translates with the same offset.
+        assert_eq!(mapper.translate(virt), Some(phys));
+        assert_eq!(mapper.translate(virt + 0x1F_F123), Some(phys + 0x1F_F123));
+        assert_eq!(mapper.translate(virt + 0x20_0000), None);
+        assert!(matches!(
+            mapper.map_huge(virt, phys, HugePageSize::Size2MiB, flags, flags),
+            Err(MappingError::AlreadyMapped)
+        ));
+
+        if allocator.sys_info().cpu_features.pages_1gib() {
+            let virt = 0x80_0000_0000;
+            mapper
+                .map_huge(virt, phys, HugePageSize::Size1GiB, flags, flags)
+                .unwrap();
+            assert_eq!(
+                mapper.translate(virt + 0x3FFF_F123),
+                Some(phys + 0x3FFF_F123)
+            );
+        }
+    }
+}
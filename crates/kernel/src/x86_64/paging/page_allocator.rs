@@ -1,41 +1,99 @@
-use core::mem::{size_of, MaybeUninit};
+use core::mem::MaybeUninit;
 use core::ops::Deref;
-use core::sync::atomic::AtomicPtr;
-use core::sync::atomic::Ordering::{Acquire, Release};
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering::{Acquire, Relaxed, Release};
 
-use nd_array::Vec;
-use nd_spin::Mutex;
 use nd_x86_64::PhysAddr;
 
-use super::{OutOfPhysicalMemory, PageProvider};
+use super::{MemorySegment, OutOfPhysicalMemory, PageProvider};
 use crate::x86_64::SysInfoTok;
 
-/// A node that's part of the free page list.
+/// The head of the free page stack.
 ///
-/// This is a linked list of free pages that can be used to allocate pages.
-struct FreePageListNode {
-    /// The next node in the list.
-    next: AtomicPtr<FreePageListNode>,
-    /// The pages that are available for allocation.
-    pages: Mutex<Vec<PhysAddr, { Self::MAX_PAGES }>>,
-}
+/// # Representation
+///
+/// Free pages are kept in a [Treiber stack](https://en.wikipedia.org/wiki/Treiber_stack): each
+/// free page stores the frame number of the next free page in its first 8 bytes.
+///
+/// The head of the stack packs two values in a single 64-bit word, allowing it to be updated with
+/// a single compare-and-swap:
+///
+/// - The lower 40 bits store the frame number of the page at the top of the stack (that is, its
+///   physical address divided by 4096). Physical addresses are at most 52 bits wide on x86_64,
+///   so this is always enough.
+///
+/// - The upper 24 bits store a tag which is incremented on every modification of the head.
+///
+/// # The ABA Problem
+///
+/// Without the tag, the following could happen:
+///
+/// 1. Thread A reads the head `X` and its successor `Y`.
+/// 2. Thread B pops `X`, pops `Y`, and pushes `X` back.
+/// 3. Thread A successfully swaps the head from `X` to `Y`, even though `Y` is in use.
+///
+/// Freed pages *can* be reused while another thread is looking at them, so this cannot be ruled
+/// out. With the tag, the compare-and-swap of thread A fails because the head has been modified
+/// in the meantime. The tag would need to wrap around (2^24 modifications) between the load and
+/// the compare-and-swap of a single thread for the problem to reappear.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct FreeStackHead(u64);
 
-const _: () = assert!(size_of::<FreePageListNode>() == 4096);
+impl FreeStackHead {
+    /// The number of bits used to store the frame number.
+    const FRAME_BITS: u32 = 40;
+    /// A mask over the bits used to store the frame number.
+    const FRAME_MASK: u64 = (1 << Self::FRAME_BITS) - 1;
+    /// The frame number used to indicate that the stack is empty.
+    const NO_FRAME: u64 = Self::FRAME_MASK;
 
-impl FreePageListNode {
-    /// The maximum number of pages that can be stored in a single node.
+    /// The head of an empty stack.
+    pub const EMPTY: Self = Self(Self::NO_FRAME);
+
+    /// Creates a new [`FreeStackHead`] from the frame number of the page at the top of the stack,
+    /// and a tag.
+    #[inline(always)]
+    pub const fn new(frame: u64, tag: u64) -> Self {
+        Self((frame & Self::FRAME_MASK) | (tag << Self::FRAME_BITS))
+    }
+
+    /// Returns the raw frame number stored in this head.
     ///
-    /// The three `usize` fields are used for the `next` pointer, the lock in `Mutex<_>` and the
-    /// `len` in `Vec<_>`.
-    pub const MAX_PAGES: usize = (4096 - size_of::<usize>() * 3) / size_of::<PhysAddr>();
+    /// This might be [`FreeStackHead::NO_FRAME`].
+    #[inline(always)]
+    pub const fn frame(self) -> u64 {
+        self.0 & Self::FRAME_MASK
+    }
 
-    /// Creates a new empty node.
-    pub const fn new() -> Self {
-        Self {
-            next: AtomicPtr::new(core::ptr::null_mut()),
-            pages: Mutex::new(Vec::new()),
+    /// Returns the physical address of the page at the top of the stack, if any.
+    #[inline(always)]
+    pub const fn page(self) -> Option<PhysAddr> {
+        match self.frame() {
+            Self::NO_FRAME => None,
+            frame => Some(frame << 12),
         }
     }
+
+    /// Returns the tag that should be used by the next version of the head.
+    #[inline(always)]
+    pub const fn next_tag(self) -> u64 {
+        (self.0 >> Self::FRAME_BITS).wrapping_add(1)
+    }
+}
+
+/// Statistics about the physical memory managed by a [`PageAllocator`].
+///
+/// All values are expressed in pages. The values are computed from several atomic counters
+/// without locking, so they might be slightly inconsistent when other threads are allocating
+/// memory at the same time.
+#[derive(Debug, Clone, Copy)]
+pub struct MemStats {
+    /// The total number of pages managed by the allocator.
+    pub total: u64,
+    /// The number of pages that can still be allocated.
+    pub free: u64,
+    /// The number of pages currently in use.
+    pub used: u64,
 }
 
 /// Contains the state of the physical memory allocator.
@@ -46,8 +104,17 @@ impl FreePageListNode {
 pub struct PageAllocator {
     /// The page provider used to allocate fresh physical pages.
     page_provider: PageProvider,
-    /// The list of free pages.
-    free_pages: AtomicPtr<FreePageListNode>,
+    /// The head of the stack of free pages.
+    ///
+    /// This is a [`FreeStackHead`].
+    free_pages: AtomicU64,
+    /// The number of pages in the free page stack.
+    ///
+    /// This is incremented *before* a page is pushed, and decremented *after* it has been popped,
+    /// ensuring that it never underflows.
+    free_count: AtomicU64,
+    /// The number of pages given to the allocator with [`PageAllocator::add_reclaimed`].
+    reclaimed: AtomicU64,
 
     /// Proves that the global system info structure has been initialized.
     sys_info: SysInfoTok,
@@ -66,69 +133,188 @@ impl PageAllocator {
         &self.page_provider
     }
 
+    /// Returns the "next" link stored within a free page.
+    ///
+    /// # Safety
+    ///
+    /// `page` must be a page that was allocated by this allocator.
+    #[inline(always)]
+    unsafe fn free_page_link(&self, page: PhysAddr) -> &AtomicU64 {
+        unsafe { &*((self.sys_info.hhdm_offset + page) as *const AtomicU64) }
+    }
+
     /// Allocates a new physical page.
     ///
     /// The returned physical address is guaranteed to be page-aligned.
     ///
+    /// The content of the page is unspecified. Notably, pages coming from the free page stack
+    /// still contain whatever their previous owner wrote to them. Pages that may be exposed to
+    /// userspace or used as page tables should be allocated with
+    /// [`PageAllocator::allocate_zeroed`] instead.
+    ///
     /// Note that you can return the page to the allocator by calling [`PageAllocator::deallocate`].
     pub fn allocate(&self) -> Result<PhysAddr, OutOfPhysicalMemory> {
-        // First, attempt to find a page in the free list.
-        let mut cur = &self.free_pages;
-
-        while let Some(node) = unsafe { cur.load(Acquire).as_ref() } {
-            // The free list isn't empty. We might be able to get a page from there.
+        // First, attempt to pop a page from the free page stack.
+        let mut head = FreeStackHead(self.free_pages.load(Acquire));
 
-            // We use `try_lock` to avoid spinning on the lock. If this node is locked, we can just
-            // skip it and try the next one.
+        while let Some(page) = head.page() {
+            // SAFETY:
+            //  Only pages allocated by this allocator are ever pushed onto the stack.
             //
-            // FIXME:
-            //  once a page is empty (and `pages.pop()` fails), it remains in the linked list.
-            //  I'm not sure how to safely remove it from the list without a lock.
-            if let Some(page) = node.pages.try_lock().and_then(|mut pages| pages.pop()) {
-                return Ok(page);
-            }
+            //  Another thread might have popped this page in the meantime and started writing to
+            //  it. In that case, the value we read here is garbage, but the compare-and-swap below
+            //  will fail because the tag of the head has changed.
+            let next = unsafe { self.free_page_link(page).load(Relaxed) };
+            let new_head = FreeStackHead::new(next, head.next_tag());
 
-            cur = &node.next;
+            match self
+                .free_pages
+                .compare_exchange_weak(head.0, new_head.0, Acquire, Acquire)
+            {
+                Ok(_) => {
+                    self.free_count.fetch_sub(1, Relaxed);
+                    return Ok(page);
+                }
+                Err(actual) => head = FreeStackHead(actual),
+            }
         }
 
-        // NOTE:
-        //  It is possible to get here while some memory is still available in the free list. This
-        //  can occur because of the `try_lock` above. This is *very* unlikely to happen. The more
-        //  free pages there are, the more likely we are to acquire a lock. If there are few free
-        //  pages, then either we're out of memory (or close to it), or we still have a lot of
-        //  memory available in the usable segments.
-
+        // The free page stack is empty, we need to take a fresh page from the page provider.
         self.page_provider.allocate()
     }
 
+    /// Allocates a new physical page and fills it with zeros.
+    ///
+    /// See [`PageAllocator::allocate`].
+    pub fn allocate_zeroed(&self) -> Result<PhysAddr, OutOfPhysicalMemory> {
+        let page = self.allocate()?;
+
+        // SAFETY:
+        //  The page has just been allocated, we own it. It is mapped in the HHDM.
+        unsafe { core::ptr::write_bytes((self.sys_info.hhdm_offset + page) as *mut u8, 0, 4096) };
+
+        Ok(page)
+    }
+
     /// Deallocates a physical address.
     ///
     /// # Safety
     ///
     /// The given address must have been allocated by this allocator.
     pub unsafe fn deallocate(&self, addr: PhysAddr) {
-        // Find a node in the free list that can contain the new free page.
-        let mut cur = &self.free_pages;
-
-        while let Some(node) = unsafe { cur.load(Acquire).as_ref() } {
-            // We use `try_lock` to avoid spinning on the lock. If this node is locked, we can just
-            // skip it and try the next one.
-            if let Some(mut pages) = node.pages.try_lock() {
-                if pages.push(addr).is_ok() {
-                    return; // success
-                }
+        nd_log::kassert!(addr & 0xFFF == 0, "deallocated pages must be page-aligned");
+
+        // SAFETY:
+        //  The caller must provide a page that was allocated by us. We're the owner of that page
+        //  now.
+        let link = unsafe { self.free_page_link(addr) };
+
+        self.free_count.fetch_add(1, Relaxed);
+
+        let mut head = FreeStackHead(self.free_pages.load(Relaxed));
+
+        loop {
+            link.store(head.frame(), Relaxed);
+            let new_head = FreeStackHead::new(addr >> 12, head.next_tag());
+
+            match self
+                .free_pages
+                .compare_exchange_weak(head.0, new_head.0, Release, Relaxed)
+            {
+                Ok(_) => break,
+                Err(actual) => head = FreeStackHead(actual),
             }
+        }
+    }
+
+    /// Returns statistics about the physical memory managed by this allocator.
+    pub fn stats(&self) -> MemStats {
+        let total = self.page_provider.total_pages() + self.reclaimed.load(Relaxed);
+        let free = self.page_provider.remaining_pages() + self.free_count.load(Relaxed);
+
+        MemStats {
+            total,
+            free,
+            used: total.saturating_sub(free),
+        }
+    }
+
+    /// Allocates `count` physically contiguous pages. The first page is aligned to `align_pages`
+    /// pages, which must be a power of two.
+    ///
+    /// The free page stack cannot provide contiguous runs, so the pages are always taken from
+    /// the page provider. Pages that had to be skipped to align the run are pushed to the free
+    /// page stack.
+    ///
+    /// The returned pages can be freed with [`PageAllocator::deallocate_contiguous`], or one by
+    /// one with [`PageAllocator::deallocate`].
+    // Nothing in the kernel needs more than one page at a time yet.
+    #[allow(dead_code)]
+    pub fn allocate_contiguous(
+        &self,
+        count: usize,
+        align_pages: usize,
+    ) -> Result<PhysAddr, OutOfPhysicalMemory> {
+        self.page_provider.allocate_contiguous(
+            count,
+            align_pages,
+            PhysAddr::MAX,
+            &mut |page| unsafe { self.deallocate(page) },
+        )
+    }
 
-            cur = &node.next;
+    /// Deallocates `count` physically contiguous pages starting at `base`.
+    ///
+    /// The pages are pushed to the free page stack individually. They are not guaranteed to be
+    /// returned together by a later call to [`PageAllocator::allocate_contiguous`].
+    ///
+    /// # Safety
+    ///
+    /// The `count` pages starting at `base` must have been allocated by this allocator.
+    #[allow(dead_code)]
+    pub unsafe fn deallocate_contiguous(&self, base: PhysAddr, count: usize) {
+        for i in 0..count as u64 {
+            // SAFETY:
+            //  The caller guarantees that the pages were allocated by us.
+            unsafe { self.deallocate(base + i * 4096) };
         }
+    }
+
+    /// Gives the pages of `segments` to the allocator.
+    ///
+    /// This is used to reclaim the memory used by the bootloader once the kernel is done with it.
+    /// The pages are pushed onto the free page stack.
+    ///
+    /// # Safety
+    ///
+    /// The provided segments must be page-aligned (in base and in length), must not overlap with
+    /// the memory managed by the page provider, and must not be in use anymore. Note that this
+    /// includes `segments` itself: it must not be stored in one of the reclaimed segments.
+    pub unsafe fn add_reclaimed(&self, segments: &[MemorySegment]) {
+        let mut total = 0;
+
+        for segment in segments {
+            nd_log::kassert!(
+                segment.base & 0xFFF == 0 && segment.length & 0xFFF == 0,
+                "reclaimed segments must be page-aligned",
+            );
 
-        // We got to the end of the free list, and we didn't find a node that can store our
-        // new page. We need to allocate a new node. We'll use the deallocated node for this.
-        let page_node_ptr = addr as *mut FreePageListNode; // Identity mapped.
+            for page in (segment.base..segment.base + segment.length).step_by(0x1000) {
+                // SAFETY:
+                //  The caller guarantees that the page is not used anymore. It is now owned by
+                //  the allocator, just like any page it would have allocated.
+                unsafe { self.deallocate(page) };
+            }
+
+            total += segment.length;
+        }
 
-        unsafe { page_node_ptr.write(FreePageListNode::new()) };
+        self.reclaimed.fetch_add(total / 4096, Relaxed);
 
-        cur.store(page_node_ptr, Release);
+        nd_log::info!(
+            "Reclaimed {} of bootloader memory.",
+            nd_fmt::HumanBytes(total)
+        );
     }
 }
 
@@ -184,7 +370,9 @@ impl PageAllocatorTok {
         unsafe {
             PAGE_ALLOCATOR.write(PageAllocator {
                 page_provider,
-                free_pages: AtomicPtr::new(core::ptr::null_mut()),
+                free_pages: AtomicU64::new(FreeStackHead::EMPTY.0),
+                free_count: AtomicU64::new(0),
+                reclaimed: AtomicU64::new(0),
                 sys_info,
             });
             Self::unchecked()
@@ -200,3 +388,115 @@ impl Deref for PageAllocatorTok {
         unsafe { PAGE_ALLOCATOR.assume_init_ref() }
     }
 }
+
+#[cfg(all(test, feature = "kernel-test"))]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn deallocated_pages_are_reused() {
+        let allocator = crate::testing::page_allocator();
+
+        let page = allocator.allocate().unwrap();
+        assert_eq!(page & 0xFFF, 0);
+
+        unsafe { allocator.deallocate(page) };
+        assert_eq!(allocator.allocate().unwrap(), page);
+        unsafe { allocator.deallocate(page) };
+    }
+
+    #[test_case]
+    fn concurrent_free_stack() {
+        use crate::x86_64::timer;
+
+        /// The marker written to the pages allocated by the timer interrupt handler.
+        const INTERRUPT_MARKER: u64 = u64::MAX;
+
+        /// Returns a pointer to a word of `page` that the free stack does not use.
+        fn marker(allocator: PageAllocatorTok, page: PhysAddr) -> *mut u64 {
+            (allocator.sys_info().hhdm_offset + page + 8) as *mut u64
+        }
+
+        // Runs in the timer interrupt handler, possibly in the middle of an allocation or a
+        // deallocation of the test itself.
+        fn from_interrupt() {
+            let allocator = crate::testing::page_allocator();
+
+            let a = allocator.allocate().unwrap();
+            let b = allocator.allocate().unwrap();
+            assert_ne!(a, b);
+
+            unsafe {
+                marker(allocator, a).write_volatile(INTERRUPT_MARKER);
+                marker(allocator, b).write_volatile(INTERRUPT_MARKER);
+                allocator.deallocate(a);
+                allocator.deallocate(b);
+            }
+        }
+
+        let allocator = crate::testing::page_allocator();
+
+        // Make sure that the free stack contains enough pages for both sides.
+        let seed: [PhysAddr; 16] = core::array::from_fn(|_| allocator.allocate().unwrap());
+        for page in seed {
+            unsafe { allocator.deallocate(page) };
+        }
+        let before = allocator.stats();
+
+        timer::set_tick_hook(Some(from_interrupt));
+
+        let end = timer::ticks() + 20;
+        let mut round = 0;
+        while timer::ticks() < end {
+            let held: [PhysAddr; 8] = core::array::from_fn(|_| allocator.allocate().unwrap());
+            for (i, &page) in held.iter().enumerate() {
+                unsafe { marker(allocator, page).write_volatile(round + i as u64) };
+            }
+
+            // A page handed out twice would have been overwritten by its other owner.
+            for (i, &page) in held.iter().enumerate() {
+                assert_eq!(
+                    unsafe { marker(allocator, page).read_volatile() },
+                    round + i as u64
+                );
+                unsafe { allocator.deallocate(page) };
+            }
+
+            round += held.len() as u64;
+        }
+
+        timer::set_tick_hook(None);
+
+        assert_eq!(allocator.stats().free, before.free);
+    }
+
+    #[test_case]
+    fn stats_track_allocations() {
+        let allocator = crate::testing::page_allocator();
+
+        let before = allocator.stats();
+        assert_eq!(before.free + before.used, before.total);
+
+        let page = allocator.allocate().unwrap();
+        assert_eq!(allocator.stats().free, before.free - 1);
+
+        unsafe { allocator.deallocate(page) };
+        assert_eq!(allocator.stats().free, before.free);
+    }
+
+    #[test_case]
+    fn contiguous_allocations_are_aligned() {
+        let allocator = crate::testing::page_allocator();
+
+        // Allocate a single page first, so that the next run is unlikely to be aligned already.
+        let single = allocator.allocate().unwrap();
+
+        for align_pages in [1, 4, 16, 512] {
+            let base = allocator.allocate_contiguous(3, align_pages).unwrap();
+            assert_eq!(base % (align_pages as u64 * 4096), 0);
+            unsafe { allocator.deallocate_contiguous(base, 3) };
+        }
+
+        unsafe { allocator.deallocate(single) };
+    }
+}
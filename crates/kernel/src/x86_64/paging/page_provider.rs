@@ -1,51 +1,45 @@
-use core::fmt;
-use core::sync::atomic::AtomicUsize;
+use core::ops::Range;
+use core::sync::atomic::AtomicU64;
 use core::sync::atomic::Ordering::*;
 
 use nd_x86_64::PhysAddr;
 
 use super::{MemorySegment, OutOfPhysicalMemory};
 
-/// Returns a [`fmt::Debug`] implementation that displays the given number of bytes in a human
-/// readable format.
-fn human_bytes(bytes: u64) -> impl fmt::Display {
-    struct Bytes(u64);
-
-    impl fmt::Display for Bytes {
-        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-            let mut bytes = self.0;
-
-            let mut write_dec =
-                |n: u64, dim: &str| write!(f, "{}.{} {}", n / 1024, ((n % 1024) * 100) / 1024, dim);
-
-            if bytes < 1024 {
-                return write!(f, "{} B", bytes);
-            }
-
-            if bytes < 1024 * 1024 {
-                return write_dec(bytes, "KiB");
-            }
-
-            bytes /= 1024;
-
-            if bytes < 1024 * 1024 {
-                return write_dec(bytes, "MiB");
-            }
-
-            bytes /= 1024;
-
-            if bytes < 1024 * 1024 {
-                return write_dec(bytes, "GiB");
-            }
+/// The allocation state of a segment of a [`PageProvider`].
+///
+/// Pages are handed out from both ends of a segment, and the state packs the two counts in a
+/// single 64-bit word, allowing it to be updated with a single compare-and-swap:
+///
+/// - The lower 32 bits store the number of pages taken from the bottom of the segment, one at a
+///   time, by [`PageProvider::allocate`].
+///
+/// - The upper 32 bits store the number of pages taken from the top of the segment by
+///   [`PageProvider::allocate_contiguous`].
+///
+/// The pages in between are still available. A segment whose state is zero has not been touched.
+#[derive(Clone, Copy)]
+struct SegmentState(u64);
 
-            bytes /= 1024;
+impl SegmentState {
+    /// Creates a new [`SegmentState`] from the number of pages taken from the bottom and from the
+    /// top of the segment.
+    #[inline(always)]
+    pub const fn new(bottom: u64, top: u64) -> Self {
+        Self(bottom | top << 32)
+    }
 
-            // wtf so much memory
-            write_dec(bytes, "TiB")
-        }
+    /// Returns the number of pages taken from the bottom of the segment.
+    #[inline(always)]
+    pub const fn bottom(self) -> u64 {
+        self.0 & 0xFFFF_FFFF
     }
 
-    Bytes(bytes)
+    /// Returns the number of pages taken from the top of the segment.
+    #[inline(always)]
+    pub const fn top(self) -> u64 {
+        self.0 >> 32
+    }
 }
 
 /// Provides a stream of physical pages.
@@ -53,23 +47,49 @@ fn human_bytes(bytes: u64) -> impl fmt::Display {
 /// Note that this type does not provide any way to free those pages.
 pub struct PageProvider {
     segments: nd_array::Vec<MemorySegment, { Self::MAX_SEGMENTS }>,
-    index: AtomicUsize,
+    /// The allocation state of each segment. See [`SegmentState`].
+    ///
+    /// The state at index `i` belongs to the segment at index `i`.
+    states: [AtomicU64; Self::MAX_SEGMENTS],
+
+    /// The ranges that have been passed to [`PageProvider::reserve`].
+    ///
+    /// This is only used to check that reserved pages are never returned.
+    #[cfg(debug_assertions)]
+    reserved: nd_array::Vec<Range<PhysAddr>, { Self::MAX_RESERVED }>,
 }
 
 impl PageProvider {
     /// The maximum number of segments that can be iterated over.
     const MAX_SEGMENTS: usize = 16;
+    /// The maximum number of reserved ranges that are remembered for debugging purposes.
+    #[cfg(debug_assertions)]
+    const MAX_RESERVED: usize = 32;
+    /// The maximum size of a segment, in bytes.
+    ///
+    /// The number of pages of a segment must fit in the 32 bits of a [`SegmentState`] half.
+    const MAX_SEGMENT_LENGTH: u64 = 0xFFFF_FFFF * 4096;
 
     /// Creates a new [`PageIterator`] instance.
     pub fn new(usable: &mut dyn Iterator<Item = MemorySegment>) -> Self {
         let mut segments = nd_array::Vec::<MemorySegment, { Self::MAX_SEGMENTS }>::new();
         let mut pages = 0;
-        for segment in usable.take(segments.capacity()) {
+        for mut segment in usable.take(segments.capacity()) {
+            if segment.length > Self::MAX_SEGMENT_LENGTH {
+                nd_log::warn!(
+                    "Usable memory region too large, {} have been ignored.",
+                    nd_fmt::HumanBytes(segment.length - Self::MAX_SEGMENT_LENGTH),
+                );
+                segment.length = Self::MAX_SEGMENT_LENGTH;
+            }
+
             pages += segment.length / 0x1000;
 
             if let Some(last) = segments.last_mut() {
                 // Attempt to merge the current segment with the last one.
-                if last.base + last.length == segment.base {
+                if last.base + last.length == segment.base
+                    && last.length + segment.length <= Self::MAX_SEGMENT_LENGTH
+                {
                     last.length += segment.length;
                     continue;
                 }
@@ -87,54 +107,249 @@ impl PageProvider {
             "{} pages of usable memory, in {} contiguous segments, {} in total.",
             pages,
             segments.len(),
-            human_bytes(pages * 0x1000)
+            nd_fmt::HumanBytes(pages * 0x1000)
         );
 
         Self {
             segments,
-            index: AtomicUsize::new(0),
+            states: core::array::from_fn(|_| AtomicU64::new(0)),
+            #[cfg(debug_assertions)]
+            reserved: nd_array::Vec::new(),
+        }
+    }
+
+    /// Removes the pages overlapping `range` from the pages that this provider may return.
+    ///
+    /// This is used to protect memory which is still in use from being handed out, such as the
+    /// kernel image or the init program.
+    ///
+    /// Reserving a range in the middle of a segment splits it in two. If there is no room left for
+    /// the new segment, the upper part is dropped (its memory is leaked). The segments remain
+    /// sorted by base address.
+    ///
+    /// # Panics
+    ///
+    /// In debug builds, this function panics if a page has already been allocated.
+    pub fn reserve(&mut self, range: Range<PhysAddr>) {
+        debug_assert!(
+            self.states.iter_mut().all(|state| *state.get_mut() == 0),
+            "pages must be reserved before any allocation",
+        );
+
+        let start = range.start & !0xFFF;
+        let end = (range.end + 0xFFF) & !0xFFF;
+
+        if start >= end {
+            return;
+        }
+
+        #[cfg(debug_assertions)]
+        match self.reserved.last_mut() {
+            Some(last) if last.end == start => last.end = end,
+            _ => {
+                // This list is only used for sanity checks; it's fine to forget some ranges.
+                let _ = self.reserved.push(start..end);
+            }
+        }
+
+        let mut i = 0;
+        while i < self.segments.len() {
+            let segment = self.segments[i];
+            let segment_end = segment.base + segment.length;
+
+            if end <= segment.base || segment_end <= start {
+                i += 1;
+                continue;
+            }
+
+            let below = MemorySegment {
+                base: segment.base,
+                length: start.saturating_sub(segment.base),
+            };
+            let above = MemorySegment {
+                base: end,
+                length: segment_end.saturating_sub(end),
+            };
+
+            match (below.length != 0, above.length != 0) {
+                (false, false) => {
+                    // The whole segment is reserved. The next segment is moved at index `i`, we
+                    // have to check it again.
+                    self.segments.drain(i..=i);
+                    continue;
+                }
+                (true, false) => self.segments[i] = below,
+                (false, true) => self.segments[i] = above,
+                (true, true) => {
+                    self.segments[i] = below;
+
+                    // Insert the upper part right after the lower one, keeping the segments sorted.
+                    if self.segments.push(above).is_ok() {
+                        self.segments[i + 1..].rotate_right(1);
+                        i += 1;
+                    } else {
+                        nd_log::warn!(
+                            "Too many memory segments, {} have been ignored.",
+                            nd_fmt::HumanBytes(above.length),
+                        );
+                    }
+                }
+            }
+
+            i += 1;
         }
     }
 
+    /// Returns whether `page` has been reserved with [`PageProvider::reserve`].
+    #[cfg(debug_assertions)]
+    fn is_reserved(&self, page: PhysAddr) -> bool {
+        self.reserved.iter().any(|r| r.contains(&page))
+    }
+
+    /// Returns an iterator over the segments of this provider, along with their allocation state.
+    #[inline]
+    fn segments(&self) -> impl Iterator<Item = (&MemorySegment, &AtomicU64)> {
+        self.segments.iter().zip(&self.states)
+    }
+
+    /// Returns the total number of pages managed by this provider, including those that have
+    /// already been handed out.
+    pub fn total_pages(&self) -> u64 {
+        self.segments.iter().map(|s| s.length / 4096).sum()
+    }
+
+    /// Returns the number of pages that have not been handed out yet.
+    pub fn remaining_pages(&self) -> u64 {
+        self.segments()
+            .map(|(segment, state)| {
+                let state = SegmentState(state.load(Relaxed));
+                segment.length / 4096 - state.bottom() - state.top()
+            })
+            .sum()
+    }
+
     /// Allocates a single page.
+    ///
+    /// Pages are taken from the bottom of the first segment which still has some.
+    ///
+    /// This executes in O(n), with n being the number of segments.
+    /// This is fine, as we don't expect to have more than `MAX_SEGMENT_COUNT` segments. It will
+    /// usually be 4 to 8 segments.
     pub fn allocate(&self) -> Result<PhysAddr, OutOfPhysicalMemory> {
-        // The index of the page that will be allocated.
-        //
-        // Relaxed ordering is sufficient here because we only care about the order of the
-        // operations on this specific atomic variable. If another threads attempts to allocate
-        // a page, their operation will be ordered with respect to this one, and we don't really
-        // care which happens before or after the other.
-        let mut page_index = self.index.fetch_add(1, Relaxed) as u64;
-
-        // This executes in O(n), with n being the number of segments.
-        // This is fine, as we don't expect to have more than `MAX_SEGMENT_COUNT` segments. It will
-        // usually be 4 to 8 segments.
-        for segment in &self.segments {
+        for (segment, state) in self.segments() {
             let page_count = segment.length / 4096;
 
-            if page_index < page_count {
-                // We found the right segment for the index!
+            // Relaxed ordering is sufficient here because we only care about the order of the
+            // operations on this specific atomic variable. If another threads attempts to allocate
+            // a page, their operation will be ordered with respect to this one, and we don't
+            // really care which happens before or after the other.
+            let mut current = SegmentState(state.load(Relaxed));
+
+            while current.bottom() + current.top() < page_count {
+                let new = SegmentState::new(current.bottom() + 1, current.top());
 
-                return Ok(segment.base + page_index * 4096);
+                match state.compare_exchange_weak(current.0, new.0, Relaxed, Relaxed) {
+                    Ok(_) => {
+                        let page = segment.base + current.bottom() * 4096;
+
+                        #[cfg(debug_assertions)]
+                        debug_assert!(!self.is_reserved(page), "allocated a reserved page");
+
+                        return Ok(page);
+                    }
+                    Err(actual) => current = SegmentState(actual),
+                }
             }
 
-            page_index -= page_count;
             // not in this segment
         }
 
-        // We need to restore the previous index in order to prevent the index from overflowing.
-        // If `next_free` overflows, then used segments will start being allocated again. This is
-        // actually pretty bad, but there's not much we can do about it without using a lock.
-        //
-        // This races with the `fetch_add` above, but if other threads are able to allocate enough
-        // pages to overflow an `usize` by the time we get here, then the system is probably having
-        // bigger issues than this.
-        //
-        // I think locking would actually be fine, but it's so unlikely that this will be an issue
-        // that the lock-free implementation is probably worth it.
-        self.index.store(page_index as usize, Relaxed);
-
         // We're out of memory :(
         Err(OutOfPhysicalMemory)
     }
+
+    /// Allocates `count` physically contiguous pages, all located below the physical address
+    /// `below`. The first page is aligned to `align_pages` pages, which must be a power of two.
+    ///
+    /// The physical address of the first page is returned.
+    ///
+    /// Every segment is searched, starting from the lowest one. The run is taken from the top of
+    /// the remaining pages of a segment when they are all below `below`, leaving the rest of
+    /// the segment to [`PageProvider::allocate`]. Otherwise, it is taken from their bottom.
+    ///
+    /// The pages that separate the run from the remaining pages because of its alignment (less
+    /// than `align_pages`) are passed to `skipped` rather than being leaked, and belong to the
+    /// caller from then on.
+    pub fn allocate_contiguous(
+        &self,
+        count: usize,
+        align_pages: usize,
+        below: PhysAddr,
+        skipped: &mut dyn FnMut(PhysAddr),
+    ) -> Result<PhysAddr, OutOfPhysicalMemory> {
+        nd_log::kassert!(count != 0, "cannot allocate zero pages");
+        nd_log::kassert!(
+            align_pages.is_power_of_two(),
+            "the alignment must be a power of two"
+        );
+
+        let size = count as u64 * 4096;
+        let align = align_pages as u64 * 4096;
+
+        for (segment, state) in self.segments() {
+            let page_count = segment.length / 4096;
+            let mut current = SegmentState(state.load(Relaxed));
+
+            loop {
+                // The remaining pages of the segment.
+                let free_start = segment.base + current.bottom() * 4096;
+                let free_end = segment.base + (page_count - current.top()) * 4096;
+
+                let (base, new, gap) = if free_end <= below {
+                    // Take the run from the top.
+                    let Some(base) = free_end.checked_sub(size).map(|end| end & !(align - 1))
+                    else {
+                        break;
+                    };
+                    if base < free_start {
+                        break;
+                    }
+
+                    let top = page_count - (base - segment.base) / 4096;
+                    let new = SegmentState::new(current.bottom(), top);
+                    (base, new, base + size..free_end)
+                } else {
+                    // Take the run from the bottom.
+                    let Some(base) = free_start.checked_add(align - 1).map(|b| b & !(align - 1))
+                    else {
+                        break;
+                    };
+                    if base.saturating_add(size) > below {
+                        break;
+                    }
+
+                    let bottom = (base - segment.base) / 4096 + count as u64;
+                    let new = SegmentState::new(bottom, current.top());
+                    (base, new, free_start..base)
+                };
+
+                match state.compare_exchange_weak(current.0, new.0, Relaxed, Relaxed) {
+                    Ok(_) => {
+                        gap.step_by(4096).for_each(&mut *skipped);
+
+                        #[cfg(debug_assertions)]
+                        debug_assert!(
+                            (0..count as u64).all(|i| !self.is_reserved(base + i * 4096)),
+                            "allocated a reserved page",
+                        );
+
+                        return Ok(base);
+                    }
+                    Err(actual) => current = SegmentState(actual),
+                }
+            }
+        }
+
+        Err(OutOfPhysicalMemory)
+    }
 }
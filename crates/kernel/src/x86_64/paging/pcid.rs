@@ -0,0 +1,58 @@
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering::{Acquire, Relaxed, Release};
+
+/// The set of process-context identifiers currently in use. Bit `n` is set when PCID `n` is in
+/// use.
+///
+/// PCID 0 is used by the kernel address space (and by address spaces which could not get an
+/// identifier of their own), and is never handed out.
+static PCID_POOL: AtomicU64 = AtomicU64::new(1);
+
+/// A *Process-Context Identifier*, used to tag the TLB entries of an address space.
+///
+/// When the [`Cr4::PCID`](nd_x86_64::Cr4::PCID) flag is set, the TLB entries of an address space
+/// survive switching to another address space, avoiding a full flush on every switch.
+///
+/// Only a small pool of identifiers is available. They are returned to the pool when dropped.
+///
+/// # Reuse
+///
+/// The TLB may still contain entries tagged with an identifier that has been released. The first
+/// time an address space is loaded with a freshly allocated [`Pcid`], those entries *must* be
+/// flushed.
+#[derive(Debug)]
+pub struct Pcid(u16);
+
+impl Pcid {
+    /// Allocates a new process-context identifier.
+    ///
+    /// Returns `None` if the pool is exhausted.
+    pub fn allocate() -> Option<Self> {
+        let mut pool = PCID_POOL.load(Relaxed);
+
+        loop {
+            if pool == u64::MAX {
+                return None;
+            }
+
+            let id = (!pool).trailing_zeros();
+
+            match PCID_POOL.compare_exchange_weak(pool, pool | (1 << id), Acquire, Relaxed) {
+                Ok(_) => return Some(Self(id as u16)),
+                Err(actual) => pool = actual,
+            }
+        }
+    }
+
+    /// Returns the raw value of this identifier.
+    #[inline(always)]
+    pub fn get(&self) -> u16 {
+        self.0
+    }
+}
+
+impl Drop for Pcid {
+    fn drop(&mut self) {
+        PCID_POOL.fetch_and(!(1 << self.0), Release);
+    }
+}
@@ -0,0 +1,99 @@
+//! Rebooting and powering off the machine.
+
+use nd_x86_64::{inb, outb, outw, TablePtr};
+
+/// Resets the machine.
+///
+/// The following methods are attempted in order, moving on to the next one if the machine is
+/// still running shortly after:
+///
+/// 1. The *Reset Control Register* of the chipset (port `0xCF9`).
+/// 2. The reset line of the 8042 keyboard controller (port `0x64`).
+/// 3. A triple fault, caused by raising an exception with an empty *Interrupt Descriptor Table*.
+///
+/// The last method works on any x86 machine.
+// Nothing asks the kernel to reboot yet.
+#[allow(dead_code)]
+pub fn reboot() -> ! {
+    nd_log::info!("Rebooting...");
+
+    unsafe {
+        nd_x86_64::cli();
+
+        // Reset Control Register: request a hard reset (bit 1), then trigger it (bit 2).
+        outb(0xCF9, 0x02);
+        super::udelay(50);
+        outb(0xCF9, 0x06);
+        super::mdelay(100);
+
+        // Pulse the reset line through the 8042 controller, once its input buffer is empty.
+        for _ in 0..0x10000 {
+            if inb(0x64) & 0b10 == 0 {
+                break;
+            }
+            core::hint::spin_loop();
+        }
+        outb(0x64, 0xFE);
+        super::mdelay(100);
+
+        // Triple fault.
+        nd_x86_64::lidt(&TablePtr { limit: 0, base: 0 });
+        nd_x86_64::int3();
+    }
+
+    nd_x86_64::hlt_loop();
+}
+
+/// Powers the machine off.
+///
+/// Without an ACPI driver, the kernel can only rely on the debug exits of emulators:
+///
+/// - QEMU (with the `q35` or `pc` machines, recent versions) powers off when `0x2000` is written
+///   to port `0x604`.
+/// - Bochs and older versions of QEMU do the same with port `0xB004`.
+///
+/// On real hardware, none of those do anything and the CPU is halted instead, leaving the machine
+/// to be powered off manually.
+// Nothing asks the kernel to power off yet.
+#[allow(dead_code)]
+pub fn shutdown() -> ! {
+    nd_log::info!("Shutting down...");
+
+    unsafe {
+        outw(0x604, 0x2000);
+        outw(0xB004, 0x2000);
+    }
+
+    nd_log::warn!("Failed to power off the machine. It is now safe to turn it off.");
+    nd_x86_64::hlt_loop();
+}
+
+/// The I/O port of the `isa-debug-exit` device of QEMU.
+#[cfg(all(test, feature = "kernel-test"))]
+const QEMU_EXIT_PORT: u16 = 0xF4;
+
+/// An exit code which QEMU turns into a successful exit status (`33`) when passed to
+/// [`qemu_exit`].
+#[cfg(all(test, feature = "kernel-test"))]
+pub const QEMU_EXIT_SUCCESS: u32 = 0x10;
+
+/// An exit code which QEMU turns into a failed exit status (`35`) when passed to [`qemu_exit`].
+#[cfg(all(test, feature = "kernel-test"))]
+pub const QEMU_EXIT_FAILURE: u32 = 0x11;
+
+/// Exits QEMU through its `isa-debug-exit` device.
+///
+/// QEMU exits with the status `(code << 1) | 1`, which means that a successful exit status of 0
+/// cannot be produced. See [`QEMU_EXIT_SUCCESS`] and [`QEMU_EXIT_FAILURE`].
+///
+/// The device must be enabled when starting QEMU:
+///
+/// ```txt
+/// -device isa-debug-exit,iobase=0xf4,iosize=0x04
+/// ```
+///
+/// If the device is not present (including on real hardware), this function returns.
+#[cfg(all(test, feature = "kernel-test"))]
+pub fn qemu_exit(code: u32) {
+    unsafe { nd_x86_64::outl(QEMU_EXIT_PORT, code) };
+}
@@ -3,6 +3,8 @@ use core::ops::Deref;
 
 use nd_x86_64::{PhysAddr, VirtAddr};
 
+use super::CpuFeatures;
+
 /// Stores information about the kernel, relevant to the `x86_64` architecture.
 ///
 /// This type is normally accessed through the [`SysInfoTok`] token type.
@@ -14,11 +16,30 @@ pub struct SysInfo {
     pub kernel_virt_end_addr: VirtAddr,
     /// The virtual address of the kernel.
     pub kernel_virt_addr: VirtAddr,
-    /// The start of the higher-half direct map in virtual memory.
-    pub hhdm_start: VirtAddr,
+    /// The offset of the higher-half direct map in virtual memory.
+    ///
+    /// The physical address `p` is mapped at the virtual address `hhdm_offset + p`.
+    pub hhdm_offset: VirtAddr,
+    /// The optional features supported by the CPU.
+    ///
+    /// The boot sequence enables the relevant control bits (such as
+    /// [`Cr4::PCID`](nd_x86_64::Cr4::PCID)) according to this.
+    pub cpu_features: CpuFeatures,
+    /// The frequency of the *Time-Stamp Counter*, in Hertz.
+    ///
+    /// This is the clock source used by [`Instant`](super::Instant).
+    pub tsc_frequency: u64,
 }
 
 impl SysInfo {
+    /// Returns the size of the kernel image, in bytes.
+    ///
+    /// The kernel image is contiguous, both in physical and virtual memory.
+    #[inline(always)]
+    pub fn kernel_size(&self) -> u64 {
+        self.kernel_virt_end_addr - self.kernel_virt_addr
+    }
+
     /// Reads the kernel virtual address from the linker script.
     #[inline(always)]
     pub fn read_kernel_virt_addr() -> VirtAddr {
@@ -38,6 +59,19 @@ impl SysInfo {
 
         unsafe { &__nd_image_end as *const _ as usize as VirtAddr }
     }
+
+    /// Reads the virtual address of the guard page of the kernel stack from the linker script.
+    ///
+    /// This page is never mapped, ensuring that overflowing the kernel stack triggers a page
+    /// fault.
+    #[inline(always)]
+    pub fn read_kernel_stack_guard_addr() -> VirtAddr {
+        extern "C" {
+            static mut __nd_stack_guard: u8;
+        }
+
+        unsafe { &__nd_stack_guard as *const _ as usize as VirtAddr }
+    }
 }
 
 /// The global system info object, protected by [`SysInfoTok`].
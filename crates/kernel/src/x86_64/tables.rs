@@ -4,8 +4,8 @@
 use core::mem::size_of_val;
 
 use nd_x86_64::{
-    DescriptorTable, Efer, GateDescriptor, GateType, Idt, IstIndex, PrivilegeLevel,
-    SegmentDescriptor, SegmentSelector, Star, TablePtr, Tss, VirtAddr,
+    DescriptorTable, Efer, GateType, Idt, IstIndex, PrivilegeLevel, RFlags, SegmentDescriptor,
+    SegmentSelector, Star, TablePtr, Tss, VirtAddr,
 };
 
 /// The global descriptor table that we are going to load. We can't use a simple array because some
@@ -47,8 +47,27 @@ impl Gdt {
     }
 }
 
+/// The size of the kernel stack, in bytes.
+pub const KERNEL_STACK_SIZE: usize = 4096 * 16;
+
+/// The backing storage of the kernel stack.
+#[repr(C, align(4096))]
+pub struct KernelStack([u8; KERNEL_STACK_SIZE]);
+
 /// The stack that will be used by the kernel.
-static mut KERNEL_STACK: [u8; 4096 * 4] = [0u8; 4096 * 4];
+///
+/// This stack is used during boot, and then when interrupts or exceptions occur while the CPU is
+/// running in user mode.
+///
+/// # Guard Page
+///
+/// The stack is placed in its own section, right after a guard page which is left unmapped (see
+/// the linker script of the kernel). Overflowing the stack triggers a page fault instead of
+/// silently overwriting other statics. See [`SysInfo::read_kernel_stack_guard_addr`].
+///
+/// [`SysInfo::read_kernel_stack_guard_addr`]: super::SysInfo::read_kernel_stack_guard_addr
+#[link_section = ".bss.nd_kernel_stack"]
+pub static mut KERNEL_STACK: KernelStack = KernelStack([0u8; KERNEL_STACK_SIZE]);
 
 /// The stack that will be used when a double fault occurs. This is required because a double
 /// fault might occur because of a stack overflow, and in that case, the kernel stack would be
@@ -80,7 +99,7 @@ pub unsafe fn setup_gdt() {
         );
         TSS.set_stack_pointer(
             PrivilegeLevel::Ring0,
-            KERNEL_STACK.as_ptr().add(size_of_val(&KERNEL_STACK)) as usize as VirtAddr,
+            KERNEL_STACK.0.as_ptr().add(KERNEL_STACK_SIZE) as usize as VirtAddr,
         );
 
         GDT.kernel_code = SegmentDescriptor::code(true, PrivilegeLevel::Ring0, false, true);
@@ -122,13 +141,13 @@ pub unsafe fn setup_idt() {
         }
         macro_rules! set_interrupt_handler {
             ($index:expr, $handler:expr) => {
-                IDT[$index] = GateDescriptor::new(
-                    $handler as usize as u64,
+                IDT.set_interrupt_handler(
+                    $index,
+                    $handler,
                     Gdt::KERNEL_CODE,
                     None,
                     GateType::Interrupt,
                     PrivilegeLevel::Ring0,
-                    true,
                 );
             };
         }
@@ -196,8 +215,9 @@ pub unsafe fn setup_idt() {
             super::interrupts::security_exception
         );
 
-        set_interrupt_handler!(32, super::interrupts::apic_timer);
+        set_interrupt_handler!(super::timer::TIMER_VECTOR, super::interrupts::apic_timer);
         set_interrupt_handler!(39, super::interrupts::apic_spurious);
+        set_interrupt_handler!(40, super::interrupts::apic_error);
 
         nd_x86_64::lidt(&IDT.table_ptr());
     }
@@ -206,7 +226,7 @@ pub unsafe fn setup_idt() {
 /// Initializes the necessary registers to make system calls work.
 ///
 /// This includes enabling the extended feature enable register for compatibility between Intel
-/// and AMD processors, setting the STAR and LSTAR registers.
+/// and AMD processors, setting the STAR, LSTAR and FMASK registers.
 ///
 /// # Safety
 ///
@@ -221,5 +241,27 @@ pub unsafe fn setup_system_calls() {
             SegmentSelector::new(1, DescriptorTable::Gdt, PrivilegeLevel::Ring0),
         ));
         nd_x86_64::set_lstar(super::interrupts::handle_syscall as usize as VirtAddr);
+
+        // `syscall` does not switch stacks, meaning that `handle_syscall` starts running on a
+        // stack controlled by userspace. If interrupts were left enabled, an interrupt could
+        // be taken right away, and its handler would run in ring 0 on that stack. The direction
+        // flag is cleared because the kernel assumes it is clear (as required by the ABI), and
+        // the trap flag to prevent userspace from single-stepping into the kernel.
+        nd_x86_64::set_fmask(RFlags::INTERRUPT | RFlags::DIRECTION | RFlags::TRAP);
+    }
+}
+
+#[cfg(all(test, feature = "kernel-test"))]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn loaded_tables() {
+        let idt = nd_x86_64::sidt();
+        let gdt = nd_x86_64::sgdt();
+
+        assert_eq!({ idt.limit } as usize, core::mem::size_of::<Idt>() - 1);
+        assert_eq!({ idt.base }, unsafe { &IDT as *const Idt as VirtAddr });
+        assert_eq!({ gdt.limit } as usize, core::mem::size_of::<Gdt>() - 1);
     }
 }
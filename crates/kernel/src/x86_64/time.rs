@@ -0,0 +1,216 @@
+//! Monotonic time keeping.
+//!
+//! The kernel uses the *Time-Stamp Counter* (TSC) of the CPU as its monotonic clock. Its
+//! frequency is measured once at boot against the *Programmable Interval Timer* (PIT), whose
+//! frequency is fixed, and stored in [`SysInfo::tsc_frequency`](super::SysInfo::tsc_frequency).
+//!
+//! There is no fallback clock source: if the TSC cannot be calibrated, the kernel refuses to boot.
+
+use core::ops::{Add, Sub};
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering::Relaxed;
+use core::time::Duration;
+
+use nd_x86_64::{inb, outb, rdtsc};
+
+use super::SysInfoTok;
+
+/// The frequency of the *Programmable Interval Timer*, in Hertz.
+const PIT_FREQUENCY: u64 = 1_193_182;
+
+/// The TSC is calibrated over `1 / CALIBRATION_DIVISOR` seconds.
+const CALIBRATION_DIVISOR: u64 = 100;
+
+/// The frequency of the *Time-Stamp Counter*, in Hertz, or zero if it has not been calibrated
+/// yet.
+///
+/// This is a copy of [`SysInfo::tsc_frequency`](super::SysInfo::tsc_frequency) which can be read
+/// before the system info structure is initialized.
+static TSC_FREQUENCY: AtomicU64 = AtomicU64::new(0);
+
+/// Returns the frequency of the *Time-Stamp Counter*, in Hertz, if [`calibrate_tsc`] has been
+/// called.
+#[inline(always)]
+pub fn calibrated_tsc_frequency() -> Option<u64> {
+    match TSC_FREQUENCY.load(Relaxed) {
+        0 => None,
+        frequency => Some(frequency),
+    }
+}
+
+/// Prepares channel 2 of the PIT to count down from `count`, without starting it.
+///
+/// Channel 2 is used because its output can be polled through port `0x61` without configuring
+/// any interrupt. The returned value must be passed to [`pit_run`].
+///
+/// # Safety
+///
+/// The PIT and the port `0x61` must not be used by anything else until [`pit_run`] returns.
+unsafe fn pit_prepare(count: u16) -> u8 {
+    unsafe {
+        // Enable the gate of channel 2, and make sure the PC speaker is disabled.
+        let control = inb(0x61) & !0b10;
+        outb(0x61, control & !0b1);
+
+        // Channel 2, lobyte/hibyte access, mode 0 (interrupt on terminal count), binary.
+        outb(0x43, 0b1011_0000);
+        outb(0x42, count as u8);
+        outb(0x42, (count >> 8) as u8);
+
+        control
+    }
+}
+
+/// Starts the countdown prepared by [`pit_prepare`] and spins until it reaches zero.
+///
+/// # Safety
+///
+/// `control` must have been returned by [`pit_prepare`].
+unsafe fn pit_run(control: u8) {
+    unsafe {
+        // Raising the gate starts the countdown.
+        outb(0x61, control | 0b1);
+
+        while inb(0x61) & 0x20 == 0 {
+            core::hint::spin_loop();
+        }
+
+        outb(0x61, control & !0b1);
+    }
+}
+
+/// Spins for `us` microseconds using channel 2 of the PIT.
+///
+/// # Safety
+///
+/// The PIT and the port `0x61` must not be used by anything else while this function runs.
+pub(super) unsafe fn pit_delay(us: u64) {
+    let mut count = (us as u128 * PIT_FREQUENCY as u128 / 1_000_000) as u64;
+
+    while count != 0 {
+        let chunk = count.min(u16::MAX as u64);
+        unsafe { pit_run(pit_prepare(chunk as u16)) };
+        count -= chunk;
+    }
+}
+
+/// Measures the frequency of the *Time-Stamp Counter*, in Hertz.
+///
+/// This is done by counting the number of TSC ticks elapsed while channel 2 of the PIT counts
+/// down from a known value.
+///
+/// The measured frequency is remembered, allowing [`udelay`](super::udelay) to use the TSC from
+/// then on.
+///
+/// If the TSC did not advance during the measurement (which can happen on broken emulators),
+/// [`None`] is returned and the TSC is not used.
+///
+/// # Safety
+///
+/// The PIT and the port `0x61` must not be used by anything else while this function runs.
+pub unsafe fn calibrate_tsc() -> Option<u64> {
+    const COUNT: u64 = PIT_FREQUENCY / CALIBRATION_DIVISOR;
+
+    let frequency = unsafe {
+        let control = pit_prepare(COUNT as u16);
+
+        let start = rdtsc();
+        pit_run(control);
+        let end = rdtsc();
+
+        end.wrapping_sub(start) * CALIBRATION_DIVISOR
+    };
+
+    if frequency == 0 {
+        return None;
+    }
+
+    TSC_FREQUENCY.store(frequency, Relaxed);
+
+    Some(frequency)
+}
+
+/// Converts a number of TSC ticks into a [`Duration`].
+fn ticks_to_duration(ticks: u64) -> Duration {
+    // SAFETY:
+    //  The kernel only measures time after the system info structure has been initialized.
+    let frequency = unsafe { SysInfoTok::unchecked() }.tsc_frequency;
+
+    let nanos = ticks as u128 * 1_000_000_000 / frequency as u128;
+    Duration::new(
+        (nanos / 1_000_000_000) as u64,
+        (nanos % 1_000_000_000) as u32,
+    )
+}
+
+/// Converts a [`Duration`] into a number of TSC ticks, saturating on overflow.
+fn duration_to_ticks(duration: Duration) -> u64 {
+    // SAFETY:
+    //  Same as `ticks_to_duration`.
+    let frequency = unsafe { SysInfoTok::unchecked() }.tsc_frequency;
+
+    let ticks = duration.as_nanos() * frequency as u128 / 1_000_000_000;
+    ticks.try_into().unwrap_or(u64::MAX)
+}
+
+/// A measurement of the monotonic clock of the kernel.
+///
+/// Instants are only meaningful after the system info structure has been initialized, as
+/// converting them to and from [`Duration`]s requires the calibrated frequency of the clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant(u64);
+
+impl Instant {
+    /// Returns the current instant.
+    #[inline(always)]
+    pub fn now() -> Self {
+        Self(rdtsc())
+    }
+
+    /// Returns the amount of time elapsed from `earlier` to `self`, or zero if `earlier` is
+    /// later than `self`.
+    #[inline]
+    pub fn saturating_duration_since(self, earlier: Instant) -> Duration {
+        ticks_to_duration(self.0.saturating_sub(earlier.0))
+    }
+
+    /// Returns the amount of time elapsed since this instant.
+    #[inline]
+    pub fn elapsed(self) -> Duration {
+        Self::now().saturating_duration_since(self)
+    }
+
+    /// Returns the instant `duration` after `self`, or [`None`] if it cannot be represented.
+    #[inline]
+    pub fn checked_add(self, duration: Duration) -> Option<Instant> {
+        self.0.checked_add(duration_to_ticks(duration)).map(Self)
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    #[inline]
+    fn add(self, rhs: Duration) -> Self::Output {
+        self.checked_add(rhs)
+            .expect("overflow when adding a duration to an instant")
+    }
+}
+
+impl Sub<Instant> for Instant {
+    type Output = Duration;
+
+    #[inline]
+    fn sub(self, rhs: Instant) -> Self::Output {
+        self.saturating_duration_since(rhs)
+    }
+}
+
+/// Spins until `duration` has elapsed.
+pub fn busy_wait(duration: Duration) {
+    let deadline = Instant::now() + duration;
+
+    while Instant::now() < deadline {
+        core::hint::spin_loop();
+    }
+}
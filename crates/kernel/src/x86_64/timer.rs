@@ -0,0 +1,150 @@
+//! The periodic tick of the local APIC timer.
+//!
+//! Every tick increments a global counter (the "jiffies"), which is used as a coarse-grained
+//! clock. Precise time measurements should use [`Instant`](super::Instant) instead.
+
+use core::sync::atomic::AtomicU32;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering::Relaxed;
+#[cfg(all(test, feature = "kernel-test"))]
+use core::sync::atomic::{AtomicPtr, Ordering::SeqCst};
+use core::time::Duration;
+
+use nd_apic::{TimerDivisor, TimerMode, XApic};
+
+/// The interrupt vector used by the local APIC timer.
+pub const TIMER_VECTOR: u8 = 32;
+
+/// The frequency of the timer, in Hertz, unless [`set_frequency`] is called.
+///
+/// 100 Hz gives a resolution of 10 ms, which is plenty for time slices while keeping the overhead
+/// of the timer interrupt negligible.
+pub const DEFAULT_FREQUENCY: u32 = 100;
+
+/// The divisor used when running the timer.
+const DIVISOR: TimerDivisor = TimerDivisor::Div16;
+
+/// The number of ticks elapsed since the timer was started.
+static JIFFIES: AtomicU64 = AtomicU64::new(0);
+
+/// The number of timer ticks per millisecond, measured by [`calibrate`].
+static TICKS_PER_MS: AtomicU32 = AtomicU32::new(0);
+
+/// The current frequency of the timer, in Hertz.
+static FREQUENCY: AtomicU32 = AtomicU32::new(0);
+
+/// Measures the frequency of the local APIC timer against the *Time-Stamp Counter*.
+///
+/// The timer is left stopped.
+///
+/// # Safety
+///
+/// [`SysInfo::tsc_frequency`](super::SysInfo::tsc_frequency) must be initialized.
+pub unsafe fn calibrate(lapic: &mut XApic) {
+    const CALIBRATION_MS: u32 = 10;
+
+    lapic.set_timer_divisor(DIVISOR);
+    lapic.configure_timer(TIMER_VECTOR, TimerMode::OneShot);
+    lapic.set_timer_initial_count(u32::MAX);
+    super::busy_wait(Duration::from_millis(CALIBRATION_MS as u64));
+    let elapsed = u32::MAX - lapic.timer_current_count();
+    lapic.set_timer_initial_count(0);
+
+    let ticks_per_ms = (elapsed / CALIBRATION_MS).max(1);
+    TICKS_PER_MS.store(ticks_per_ms, Relaxed);
+
+    nd_log::trace!("  > APIC timer: {} ticks/ms", ticks_per_ms);
+}
+
+/// Starts the timer in periodic mode, firing `hz` times per second.
+///
+/// If the requested frequency is higher than what the calibrated timer can achieve, the highest
+/// possible frequency is used instead and a warning is logged.
+///
+/// # Panics
+///
+/// In debug builds, this function panics if [`calibrate`] has not been called.
+pub fn set_frequency(lapic: &mut XApic, hz: u32) {
+    let ticks_per_ms = TICKS_PER_MS.load(Relaxed);
+    debug_assert!(ticks_per_ms != 0, "the APIC timer has not been calibrated");
+
+    let ticks_per_second = ticks_per_ms as u64 * 1000;
+    let max_frequency = ticks_per_second.min(u32::MAX as u64) as u32;
+
+    let hz = if hz == 0 || hz > max_frequency {
+        nd_log::warn!(
+            "Cannot run the APIC timer at {} Hz, using {} Hz instead.",
+            hz,
+            max_frequency,
+        );
+        max_frequency
+    } else {
+        hz
+    };
+
+    let count = (ticks_per_second / hz as u64).min(u32::MAX as u64) as u32;
+
+    FREQUENCY.store(hz, Relaxed);
+
+    lapic.set_timer_divisor(DIVISOR);
+    lapic.configure_timer(TIMER_VECTOR, TimerMode::Periodic);
+    lapic.set_timer_initial_count(count);
+}
+
+/// A function called by [`tick`], stored as a raw pointer. Null when no function is set.
+#[cfg(all(test, feature = "kernel-test"))]
+static TICK_HOOK: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+
+/// Sets a function to be called from the timer interrupt handler on every tick.
+///
+/// This allows tests to run code concurrently with themselves, interrupting them at arbitrary
+/// points.
+#[cfg(all(test, feature = "kernel-test"))]
+pub fn set_tick_hook(hook: Option<fn()>) {
+    let hook = hook.map_or(core::ptr::null_mut(), |hook| hook as *mut ());
+    TICK_HOOK.store(hook, SeqCst);
+}
+
+/// Records that a timer tick has elapsed.
+///
+/// This is called by the timer interrupt handler.
+#[inline(always)]
+pub fn tick() {
+    JIFFIES.fetch_add(1, Relaxed);
+
+    #[cfg(all(test, feature = "kernel-test"))]
+    {
+        let hook = TICK_HOOK.load(SeqCst);
+        if !hook.is_null() {
+            // SAFETY:
+            //  Only `fn()` pointers are stored in `TICK_HOOK`.
+            unsafe { core::mem::transmute::<*mut (), fn()>(hook)() };
+        }
+    }
+}
+
+/// Returns the number of timer ticks elapsed since the timer was started.
+#[inline(always)]
+pub fn ticks() -> u64 {
+    JIFFIES.load(Relaxed)
+}
+
+/// Returns the current frequency of the timer, in Hertz.
+///
+/// This is zero if the timer has not been started.
+#[inline(always)]
+pub fn frequency() -> u32 {
+    FREQUENCY.load(Relaxed)
+}
+
+/// Returns the time elapsed since the timer was started, with the resolution of a tick.
+pub fn uptime() -> Duration {
+    let hz = frequency() as u64;
+
+    if hz == 0 {
+        return Duration::ZERO;
+    }
+
+    let ticks = ticks();
+    Duration::from_secs(ticks / hz) + Duration::from_nanos((ticks % hz) * 1_000_000_000 / hz)
+}
@@ -67,6 +67,77 @@ impl SysResult {
             val => Ok(val),
         }
     }
+
+    /// Returns the success value of this [`SysResult`].
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the [`SysResult`] represents an error.
+    #[inline]
+    #[track_caller]
+    pub fn unwrap(self) -> usize {
+        match self.to_result() {
+            Ok(val) => val,
+            Err(err) => panic!("called `SysResult::unwrap()` on an error: {err:?}"),
+        }
+    }
+
+    /// Returns the success value of this [`SysResult`].
+    ///
+    /// # Panics
+    ///
+    /// This function panics with the provided message if the [`SysResult`] represents an error.
+    #[inline]
+    #[track_caller]
+    pub fn expect(self, msg: &str) -> usize {
+        match self.to_result() {
+            Ok(val) => val,
+            Err(err) => panic!("{msg}: {err:?}"),
+        }
+    }
+
+    /// Returns the error stored in this [`SysResult`].
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the [`SysResult`] represents success.
+    #[inline]
+    #[track_caller]
+    pub fn unwrap_err(self) -> SysError {
+        match self.to_result() {
+            Ok(val) => panic!("called `SysResult::unwrap_err()` on a success value: {val}"),
+            Err(err) => err,
+        }
+    }
+}
+
+impl From<Result<usize, SysError>> for SysResult {
+    /// Encodes a regular [`Result`] as a [`SysResult`].
+    ///
+    /// The success value must be less than [`SysResult::FIRST_ERROR`], or it would be mistaken
+    /// for an error.
+    #[inline(always)]
+    fn from(value: Result<usize, SysError>) -> Self {
+        match value {
+            Ok(val) => {
+                debug_assert!(
+                    val < Self::FIRST_ERROR,
+                    "success value overlaps with the error range"
+                );
+                Self(val)
+            }
+            Err(err) => Self::from_error(err),
+        }
+    }
+}
+
+impl fmt::Display for SysResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_result() {
+            Ok(val) => write!(f, "Ok({val})"),
+            Err(err) => f.write_str(err.name()),
+        }
+    }
 }
 
 #[cfg(feature = "try_trait_v2")]
@@ -164,3 +235,53 @@ impl fmt::Display for SysError {
 
 /// A unique identifier for a process in the system.
 pub type ProcessHandle = NonZeroUsize;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_boundary() {
+        assert!(SysResult(0).is_success());
+        assert!(SysResult(SysResult::FIRST_ERROR - 1).is_success());
+        assert!(SysResult(SysResult::FIRST_ERROR).is_error());
+        assert!(SysResult(usize::MAX).is_error());
+        assert_eq!(SysResult::FIRST_ERROR, usize::MAX - 4095);
+    }
+
+    #[test]
+    fn to_result() {
+        assert_eq!(SysResult(42).to_result(), Ok(42));
+        assert_eq!(
+            SysResult(SysResult::FIRST_ERROR - 1).to_result(),
+            Ok(SysResult::FIRST_ERROR - 1)
+        );
+        assert_eq!(
+            SysResult::from_error(SysError::CONFLICT).to_result(),
+            Err(SysError::CONFLICT)
+        );
+        assert_eq!(SysResult(usize::MAX).unwrap_err(), SysError(usize::MAX));
+    }
+
+    #[test]
+    fn from_result() {
+        assert_eq!(SysResult::from(Ok(7)), SysResult(7));
+        assert_eq!(
+            SysResult::from(Err(SysError::INVALID_ARGUMENT)),
+            SysResult(SysResult::FIRST_ERROR)
+        );
+    }
+
+    #[test]
+    fn error_names() {
+        assert_eq!(SysError::INVALID_ARGUMENT.0, SysResult::FIRST_ERROR);
+        assert_eq!(SysError::CONFLICT.name(), "CONFLICT");
+        assert_eq!(SysError(SysResult::FIRST_ERROR + 4000).name(), "UNKNOWN");
+    }
+
+    #[test]
+    #[should_panic]
+    fn unwrap_error() {
+        let _ = SysResult::from_error(SysError::CONFLICT).unwrap();
+    }
+}
@@ -0,0 +1,144 @@
+//! The raw `syscall` instruction, for each number of arguments.
+//!
+//! # Calling Convention
+//!
+//! This must match the `handle_syscall` function of the kernel:
+//!
+//! - `rax` contains the system call number, and receives the returned [`SysResult`].
+//!
+//! - `rdi`, `rsi` and `rdx` contain the first, second and third arguments.
+//!
+//! # Clobbered Registers
+//!
+//! The `syscall` instruction itself overwrites two registers: `rcx` receives the return address
+//! and `r11` receives the value of **RFLAGS**. The kernel uses them to return with `sysretq`, but
+//! their values are lost from the point of view of the caller.
+//!
+//! On top of that, the kernel dispatches the system call to functions using the C calling
+//! convention without saving anything but `rcx` and `r11`. Any register that is not preserved by
+//! the C calling convention (`rdi`, `rsi`, `rdx`, `r8`, `r9` and `r10`) must be considered
+//! clobbered as well, and so are the flags.
+//!
+//! Finally, the kernel keeps using the stack of the caller while handling the system call. It may
+//! write below the stack pointer, which is why the `nostack` option cannot be used (the red zone
+//! of the caller would be overwritten).
+
+use core::arch::asm;
+
+use neodym_sys_common::{SysResult, SystemCall};
+
+/// Performs a system call with no arguments.
+///
+/// # Safety
+///
+/// System calls are fundamentally unsafe. The specific safety requirement of this function depend
+/// on the system call being performed.
+// No system call takes zero arguments yet.
+#[allow(dead_code)]
+#[inline(always)]
+pub unsafe fn syscall0(n: SystemCall) -> SysResult {
+    let ret: usize;
+
+    unsafe {
+        asm!(
+            "syscall",
+            inlateout("rax") n.to_usize() => ret,
+            lateout("rdi") _,
+            lateout("rsi") _,
+            lateout("rdx") _,
+            lateout("rcx") _,
+            lateout("r8") _,
+            lateout("r9") _,
+            lateout("r10") _,
+            lateout("r11") _,
+        );
+    }
+
+    SysResult(ret)
+}
+
+/// Performs a system call with one argument.
+///
+/// # Safety
+///
+/// System calls are fundamentally unsafe. The specific safety requirement of this function depend
+/// on the system call being performed.
+#[inline(always)]
+pub unsafe fn syscall1(n: SystemCall, arg0: usize) -> SysResult {
+    let ret: usize;
+
+    unsafe {
+        asm!(
+            "syscall",
+            inlateout("rax") n.to_usize() => ret,
+            inlateout("rdi") arg0 => _,
+            lateout("rsi") _,
+            lateout("rdx") _,
+            lateout("rcx") _,
+            lateout("r8") _,
+            lateout("r9") _,
+            lateout("r10") _,
+            lateout("r11") _,
+        );
+    }
+
+    SysResult(ret)
+}
+
+/// Performs a system call with two arguments.
+///
+/// # Safety
+///
+/// System calls are fundamentally unsafe. The specific safety requirement of this function depend
+/// on the system call being performed.
+#[inline(always)]
+pub unsafe fn syscall2(n: SystemCall, arg0: usize, arg1: usize) -> SysResult {
+    let ret: usize;
+
+    unsafe {
+        asm!(
+            "syscall",
+            inlateout("rax") n.to_usize() => ret,
+            inlateout("rdi") arg0 => _,
+            inlateout("rsi") arg1 => _,
+            lateout("rdx") _,
+            lateout("rcx") _,
+            lateout("r8") _,
+            lateout("r9") _,
+            lateout("r10") _,
+            lateout("r11") _,
+        );
+    }
+
+    SysResult(ret)
+}
+
+/// Performs a system call with three arguments.
+///
+/// # Safety
+///
+/// System calls are fundamentally unsafe. The specific safety requirement of this function depend
+/// on the system call being performed.
+// No system call takes three arguments yet.
+#[allow(dead_code)]
+#[inline(always)]
+pub unsafe fn syscall3(n: SystemCall, arg0: usize, arg1: usize, arg2: usize) -> SysResult {
+    let ret: usize;
+
+    unsafe {
+        asm!(
+            "syscall",
+            inlateout("rax") n.to_usize() => ret,
+            inlateout("rdi") arg0 => _,
+            inlateout("rsi") arg1 => _,
+            inlateout("rdx") arg2 => _,
+            lateout("rcx") _,
+            lateout("r8") _,
+            lateout("r9") _,
+            lateout("r10") _,
+            lateout("r11") _,
+        );
+    }
+
+    SysResult(ret)
+}
@@ -135,7 +135,8 @@ where
         let mut hole = unsafe { Hole::new(&mut self.data, pos) };
         let mut child = 2 * hole.pos() + 1;
 
-        while child < end {
+        // Stop before the last child if it has no sibling; it's handled below.
+        while child + 1 < end {
             child += unsafe { hole.get(child) <= hole.get(child + 1) } as usize;
             unsafe { hole.move_to(child) };
             child = 2 * hole.pos() + 1;
@@ -238,3 +239,36 @@ impl<'a, T> Drop for Hole<'a, T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pops_in_order() {
+        let mut heap = BinaryHeap::<u32, 8>::new();
+        assert!(heap.is_empty());
+        assert_eq!(heap.peek(), None);
+
+        for x in [5, 1, 8, 3, 9, 2] {
+            heap.push(x).unwrap();
+        }
+        assert_eq!(heap.len(), 6);
+        assert_eq!(heap.peek(), Some(&9));
+
+        for expected in [9, 8, 5, 3, 2, 1] {
+            assert_eq!(heap.pop(), Some(expected));
+        }
+        assert_eq!(heap.pop(), None);
+    }
+
+    #[test]
+    fn full() {
+        let mut heap = BinaryHeap::<u32, 2>::new();
+        heap.push(1).unwrap();
+        heap.push(2).unwrap();
+        assert!(heap.is_full());
+        assert_eq!(heap.push(3), Err(3));
+        assert_eq!(heap.peek(), Some(&2));
+    }
+}
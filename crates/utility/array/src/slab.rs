@@ -108,3 +108,35 @@ impl<T, const N: usize> Slab<T, N> {
         self.data.get_mut(index)?.take()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuses_free_slots() {
+        let mut slab = Slab::<u32, 4>::new();
+        assert_eq!(slab.insert(10), Ok(0));
+        assert_eq!(slab.insert(11), Ok(1));
+        assert_eq!(slab.insert(12), Ok(2));
+
+        assert_eq!(slab.remove(1), Some(11));
+        assert_eq!(slab.remove(1), None);
+        assert_eq!(slab.insert(13), Ok(1));
+        assert_eq!(slab.insert(14), Ok(3));
+    }
+
+    #[test]
+    fn full() {
+        let mut slab = Slab::<u32, 2>::new();
+        slab.insert(1).unwrap();
+        slab.insert(2).unwrap();
+        assert!(slab.is_full());
+        assert_eq!(slab.insert(3), Err(3));
+
+        assert_eq!(slab.remove(0), Some(1));
+        assert!(!slab.is_full());
+        assert_eq!(slab.insert(3), Ok(0));
+        assert_eq!(slab.remove(5), None);
+    }
+}
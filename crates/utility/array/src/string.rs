@@ -54,3 +54,23 @@ impl<const N: usize> String<N> {
         true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_str() {
+        let mut s = String::<8>::new();
+        assert!(s.is_empty());
+        assert_eq!(s.capacity(), 8);
+
+        assert!(s.push_str("hello"));
+        assert_eq!(s.len(), 5);
+        assert!(!s.push_str("world"));
+        assert_eq!(s.len(), 5);
+        assert!(s.push_str("abc"));
+        assert_eq!(s.len(), 8);
+        assert!(s.push_str(""));
+    }
+}
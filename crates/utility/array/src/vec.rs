@@ -1,5 +1,6 @@
+use core::iter::Peekable;
 use core::mem::MaybeUninit;
-use core::ops::{Deref, DerefMut};
+use core::ops::{Bound, Deref, DerefMut, RangeBounds};
 
 /// An array-based vector.
 pub struct Vec<T, const N: usize> {
@@ -45,6 +46,28 @@ impl<T, const N: usize> Vec<T, N> {
         self.len == self.capacity()
     }
 
+    /// Returns the number of elements that can still be pushed onto the vector.
+    #[inline(always)]
+    pub const fn remaining_capacity(&self) -> usize {
+        N - self.len
+    }
+
+    /// Returns a slice over the elements of the vector.
+    ///
+    /// The returned slice contains exactly [`len`](Vec::len) elements.
+    #[inline(always)]
+    pub fn as_slice(&self) -> &[T] {
+        self
+    }
+
+    /// Returns a mutable slice over the elements of the vector.
+    ///
+    /// The returned slice contains exactly [`len`](Vec::len) elements.
+    #[inline(always)]
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        self
+    }
+
     /// Returns a pointer to the array backing this vector.
     #[inline(always)]
     pub const fn as_ptr(&self) -> *const T {
@@ -110,6 +133,54 @@ impl<T, const N: usize> Vec<T, N> {
         self.len += 1;
     }
 
+    /// Creates a new [`Vec<T, N>`] from the elements of `iter`.
+    ///
+    /// # Errors
+    ///
+    /// If `iter` yields more than `N` elements, the vector is filled up to its capacity and
+    /// returned along with the rest of the iterator. No element is lost: the first element that
+    /// did not fit can be retrieved by peeking into the returned iterator.
+    pub fn try_from_iter<I>(iter: I) -> Result<Self, (Self, Peekable<I::IntoIter>)>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let mut vec = Self::new();
+        match vec.try_extend(iter) {
+            Ok(()) => Ok(vec),
+            Err(rest) => Err((vec, rest)),
+        }
+    }
+
+    /// Appends the elements of `iter` to the vector.
+    ///
+    /// # Errors
+    ///
+    /// If the elements of `iter` do not fit in the remaining capacity of the vector, the vector is
+    /// filled up to its capacity and the rest of the iterator is returned. No element is lost:
+    /// the first element that did not fit can be retrieved by peeking into the returned iterator.
+    pub fn try_extend<I>(&mut self, iter: I) -> Result<(), Peekable<I::IntoIter>>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let mut iter = iter.into_iter().peekable();
+
+        while iter.peek().is_some() {
+            if self.is_full() {
+                return Err(iter);
+            }
+
+            // SAFETY:
+            //  We just checked that the iterator has a next element and that the vector is not
+            //  full.
+            unsafe {
+                let value = iter.next().unwrap_unchecked();
+                self.push_unchecked(value);
+            }
+        }
+
+        Ok(())
+    }
+
     /// Attempts to remove the last value from the vector.
     #[inline]
     pub fn pop(&mut self) -> Option<T> {
@@ -172,6 +243,165 @@ impl<T, const N: usize> Vec<T, N> {
             Some(unsafe { self.swap_remove_unchecked(index) })
         }
     }
+
+    /// Shortens the vector, keeping the first `len` elements and dropping the rest.
+    ///
+    /// If `len` is greater than or equal to the current length of the vector, this function has
+    /// no effect.
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+
+        let old_len = self.len;
+
+        // Update the length first, in case dropping an element panics.
+        self.len = len;
+
+        unsafe {
+            let tail =
+                core::ptr::slice_from_raw_parts_mut(self.as_mut_ptr().add(len), old_len - len);
+            core::ptr::drop_in_place(tail);
+        }
+    }
+
+    /// Removes all the elements of the vector.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Removes the elements in the provided range from the vector, returning an iterator over
+    /// them.
+    ///
+    /// The elements after the range are shifted to fill the hole when the returned iterator is
+    /// dropped. Elements which were not consumed by the iterator are dropped at the same time.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the start of the range is greater than its end, or if the end of
+    /// the range is out of bounds.
+    pub fn drain<R>(&mut self, range: R) -> Drain<T, N>
+    where
+        R: RangeBounds<usize>,
+    {
+        let start = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&n) => n + 1,
+            Bound::Excluded(&n) => n,
+            Bound::Unbounded => self.len,
+        };
+
+        assert!(start <= end, "drain range starts after its end");
+        assert!(end <= self.len, "drain range is out of bounds");
+
+        let tail_len = self.len - end;
+
+        // If the `Drain` is leaked, the drained elements and the tail are leaked as well, but the
+        // vector remains valid.
+        self.len = start;
+
+        Drain {
+            vec: self,
+            next: start,
+            end,
+            tail_start: end,
+            tail_len,
+        }
+    }
+}
+
+impl<T: Copy, const N: usize> Vec<T, N> {
+    /// Attempts to append the elements of `slice` to the vector.
+    ///
+    /// This function returns its input in case it does not fit in the remaining capacity of the
+    /// vector. The vector is left unchanged in that case.
+    #[inline]
+    pub fn extend_from_slice<'a>(&mut self, slice: &'a [T]) -> Result<(), &'a [T]> {
+        let buf = self.spare_capacity_mut();
+
+        if buf.len() < slice.len() {
+            return Err(slice);
+        }
+
+        unsafe {
+            core::ptr::copy_nonoverlapping(slice.as_ptr(), buf.as_mut_ptr() as *mut T, slice.len());
+            self.set_len(self.len + slice.len());
+        }
+
+        Ok(())
+    }
+}
+
+/// An iterator over the elements removed from a [`Vec<T, N>`] by [`Vec::drain`].
+pub struct Drain<'a, T, const N: usize> {
+    /// The vector being drained.
+    ///
+    /// Its length is set to the start of the drained range while the iterator is alive.
+    vec: &'a mut Vec<T, N>,
+    /// The index of the next element to yield.
+    next: usize,
+    /// The index after the last element to yield.
+    end: usize,
+    /// The index of the first element after the drained range.
+    tail_start: usize,
+    /// The number of elements after the drained range.
+    tail_len: usize,
+}
+
+impl<'a, T, const N: usize> Iterator for Drain<'a, T, N> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next == self.end {
+            return None;
+        }
+
+        let value = unsafe { self.vec.data.get_unchecked(self.next).assume_init_read() };
+        self.next += 1;
+        Some(value)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end - self.next;
+        (len, Some(len))
+    }
+}
+
+impl<'a, T, const N: usize> DoubleEndedIterator for Drain<'a, T, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.next == self.end {
+            return None;
+        }
+
+        self.end -= 1;
+        Some(unsafe { self.vec.data.get_unchecked(self.end).assume_init_read() })
+    }
+}
+
+impl<'a, T, const N: usize> ExactSizeIterator for Drain<'a, T, N> {}
+
+impl<'a, T, const N: usize> Drop for Drain<'a, T, N> {
+    fn drop(&mut self) {
+        unsafe {
+            let base = self.vec.as_mut_ptr();
+            let start = self.vec.len;
+
+            // Drop the elements that were not consumed.
+            let remaining =
+                core::ptr::slice_from_raw_parts_mut(base.add(self.next), self.end - self.next);
+            core::ptr::drop_in_place(remaining);
+
+            // Move the tail back to fill the hole.
+            core::ptr::copy(base.add(self.tail_start), base.add(start), self.tail_len);
+            self.vec.set_len(start + self.tail_len);
+        }
+    }
 }
 
 impl<T, const N: usize> Deref for Vec<T, N> {
@@ -179,14 +409,14 @@ impl<T, const N: usize> Deref for Vec<T, N> {
 
     #[inline(always)]
     fn deref(&self) -> &Self::Target {
-        unsafe { core::slice::from_raw_parts(self.as_ptr() as *const T, self.len) }
+        unsafe { core::slice::from_raw_parts(self.as_ptr(), self.len) }
     }
 }
 
 impl<T, const N: usize> DerefMut for Vec<T, N> {
     #[inline(always)]
     fn deref_mut(&mut self) -> &mut Self::Target {
-        unsafe { core::slice::from_raw_parts_mut(self.as_mut_ptr() as *mut T, self.len) }
+        unsafe { core::slice::from_raw_parts_mut(self.as_mut_ptr(), self.len) }
     }
 }
 
@@ -250,3 +480,159 @@ impl<'a, T, const N: usize> IntoIterator for &'a mut Vec<T, N> {
         self.iter_mut()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capacity() {
+        let mut v = Vec::<u32, 3>::new();
+        assert_eq!(v.remaining_capacity(), 3);
+        assert!(!v.is_full());
+
+        v.push(1).unwrap();
+        v.push(2).unwrap();
+        assert_eq!(v.remaining_capacity(), 1);
+
+        v.push(3).unwrap();
+        assert_eq!(v.remaining_capacity(), 0);
+        assert!(v.is_full());
+        assert_eq!(v.push(4), Err(4));
+    }
+
+    #[test]
+    fn slices() {
+        let mut v = Vec::<u32, 8>::new();
+        assert!(v.as_slice().is_empty());
+
+        v.push(1).unwrap();
+        v.push(2).unwrap();
+        assert_eq!(v.as_slice(), &[1, 2]);
+
+        v.as_mut_slice()[0] = 5;
+        assert_eq!(v.as_slice(), &[5, 2]);
+        assert_eq!(v.get(1), Some(&2));
+        assert_eq!(v.get(2), None);
+        *v.get_mut(1).unwrap() = 7;
+        assert_eq!(v.as_slice(), &[5, 7]);
+    }
+
+    #[test]
+    fn try_from_iter() {
+        let v = Vec::<u32, 3>::try_from_iter([1, 2]).ok().unwrap();
+        assert_eq!(v.as_slice(), &[1, 2]);
+
+        let v = Vec::<u32, 3>::try_from_iter([1, 2, 3]).ok().unwrap();
+        assert_eq!(v.as_slice(), &[1, 2, 3]);
+
+        let Err((v, mut rest)) = Vec::<u32, 3>::try_from_iter([1, 2, 3, 4, 5]) else {
+            panic!("the iterator should not fit");
+        };
+        assert_eq!(v.as_slice(), &[1, 2, 3]);
+        assert_eq!(rest.peek(), Some(&4));
+        assert_eq!(rest.count(), 2);
+    }
+
+    #[test]
+    fn try_extend() {
+        let mut v = Vec::<u32, 4>::new();
+        v.push(1).unwrap();
+
+        assert!(v.try_extend([2, 3]).is_ok());
+        assert_eq!(v.as_slice(), &[1, 2, 3]);
+
+        assert!(v.try_extend(core::iter::empty()).is_ok());
+        assert_eq!(v.as_slice(), &[1, 2, 3]);
+
+        let mut rest = v.try_extend([4, 5, 6]).unwrap_err();
+        assert_eq!(v.as_slice(), &[1, 2, 3, 4]);
+        assert_eq!(rest.next(), Some(5));
+        assert_eq!(rest.next(), Some(6));
+        assert_eq!(rest.next(), None);
+
+        // A full vector accepts an empty iterator.
+        assert!(v.try_extend(core::iter::empty()).is_ok());
+    }
+
+    #[test]
+    fn truncate() {
+        let mut v = Vec::<u32, 4>::try_from_iter([1, 2, 3]).ok().unwrap();
+
+        v.truncate(5);
+        assert_eq!(v.as_slice(), &[1, 2, 3]);
+
+        v.truncate(1);
+        assert_eq!(v.as_slice(), &[1]);
+
+        v.clear();
+        assert!(v.is_empty());
+    }
+
+    #[test]
+    fn truncate_drops_elements() {
+        use core::cell::Cell;
+
+        struct Counted<'a>(&'a Cell<usize>);
+
+        impl Drop for Counted<'_> {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let drops = Cell::new(0);
+        let mut v = Vec::<Counted, 4>::new();
+        for _ in 0..4 {
+            assert!(v.push(Counted(&drops)).is_ok());
+        }
+
+        v.truncate(1);
+        assert_eq!(drops.get(), 3);
+
+        drop(v);
+        assert_eq!(drops.get(), 4);
+    }
+
+    #[test]
+    fn drain() {
+        let mut v = Vec::<u32, 8>::try_from_iter([1, 2, 3, 4, 5]).ok().unwrap();
+
+        let mut drain = v.drain(1..3);
+        assert_eq!(drain.len(), 2);
+        assert_eq!(drain.next(), Some(2));
+        assert_eq!(drain.next_back(), Some(3));
+        assert_eq!(drain.next(), None);
+        drop(drain);
+        assert_eq!(v.as_slice(), &[1, 4, 5]);
+
+        // Elements which are not consumed are removed anyway.
+        v.drain(..=1);
+        assert_eq!(v.as_slice(), &[5]);
+
+        assert!(v.drain(..).eq([5]));
+        assert!(v.is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn drain_out_of_bounds() {
+        let mut v = Vec::<u32, 8>::try_from_iter([1, 2]).ok().unwrap();
+        v.drain(1..3);
+    }
+
+    #[test]
+    fn extend_from_slice() {
+        let mut v = Vec::<u32, 4>::new();
+
+        assert_eq!(v.extend_from_slice(&[1, 2]), Ok(()));
+        assert_eq!(v.extend_from_slice(&[]), Ok(()));
+        assert_eq!(v.as_slice(), &[1, 2]);
+
+        assert_eq!(v.extend_from_slice(&[3, 4, 5]), Err(&[3, 4, 5][..]));
+        assert_eq!(v.as_slice(), &[1, 2]);
+
+        assert_eq!(v.extend_from_slice(&[3, 4]), Ok(()));
+        assert_eq!(v.as_slice(), &[1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,135 @@
+//! Formatting helpers which do not require any allocation.
+
+#![no_std]
+#![warn(missing_docs, missing_debug_implementations)]
+#![deny(unsafe_op_in_unsafe_fn)]
+
+use core::fmt;
+
+/// Displays a number of bytes in a human readable format (e.g. `1.50 KiB`).
+///
+/// Binary units are used (1 KiB = 1024 B). The fractional part is truncated to two digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HumanBytes(pub u64);
+
+impl fmt::Display for HumanBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+        if self.0 < 1024 {
+            return write!(f, "{} B", self.0);
+        }
+
+        // `value` is always expressed in the unit *below* `UNITS[unit]`, so that the fractional
+        // part can be computed from the remainder.
+        let mut value = self.0;
+        let mut unit = 0;
+        while unit + 1 < UNITS.len() && value >= 1024 * 1024 {
+            value /= 1024;
+            unit += 1;
+        }
+
+        write!(
+            f,
+            "{}.{:02} {}",
+            value / 1024,
+            (value % 1024) * 100 / 1024,
+            UNITS[unit],
+        )
+    }
+}
+
+/// Displays a 64-bit number in hexadecimal, padded with zeros (e.g. `0x00000000deadbeef`).
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Hex(pub u64);
+
+impl fmt::Display for Hex {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#018x}", self.0)
+    }
+}
+
+impl fmt::Debug for Hex {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// Displays a 32-bit number in hexadecimal, padded with zeros (e.g. `0x0000beef`).
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Hex32(pub u32);
+
+impl fmt::Display for Hex32 {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#010x}", self.0)
+    }
+}
+
+impl fmt::Debug for Hex32 {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// The type returned by [`padded`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Padded {
+    value: u64,
+    width: usize,
+}
+
+impl fmt::Display for Padded {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:0width$}", self.value, width = self.width)
+    }
+}
+
+/// Displays `value` in decimal, left-padded with zeros to at least `width` digits.
+#[inline]
+pub fn padded(value: u64, width: usize) -> Padded {
+    Padded { value, width }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn human_bytes_boundaries() {
+        assert_eq!(HumanBytes(0).to_string(), "0 B");
+        assert_eq!(HumanBytes(1023).to_string(), "1023 B");
+        assert_eq!(HumanBytes(1024).to_string(), "1.00 KiB");
+        assert_eq!(HumanBytes(1024 + 512).to_string(), "1.50 KiB");
+        assert_eq!(HumanBytes(1024 + 51).to_string(), "1.04 KiB");
+        assert_eq!(HumanBytes((1 << 20) - 1).to_string(), "1023.99 KiB");
+        assert_eq!(HumanBytes(1 << 20).to_string(), "1.00 MiB");
+        assert_eq!(HumanBytes((1 << 30) - 1).to_string(), "1023.99 MiB");
+        assert_eq!(HumanBytes(1 << 30).to_string(), "1.00 GiB");
+        assert_eq!(HumanBytes(3 << 29).to_string(), "1.50 GiB");
+        assert_eq!(HumanBytes((1 << 40) - 1).to_string(), "1023.99 GiB");
+        assert_eq!(HumanBytes(1 << 40).to_string(), "1.00 TiB");
+        assert_eq!(HumanBytes(1 << 50).to_string(), "1024.00 TiB");
+        assert_eq!(HumanBytes(u64::MAX).to_string(), "16777215.99 TiB");
+    }
+
+    #[test]
+    fn hex() {
+        assert_eq!(Hex(0xdeadbeef).to_string(), "0x00000000deadbeef");
+        assert_eq!(Hex(u64::MAX).to_string(), "0xffffffffffffffff");
+        assert_eq!(Hex32(0xbeef).to_string(), "0x0000beef");
+    }
+
+    #[test]
+    fn padded_decimal() {
+        assert_eq!(padded(42, 5).to_string(), "00042");
+        assert_eq!(padded(123456, 3).to_string(), "123456");
+    }
+}
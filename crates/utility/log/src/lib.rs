@@ -4,20 +4,46 @@
 #![warn(missing_docs, missing_debug_implementations)]
 #![deny(unsafe_op_in_unsafe_fn)]
 
+use core::fmt;
 use core::fmt::Arguments;
 use core::sync::atomic::AtomicPtr;
 use core::sync::atomic::Ordering::Relaxed;
 
 /// A verbosity level associated with a [`Record`].
+///
+/// # Ordering
+///
+/// Levels are ordered from the least verbose to the most verbose: a *higher* level is *more
+/// verbose* and *less severe*. In other words, `Error < Warn < Info < Debug < Trace`.
+///
+/// This means that a filter keeping every record up to a maximum level should keep records for
+/// which `record.verbosity <= max_level`. When comparing levels in terms of severity,
+/// [`Verbosity::is_at_least_as_severe_as`] might be clearer.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-#[allow(missing_docs)]
 pub enum Verbosity {
+    /// An error which prevents the system from working properly.
     Error,
+    /// Something unexpected happened, but the system can keep working.
     Warn,
+    /// General information about the state of the system.
     Info,
+    /// Information which is mostly useful when debugging the system.
+    Debug,
+    /// Very detailed information about what the system is doing.
     Trace,
 }
 
+impl Verbosity {
+    /// Returns whether this level is at least as severe as `other`.
+    ///
+    /// For example, [`Verbosity::Error`] is at least as severe as [`Verbosity::Warn`], but
+    /// [`Verbosity::Trace`] is not.
+    #[inline(always)]
+    pub fn is_at_least_as_severe_as(self, other: Self) -> bool {
+        self <= other
+    }
+}
+
 /// A record that can be logged by the global logger.
 #[derive(Debug, Clone, Copy)]
 pub struct Record<'a> {
@@ -29,6 +55,11 @@ pub struct Record<'a> {
     pub file: &'static str,
     /// The line within the file from which this record originates.
     pub line: u32,
+    /// Additional key-value pairs attached to the record.
+    ///
+    /// This is empty unless the record was created with [`record_kv!`] or [`log_kv!`]. Loggers
+    /// are free to ignore those fields, or to display them as `key=value` pairs.
+    pub fields: &'a [(&'static str, Arguments<'a>)],
 }
 
 /// Creates a [`Record`] for the current call-site.
@@ -40,6 +71,24 @@ macro_rules! record {
             message: ::core::format_args!($($args)*),
             file: ::core::file!(),
             line: ::core::line!(),
+            fields: &[],
+        }
+    };
+}
+
+/// Creates a [`Record`] with key-value fields for the current call-site.
+///
+/// Fields are specified between braces, before the message. Their values are formatted using
+/// their [`Display`](core::fmt::Display) implementation.
+#[macro_export]
+macro_rules! record_kv {
+    ($verbosity:expr, { $($key:ident = $value:expr),* $(,)? }, $($args:tt)*) => {
+        $crate::Record {
+            verbosity: $verbosity,
+            message: ::core::format_args!($($args)*),
+            file: ::core::file!(),
+            line: ::core::line!(),
+            fields: &[$((::core::stringify!($key), ::core::format_args!("{}", $value))),*],
         }
     };
 }
@@ -83,6 +132,24 @@ macro_rules! log {
     };
 }
 
+/// Logs a message with key-value fields using the global logger.
+///
+/// # Examples
+///
+/// ```ignore
+/// nd_log::log_kv!(
+///     Verbosity::Info,
+///     { cpu = 0, phys = format_args!("{:#x}", phys) },
+///     "Mapped the local APIC",
+/// );
+/// ```
+#[macro_export]
+macro_rules! log_kv {
+    ($verbosity:expr, { $($fields:tt)* }, $($args:tt)*) => {
+        $crate::get_global_logger()(&$crate::record_kv!($verbosity, { $($fields)* }, $($args)*))
+    };
+}
+
 /// Logs a message with the [`Verbosity::Error`] level.
 #[macro_export]
 macro_rules! error {
@@ -107,6 +174,14 @@ macro_rules! info {
     };
 }
 
+/// Logs a message with the [`Verbosity::Debug`] level.
+#[macro_export]
+macro_rules! debug {
+    ($($args:tt)*) => {
+        $crate::log!($crate::Verbosity::Debug, $($args)*);
+    };
+}
+
 /// Logs a message with the [`Verbosity::Trace`] level.
 #[macro_export]
 macro_rules! trace {
@@ -114,3 +189,206 @@ macro_rules! trace {
         $crate::log!($crate::Verbosity::Trace, $($args)*);
     };
 }
+
+/// Asserts that a condition holds, logging an error record before panicking if it does not.
+///
+/// Unlike [`debug_assert!`], this check is kept in release builds. It is meant to guard critical
+/// invariants whose violation must always be visible in the logs.
+///
+/// # Examples
+///
+/// ```ignore
+/// nd_log::kassert!(addr % 4096 == 0, "address {:#x} is not page-aligned", addr);
+/// ```
+#[macro_export]
+macro_rules! kassert {
+    ($cond:expr $(,)?) => {
+        $crate::kassert!($cond, "assertion failed: {}", ::core::stringify!($cond))
+    };
+    ($cond:expr, $($args:tt)+) => {
+        if !$cond {
+            $crate::assertion_failed(::core::format_args!($($args)+));
+        }
+    };
+}
+
+/// Unwraps a [`Result`], logging an error record before panicking if it is an [`Err`].
+///
+/// The error is displayed using its [`Debug`](core::fmt::Debug) implementation. Like
+/// [`kassert!`], this check is kept in release builds.
+///
+/// # Examples
+///
+/// ```ignore
+/// let page = nd_log::kexpect!(allocator.allocate(), "failed to allocate the PML4");
+/// ```
+#[macro_export]
+macro_rules! kexpect {
+    ($result:expr $(,)?) => {
+        $crate::kexpect!($result, "called `kexpect!` on an `Err` value")
+    };
+    ($result:expr, $($args:tt)+) => {
+        match $result {
+            ::core::result::Result::Ok(val) => val,
+            ::core::result::Result::Err(err) => {
+                $crate::expect_failed(&err, ::core::format_args!($($args)+))
+            }
+        }
+    };
+}
+
+/// The failure path of [`kassert!`].
+#[doc(hidden)]
+#[cold]
+#[track_caller]
+pub fn assertion_failed(message: Arguments) -> ! {
+    let location = core::panic::Location::caller();
+
+    get_global_logger()(&Record {
+        verbosity: Verbosity::Error,
+        message,
+        file: location.file(),
+        line: location.line(),
+        fields: &[],
+    });
+
+    panic!("{message}");
+}
+
+/// The failure path of [`kexpect!`].
+#[doc(hidden)]
+#[cold]
+#[track_caller]
+pub fn expect_failed(err: &dyn fmt::Debug, message: Arguments) -> ! {
+    let location = core::panic::Location::caller();
+
+    get_global_logger()(&Record {
+        verbosity: Verbosity::Error,
+        message: format_args!("{message}: {err:?}"),
+        file: location.file(),
+        line: location.line(),
+        fields: &[],
+    });
+
+    panic!("{message}: {err:?}");
+}
+
+/// The number of bytes displayed on each line of a hexdump.
+const HEXDUMP_LINE_LEN: usize = 16;
+
+/// A single line of a hexdump.
+struct HexdumpLine<'a> {
+    /// The address of the first byte of the line, if it should be displayed.
+    addr: Option<usize>,
+    /// The bytes to display. At most [`HEXDUMP_LINE_LEN`] bytes.
+    bytes: &'a [u8],
+}
+
+impl<'a> fmt::Display for HexdumpLine<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(addr) = self.addr {
+            write!(f, "{addr:016x}  ")?;
+        }
+
+        for i in 0..HEXDUMP_LINE_LEN {
+            match self.bytes.get(i) {
+                Some(b) => write!(f, "{b:02x} ")?,
+                None => f.write_str("   ")?,
+            }
+
+            if i == HEXDUMP_LINE_LEN / 2 - 1 {
+                f.write_str(" ")?;
+            }
+        }
+
+        f.write_str(" |")?;
+        for &b in self.bytes {
+            let c = if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            };
+            fmt::Write::write_char(f, c)?;
+        }
+        f.write_str("|")
+    }
+}
+
+/// Logs a hexdump of `bytes` using the global logger.
+///
+/// Each line displays 16 bytes as hexadecimal columns, followed by their printable ASCII
+/// representation. Each line is logged as a separate [`Record`], attributed to the caller.
+#[track_caller]
+pub fn hexdump(verbosity: Verbosity, bytes: &[u8]) {
+    hexdump_inner(verbosity, None, bytes);
+}
+
+/// Logs a hexdump of `bytes` using the global logger, prefixing each line with its address.
+///
+/// `addr` is the address of the first byte of `bytes`. See [`hexdump`] for more information.
+#[track_caller]
+pub fn hexdump_at(verbosity: Verbosity, addr: usize, bytes: &[u8]) {
+    hexdump_inner(verbosity, Some(addr), bytes);
+}
+
+/// The implementation of [`hexdump`] and [`hexdump_at`].
+#[track_caller]
+fn hexdump_inner(verbosity: Verbosity, addr: Option<usize>, bytes: &[u8]) {
+    let location = core::panic::Location::caller();
+    let logger = get_global_logger();
+
+    for (i, chunk) in bytes.chunks(HEXDUMP_LINE_LEN).enumerate() {
+        let line = HexdumpLine {
+            addr: addr.map(|addr| addr.wrapping_add(i * HEXDUMP_LINE_LEN)),
+            bytes: chunk,
+        };
+
+        logger(&Record {
+            verbosity,
+            message: format_args!("{line}"),
+            file: location.file(),
+            line: location.line(),
+            fields: &[],
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn severity() {
+        assert!(Verbosity::Error < Verbosity::Trace);
+        assert!(Verbosity::Error.is_at_least_as_severe_as(Verbosity::Warn));
+        assert!(Verbosity::Info.is_at_least_as_severe_as(Verbosity::Info));
+        assert!(!Verbosity::Trace.is_at_least_as_severe_as(Verbosity::Debug));
+    }
+
+    #[test]
+    fn record_fields() {
+        let record = record!(Verbosity::Info, "no fields");
+        assert!(record.fields.is_empty());
+
+        // Records borrow temporaries from the call-site, they must be used within the same
+        // statement.
+        fn check(record: &Record) {
+            assert_eq!(record.message.to_string(), "with fields");
+            assert_eq!(record.fields.len(), 2);
+            assert_eq!(record.fields[0].0, "cpu");
+            assert_eq!(record.fields[0].1.to_string(), "0");
+            assert_eq!(record.fields[1].0, "phys");
+            assert_eq!(record.fields[1].1.to_string(), "0x1000");
+        }
+
+        let phys = 0x1000;
+        check(&record_kv!(
+            Verbosity::Info,
+            { cpu = 0, phys = format_args!("{:#x}", phys) },
+            "with fields",
+        ));
+    }
+}
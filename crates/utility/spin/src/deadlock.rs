@@ -0,0 +1,149 @@
+//! Detection of locks held for an implausibly long time.
+//!
+//! This module is only compiled when the `deadlock-detection` feature is enabled. In that case,
+//! every lock remembers where, when and by which CPU it was last acquired. A thread which spins for more than
+//! [`SPIN_THRESHOLD`] iterations while waiting for a lock panics with that information, turning a
+//! silent hang into a diagnosable failure.
+//!
+//! The typical case is an interrupt handler trying to acquire a lock held by the code it
+//! interrupted.
+
+use core::panic::Location;
+use core::ptr::null_mut;
+use core::sync::atomic::Ordering::Relaxed;
+use core::sync::atomic::{AtomicPtr, AtomicU32, AtomicU64};
+
+/// The number of iterations after which a thread waiting for a lock assumes a deadlock.
+///
+/// Each iteration executes a `pause` instruction, which takes between a few and a hundred-odd
+/// cycles depending on the CPU. This amounts to at least a few hundred milliseconds, which no
+/// legitimate critical section of the kernel should come close to.
+#[cfg(not(test))]
+pub const SPIN_THRESHOLD: u64 = 1 << 26;
+
+/// A lower threshold, to keep tests fast.
+///
+/// Tests run as threads of the host, which may be preempted while holding a lock. This must stay
+/// well above the number of iterations a waiting thread can perform in a single time slice.
+#[cfg(test)]
+pub const SPIN_THRESHOLD: u64 = 1 << 20;
+
+/// Returns a timestamp, in arbitrary units.
+#[inline(always)]
+fn timestamp() -> u64 {
+    #[cfg(target_arch = "x86_64")]
+    {
+        // SAFETY:
+        //  The RDTSC instruction is always available on x86_64.
+        unsafe { core::arch::x86_64::_rdtsc() }
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    0
+}
+
+/// Returns an identifier for the CPU running the caller.
+///
+/// On x86_64, this is the initial local APIC ID reported by bits 24 to 31 of EBX for the CPUID
+/// leaf `1`. Executing `CPUID` is slow (and exits to the hypervisor in virtual machines), but this
+/// cost is only paid when the `deadlock-detection` feature is enabled.
+#[inline(always)]
+fn cpu_id() -> u32 {
+    #[cfg(target_arch = "x86_64")]
+    {
+        // SAFETY:
+        //  The CPUID instruction is always available on x86_64.
+        unsafe { core::arch::x86_64::__cpuid(1).ebx >> 24 }
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    0
+}
+
+/// Remembers which call-site last acquired a lock, on which CPU, and when.
+pub struct LockOwner {
+    /// The location at which the lock was acquired, or null if it never was.
+    location: AtomicPtr<Location<'static>>,
+    /// The value of [`cpu_id`] on the CPU which acquired the lock.
+    cpu: AtomicU32,
+    /// The value of [`timestamp`] when the lock was acquired.
+    acquired_at: AtomicU64,
+}
+
+impl LockOwner {
+    /// Creates a new [`LockOwner`] for a lock that was never acquired.
+    pub const fn new() -> Self {
+        Self {
+            location: AtomicPtr::new(null_mut()),
+            cpu: AtomicU32::new(0),
+            acquired_at: AtomicU64::new(0),
+        }
+    }
+
+    /// Records that the lock has just been acquired at `location`.
+    #[inline(always)]
+    pub fn set(&self, location: &'static Location<'static>) {
+        self.acquired_at.store(timestamp(), Relaxed);
+        self.cpu.store(cpu_id(), Relaxed);
+        self.location
+            .store(location as *const Location as *mut Location, Relaxed);
+    }
+
+    /// Reports a probable deadlock detected by a thread waiting at `waiter`.
+    #[cold]
+    pub fn report(&self, waiter: &'static Location<'static>) -> ! {
+        let location = self.location.load(Relaxed);
+        let cpu = self.cpu.load(Relaxed);
+        let held_for = timestamp().wrapping_sub(self.acquired_at.load(Relaxed));
+
+        // SAFETY:
+        //  Only `&'static Location` references are ever stored in `self.location`.
+        match unsafe { location.as_ref() } {
+            Some(location) => panic!(
+                "probable deadlock at {waiter}: the lock was acquired at {location} by CPU {cpu}, \
+                 {held_for} cycles ago"
+            ),
+            None => panic!("probable deadlock at {waiter}: the lock owner is unknown"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Mutex, RwLock};
+
+    #[test]
+    #[should_panic(expected = "the lock was acquired at")]
+    fn held_lock() {
+        let mutex = Mutex::new(0);
+        let _guard = mutex.lock();
+
+        // Spins for `SPIN_THRESHOLD` iterations, then reports the first `lock()` call.
+        let _ = mutex.lock();
+    }
+
+    #[test]
+    #[should_panic(expected = "by CPU")]
+    fn held_write_lock() {
+        let lock = RwLock::new(0);
+        let _guard = lock.write();
+
+        let _ = lock.read();
+    }
+
+    #[test]
+    #[should_panic(expected = "the lock was acquired at")]
+    fn held_read_lock() {
+        let lock = RwLock::new(0);
+        let _guard = lock.read();
+
+        let _ = lock.write();
+    }
+
+    #[test]
+    #[should_panic(expected = "the lock owner is unknown")]
+    fn unknown_owner() {
+        LockOwner::new().report(Location::caller());
+    }
+}
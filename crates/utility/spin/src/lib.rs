@@ -2,6 +2,10 @@
 
 #![no_std]
 
+#[cfg(feature = "deadlock-detection")]
+mod deadlock;
 mod mutex;
+mod rwlock;
 
 pub use self::mutex::*;
+pub use self::rwlock::*;
@@ -4,6 +4,9 @@ use core::ops::{Deref, DerefMut};
 use core::sync::atomic::AtomicBool;
 use core::sync::atomic::Ordering::{Acquire, Relaxed, Release};
 
+#[cfg(feature = "deadlock-detection")]
+use crate::deadlock::LockOwner;
+
 /// A mutually exclusive lock protecting a value of type `T`.'
 ///
 /// # Fairness
@@ -14,7 +17,86 @@ pub struct Mutex<T> {
     /// The protected value.
     value: UnsafeCell<T>,
     /// the current state of the mutex.
-    lock: AtomicBool,
+    lock: RawLock,
+}
+
+/// The state of a [`Mutex<T>`], independent of the protected value.
+struct RawLock {
+    /// Whether the mutex is currently locked.
+    locked: AtomicBool,
+    /// Information about the current owner of the lock.
+    #[cfg(feature = "deadlock-detection")]
+    owner: LockOwner,
+}
+
+impl RawLock {
+    /// Creates a new unlocked [`RawLock`].
+    const fn new() -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            #[cfg(feature = "deadlock-detection")]
+            owner: LockOwner::new(),
+        }
+    }
+
+    /// Attempts to lock, returning whether the lock was acquired.
+    #[inline]
+    #[cfg_attr(feature = "deadlock-detection", track_caller)]
+    fn try_lock(&self) -> bool {
+        if self
+            .locked
+            .compare_exchange(false, true, Acquire, Relaxed)
+            .is_err()
+        {
+            return false;
+        }
+
+        #[cfg(feature = "deadlock-detection")]
+        self.owner.set(core::panic::Location::caller());
+
+        true
+    }
+
+    /// Spins until the lock is acquired.
+    #[inline]
+    #[cfg_attr(feature = "deadlock-detection", track_caller)]
+    fn lock(&self) {
+        #[cfg(feature = "deadlock-detection")]
+        let mut spins = 0u64;
+
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Acquire, Relaxed)
+            .is_err()
+        {
+            // Wait until the lock seems released.
+            while self.locked.load(Relaxed) {
+                #[cfg(feature = "deadlock-detection")]
+                {
+                    spins += 1;
+                    if spins == crate::deadlock::SPIN_THRESHOLD {
+                        self.owner.report(core::panic::Location::caller());
+                    }
+                }
+
+                // On x86_64, this emits the `pause` instruction. Without it, a spinning logical
+                // processor competes for the execution resources of the core it shares with its
+                // SMT sibling, which might be the one holding the lock. It also prevents the
+                // pipeline from being flushed because of a memory-order violation when the lock
+                // is finally released.
+                core::hint::spin_loop();
+            }
+        }
+
+        #[cfg(feature = "deadlock-detection")]
+        self.owner.set(core::panic::Location::caller());
+    }
+
+    /// Releases the lock.
+    #[inline(always)]
+    fn unlock(&self) {
+        self.locked.store(false, Release);
+    }
 }
 
 unsafe impl<T: Send> Send for Mutex<T> {}
@@ -27,7 +109,7 @@ impl<T> Mutex<T> {
     pub const fn new(value: T) -> Self {
         Self {
             value: UnsafeCell::new(value),
-            lock: AtomicBool::new(false),
+            lock: RawLock::new(),
         }
     }
 
@@ -37,17 +119,14 @@ impl<T> Mutex<T> {
     /// time this function returns.
     #[inline(always)]
     pub fn is_locked(&self) -> bool {
-        self.lock.load(Relaxed)
+        self.lock.locked.load(Relaxed)
     }
 
     /// Attempts to lock the mutex, returning `None` if it is already locked.
     #[inline]
+    #[cfg_attr(feature = "deadlock-detection", track_caller)]
     pub fn try_lock(&self) -> Option<MutexLock<T>> {
-        if self
-            .lock
-            .compare_exchange(false, true, Acquire, Relaxed)
-            .is_ok()
-        {
+        if self.lock.try_lock() {
             Some(MutexLock {
                 value: unsafe { &mut *self.value.get() },
                 lock: &self.lock,
@@ -58,18 +137,15 @@ impl<T> Mutex<T> {
     }
 
     /// Locks the mutex and returns a guard that releases the lock when dropped.
+    ///
+    /// # Deadlock Detection
+    ///
+    /// When the `deadlock-detection` feature is enabled, this function panics if it spins for an
+    /// implausibly long time, reporting where the lock was acquired and how long ago.
     #[inline]
+    #[cfg_attr(feature = "deadlock-detection", track_caller)]
     pub fn lock(&self) -> MutexLock<T> {
-        while self
-            .lock
-            .compare_exchange_weak(false, true, Acquire, Relaxed)
-            .is_err()
-        {
-            // Wait until the lock seems released.
-            while self.is_locked() {
-                core::hint::spin_loop();
-            }
-        }
+        self.lock.lock();
 
         MutexLock {
             value: unsafe { &mut *self.value.get() },
@@ -90,7 +166,7 @@ impl<T> Mutex<T> {
 /// Holds a lock on a [`Mutex<T>`], ensuring exclusive access to the protected value.
 pub struct MutexLock<'a, T> {
     value: &'a mut T,
-    lock: &'a AtomicBool,
+    lock: &'a RawLock,
 }
 
 impl<'a, T> MutexLock<'a, T> {
@@ -138,6 +214,6 @@ impl<'a, T> DerefMut for MutexLock<'a, T> {
 impl<'a, T> Drop for MutexLock<'a, T> {
     #[inline(always)]
     fn drop(&mut self) {
-        self.lock.store(false, Release);
+        self.lock.unlock();
     }
 }
@@ -0,0 +1,297 @@
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering::{Acquire, Relaxed, Release};
+
+#[cfg(feature = "deadlock-detection")]
+use crate::deadlock::LockOwner;
+
+/// The bit of [`RwLock::state`] set when a writer holds the lock.
+const WRITER: usize = 1;
+
+/// The amount added to [`RwLock::state`] by each reader holding the lock.
+const READER: usize = 2;
+
+/// A reader-writer lock protecting a value of type `T`.
+///
+/// Any number of readers may hold the lock at the same time, while writers have exclusive access
+/// to the protected value.
+///
+/// # Fairness
+///
+/// This lock is *not* fair. Notably, a continuous stream of readers may prevent a writer from
+/// ever acquiring the lock. It is meant for values which are read often and rarely modified.
+///
+/// # Deadlock Detection
+///
+/// When the `deadlock-detection` feature is enabled, [`RwLock::read`] and [`RwLock::write`] panic
+/// if they spin for an implausibly long time, like [`Mutex::lock`](crate::Mutex::lock). Because
+/// readers share the lock, only the last reader or writer to acquire it is reported.
+pub struct RwLock<T> {
+    /// The protected value.
+    value: UnsafeCell<T>,
+    /// The state of the lock.
+    ///
+    /// The [`WRITER`] bit is set when a writer holds the lock. The remaining bits count the
+    /// readers currently holding the lock.
+    state: AtomicUsize,
+    /// Information about the last reader or writer which acquired the lock.
+    #[cfg(feature = "deadlock-detection")]
+    owner: LockOwner,
+}
+
+unsafe impl<T: Send> Send for RwLock<T> {}
+unsafe impl<T: Send + Sync> Sync for RwLock<T> {}
+
+impl<T> RwLock<T> {
+    /// Creates a new [`RwLock<T>`] with the given value.
+    ///
+    /// The lock is initially unlocked.
+    pub const fn new(value: T) -> Self {
+        Self {
+            value: UnsafeCell::new(value),
+            state: AtomicUsize::new(0),
+            #[cfg(feature = "deadlock-detection")]
+            owner: LockOwner::new(),
+        }
+    }
+
+    /// Attempts to acquire a shared lock, returning `None` if a writer currently holds the lock.
+    #[inline]
+    #[cfg_attr(feature = "deadlock-detection", track_caller)]
+    pub fn try_read(&self) -> Option<RwLockReadGuard<T>> {
+        let mut state = self.state.load(Relaxed);
+
+        loop {
+            if state & WRITER != 0 {
+                return None;
+            }
+
+            match self
+                .state
+                .compare_exchange_weak(state, state + READER, Acquire, Relaxed)
+            {
+                Ok(_) => {
+                    #[cfg(feature = "deadlock-detection")]
+                    self.owner.set(core::panic::Location::caller());
+
+                    return Some(RwLockReadGuard {
+                        value: unsafe { &*self.value.get() },
+                        state: &self.state,
+                    });
+                }
+                Err(actual) => state = actual,
+            }
+        }
+    }
+
+    /// Acquires a shared lock, spinning until no writer holds the lock.
+    #[inline]
+    #[cfg_attr(feature = "deadlock-detection", track_caller)]
+    pub fn read(&self) -> RwLockReadGuard<T> {
+        loop {
+            if let Some(guard) = self.try_read() {
+                return guard;
+            }
+
+            // Only the time spent waiting for a single writer counts.
+            #[cfg(feature = "deadlock-detection")]
+            let mut spins = 0u64;
+
+            while self.state.load(Relaxed) & WRITER != 0 {
+                #[cfg(feature = "deadlock-detection")]
+                {
+                    spins += 1;
+                    if spins == crate::deadlock::SPIN_THRESHOLD {
+                        self.owner.report(core::panic::Location::caller());
+                    }
+                }
+
+                core::hint::spin_loop();
+            }
+        }
+    }
+
+    /// Attempts to acquire an exclusive lock, returning `None` if the lock is already held by a
+    /// reader or a writer.
+    #[inline]
+    #[cfg_attr(feature = "deadlock-detection", track_caller)]
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<T>> {
+        if self
+            .state
+            .compare_exchange(0, WRITER, Acquire, Relaxed)
+            .is_err()
+        {
+            return None;
+        }
+
+        #[cfg(feature = "deadlock-detection")]
+        self.owner.set(core::panic::Location::caller());
+
+        Some(RwLockWriteGuard {
+            value: unsafe { &mut *self.value.get() },
+            state: &self.state,
+        })
+    }
+
+    /// Acquires an exclusive lock, spinning until nobody else holds the lock.
+    #[inline]
+    #[cfg_attr(feature = "deadlock-detection", track_caller)]
+    pub fn write(&self) -> RwLockWriteGuard<T> {
+        while self
+            .state
+            .compare_exchange_weak(0, WRITER, Acquire, Relaxed)
+            .is_err()
+        {
+            // Only the time spent waiting for the lock to be released once counts.
+            #[cfg(feature = "deadlock-detection")]
+            let mut spins = 0u64;
+
+            // Wait until the lock seems released. See `Mutex::lock` for why this uses a
+            // spin-loop hint.
+            while self.state.load(Relaxed) != 0 {
+                #[cfg(feature = "deadlock-detection")]
+                {
+                    spins += 1;
+                    if spins == crate::deadlock::SPIN_THRESHOLD {
+                        self.owner.report(core::panic::Location::caller());
+                    }
+                }
+
+                core::hint::spin_loop();
+            }
+        }
+
+        #[cfg(feature = "deadlock-detection")]
+        self.owner.set(core::panic::Location::caller());
+
+        RwLockWriteGuard {
+            value: unsafe { &mut *self.value.get() },
+            state: &self.state,
+        }
+    }
+
+    /// Returns the inner value without locking.
+    ///
+    /// This is safe because the lock must be exclusively borrowed to call this function, which
+    /// ensures that no guard exists for it.
+    #[inline(always)]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+/// Holds a shared lock on a [`RwLock<T>`].
+pub struct RwLockReadGuard<'a, T> {
+    value: &'a T,
+    state: &'a AtomicUsize,
+}
+
+impl<'a, T> Deref for RwLockReadGuard<'a, T> {
+    type Target = T;
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+
+impl<'a, T> Drop for RwLockReadGuard<'a, T> {
+    #[inline(always)]
+    fn drop(&mut self) {
+        self.state.fetch_sub(READER, Release);
+    }
+}
+
+/// Holds an exclusive lock on a [`RwLock<T>`].
+pub struct RwLockWriteGuard<'a, T> {
+    value: &'a mut T,
+    state: &'a AtomicUsize,
+}
+
+impl<'a, T> Deref for RwLockWriteGuard<'a, T> {
+    type Target = T;
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+
+impl<'a, T> DerefMut for RwLockWriteGuard<'a, T> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.value
+    }
+}
+
+impl<'a, T> Drop for RwLockWriteGuard<'a, T> {
+    #[inline(always)]
+    fn drop(&mut self) {
+        // Readers never register themselves while the writer bit is set, so the state is
+        // exactly `WRITER` here.
+        self.state.store(0, Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+    use std::vec::Vec;
+
+    #[test]
+    fn exclusion() {
+        let lock = RwLock::new(0);
+
+        let a = lock.read();
+        let b = lock.try_read().unwrap();
+        assert!(lock.try_write().is_none());
+        drop((a, b));
+
+        let w = lock.try_write().unwrap();
+        assert!(lock.try_read().is_none());
+        assert!(lock.try_write().is_none());
+        drop(w);
+
+        *lock.write() = 5;
+        assert_eq!(*lock.read(), 5);
+    }
+
+    #[test]
+    fn concurrent_readers_and_writer() {
+        const WRITES: usize = 10_000;
+
+        // The writer always updates both values together. Readers must never observe them
+        // out of sync.
+        let lock = Arc::new(RwLock::new((0usize, 0usize)));
+
+        let readers: Vec<_> = (0..8)
+            .map(|_| {
+                let lock = lock.clone();
+                thread::spawn(move || loop {
+                    let guard = lock.read();
+                    assert_eq!(guard.0, guard.1);
+                    if guard.0 == WRITES {
+                        break;
+                    }
+                })
+            })
+            .collect();
+
+        for _ in 0..WRITES {
+            let mut guard = lock.write();
+            guard.0 += 1;
+            guard.1 += 1;
+        }
+
+        for reader in readers {
+            reader.join().unwrap();
+        }
+
+        assert_eq!(*lock.read(), (WRITES, WRITES));
+    }
+}